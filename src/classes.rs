@@ -0,0 +1,535 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style,Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Cell,Block,Clear,Paragraph,StatefulWidget,Row,Table,TableState,Borders},
+};
+use ratatui::prelude::*;
+use ratatui::layout::Constraint;
+
+use crate::cups::{
+    error::CupsError,
+    printer::{self, Printer, PrinterState},
+};
+
+use crate::utils::{
+    TUIMode,
+    ClassBlock,
+    Status,
+    popup_area,
+    edit_text,
+};
+
+use crate::App;
+use crate::confirm::{Confirm, ConfirmAction};
+use crate::keymap::{self, Action, Context, Keymap};
+use crate::theme::Theme;
+use crossterm::event::KeyEvent;
+
+/// The printers offered to a new or existing class, ticked ones becoming members.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemberPicker {
+    /// The class gaining members, `None` when creating one named `name`.
+    pub class: Option<String>,
+    pub name: String,
+    /// Whether keys go to the name field rather than the printer list.
+    pub naming: bool,
+    pub printers: Vec<String>,
+    pub checked: Vec<bool>,
+    pub selected: usize,
+}
+
+impl MemberPicker {
+    fn toggle(&mut self) {
+        if let Some(checked) = self.checked.get_mut(self.selected) {
+            *checked = !*checked;
+        }
+    }
+
+    fn next(&mut self) {
+        if !self.printers.is_empty() {
+            self.selected = (self.selected + 1) % self.printers.len();
+        }
+    }
+
+    fn previous(&mut self) {
+        if !self.printers.is_empty() {
+            self.selected = (self.selected + self.printers.len() - 1) % self.printers.len();
+        }
+    }
+
+    fn picked(&self) -> Vec<String> {
+        self.printers.iter()
+            .zip(&self.checked)
+            .filter(|(_, checked)| **checked)
+            .map(|(printer, _)| printer.clone())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct Classes<'a> {
+    pub state: TableState,
+    pub members_state: TableState,
+    pub picker_state: TableState,
+    pub classes: Vec<&'a Printer>,
+    pub printers: &'a Vec<Printer>,
+    pub selected_class: usize,
+    pub selected_block: ClassBlock,
+    pub picker: Option<&'a MemberPicker>,
+    /// Why the new class name can't be used, if it can't.
+    pub name_error: Option<String>,
+    pub keymap: &'a Keymap,
+    pub theme: &'a Theme,
+    pub help: bool,
+}
+
+impl<'a> Classes<'a> {
+    pub fn new(app: &'a App) -> Self {
+        Classes {
+            state: TableState::default()
+                .with_selected(app.selected_class),
+            members_state: TableState::default()
+                .with_selected(if app.selected_class_block == ClassBlock::Members {Some(app.selected_member)} else {None}),
+            picker_state: TableState::default()
+                .with_selected(app.member_picker.as_ref().map(|picker| picker.selected)),
+            classes: Classes::classes(app),
+            printers: &app.printers,
+            selected_class: app.selected_class,
+            selected_block: app.selected_class_block,
+            picker: app.member_picker.as_ref(),
+            name_error: app.member_picker.as_ref()
+                .filter(|picker| picker.class.is_none())
+                .and_then(|picker| Classes::validate_name(app, &picker.name).err())
+                .map(|error| error.to_string()),
+            keymap: &app.config.keymap,
+            theme: &app.config.palette,
+            help: app.help,
+        }
+    }
+
+    fn classes(app: &App) -> Vec<&Printer> {
+        app.printers.iter().filter(|printer| printer.is_class).collect()
+    }
+
+    fn current_class(app: &App) -> Option<&Printer> {
+        Classes::classes(app).into_iter().nth(app.selected_class)
+    }
+
+    fn validate_name(app: &App, name: &str) -> Result<(), CupsError> {
+        printer::validate_name(name, app.printers.iter().map(|printer| printer.name.as_str()))
+    }
+
+    pub fn open(app:&mut App) {
+        app.selected_class = 0;
+        app.selected_member = 0;
+        app.selected_class_block = ClassBlock::Classes;
+        app.member_picker = None;
+        app.change_mode(TUIMode::Classes);
+    }
+
+    pub fn handle_events(app:&mut App, key_event: KeyEvent) {
+        if app.member_picker.is_some() {
+            Classes::handle_picker(app,key_event);
+            return;
+        }
+        match app.selected_class_block {
+            ClassBlock::Classes => Classes::handle_classes_block(app,key_event),
+            ClassBlock::Members => Classes::handle_members_block(app,key_event),
+        }
+    }
+
+    fn handle_classes_block(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.action(key_event, keymap::CLASSES) {
+            Some(Action::Up) => Classes::previous_class(app),
+            Some(Action::Down) => Classes::next_class(app),
+            Some(Action::Right | Action::NextField) if Classes::current_class(app).is_some_and(|class| !class.members.is_empty()) => {
+                app.selected_member = 0;
+                app.selected_class_block = ClassBlock::Members;
+            }
+            Some(Action::NewClass) => Classes::open_picker(app, None),
+            Some(Action::Add) => {
+                if let Some(class) = Classes::current_class(app).map(|class| class.name.clone()) {
+                    Classes::open_picker(app, Some(class));
+                }
+            }
+            Some(Action::Remove) => Classes::confirm_remove(app),
+            Some(Action::Refresh) => app.refresh_printers(),
+            Some(Action::Back) => app.change_mode(TUIMode::View),
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
+            _ => {}
+        }
+    }
+
+    fn handle_members_block(app:&mut App, key_event: KeyEvent) {
+        let count = Classes::current_class(app).map_or(0, |class| class.members.len());
+        match app.config.keymap.action(key_event, keymap::MEMBERS) {
+            Some(Action::Up) if count > 0 => {
+                app.selected_member = if app.selected_member == 0 { count - 1 } else { app.selected_member - 1 };
+            }
+            Some(Action::Down) if count > 0 => {
+                app.selected_member = if app.selected_member >= count - 1 { 0 } else { app.selected_member + 1 };
+            }
+            Some(Action::RemoveMember) => Classes::remove_member(app),
+            Some(Action::Left | Action::PreviousField | Action::Back) => app.selected_class_block = ClassBlock::Classes,
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
+            _ => {}
+        }
+    }
+
+    fn handle_picker(app:&mut App, key_event: KeyEvent) {
+        let Some(picker) = app.member_picker.as_mut() else {
+            return;
+        };
+        let action = if picker.naming {
+            app.config.keymap.text_action(key_event, keymap::MEMBER_PICKER)
+        } else {
+            app.config.keymap.action(key_event, keymap::MEMBER_PICKER)
+        };
+        match action {
+            Some(Action::Back) => app.member_picker = None,
+            Some(Action::Select) => Classes::apply_picker(app),
+            Some(Action::NextField | Action::PreviousField) if picker.class.is_none() => picker.naming = !picker.naming,
+            Some(Action::Up) => picker.previous(),
+            Some(Action::Down) => picker.next(),
+            Some(Action::Pick) => picker.toggle(),
+            None if picker.naming => {
+                edit_text(&mut picker.name, key_event);
+            }
+            _ => {}
+        }
+    }
+
+    /// Offers every printer not already in `class`, the one selected in the
+    /// Printers view ticked when creating a class.
+    fn open_picker(app:&mut App, class: Option<String>) {
+        let members = class.as_ref()
+            .and_then(|class| app.printers.iter().find(|printer| &printer.name == class))
+            .map_or(vec![], |class| class.members.clone());
+        let printers: Vec<String> = app.printers.iter()
+            .filter(|printer| !printer.is_class && !members.contains(&printer.name))
+            .map(|printer| printer.name.clone())
+            .collect();
+        if printers.is_empty() {
+            app.status = Some(Status::Info("No printers left to add".to_string()));
+            return;
+        }
+        let current = app.printers.get(app.selected_printer).map(|printer| printer.name.as_str());
+        let checked = printers.iter()
+            .map(|printer| class.is_none() && Some(printer.as_str()) == current)
+            .collect();
+        app.member_picker = Some(MemberPicker {
+            naming: class.is_none(),
+            class,
+            name: String::new(),
+            printers,
+            checked,
+            selected: 0,
+        });
+    }
+
+    /// Adds the ticked printers one by one, `lpadmin` creating the class with the first.
+    fn apply_picker(app:&mut App) {
+        let Some(picker) = app.member_picker.clone() else {
+            return;
+        };
+        let class = match &picker.class {
+            Some(class) => class.clone(),
+            None => {
+                if let Err(error) = Classes::validate_name(app, &picker.name) {
+                    app.status = Some(Status::Error(error.to_string()));
+                    return;
+                }
+                picker.name.clone()
+            }
+        };
+        let picked = picker.picked();
+        if picked.is_empty() {
+            app.status = Some(Status::Error(format!("Pick at least one printer with {}", app.config.keymap.key(Action::Pick))));
+            return;
+        }
+        app.member_picker = None;
+        let added = picked.iter()
+            .try_for_each(|printer| app.backend.add_class_member(&class, printer));
+        if app.report(added).is_some() {
+            app.status = Some(Status::Info(match picker.class {
+                Some(_) => format!("{} added to {}", picked.join(", "), class),
+                None => format!("Class {} created with {}", class, picked.join(", ")),
+            }));
+        }
+        app.refresh_printers();
+        app.selected_class = Classes::classes(app).iter()
+            .position(|printer| printer.name == class)
+            .unwrap_or(0);
+    }
+
+    fn remove_member(app:&mut App) {
+        let Some((class, member, last)) = Classes::current_class(app).and_then(|class| {
+            let member = class.members.get(app.selected_member)?;
+            Some((class.name.clone(), member.clone(), class.members.len() == 1))
+        }) else {
+            return;
+        };
+        let removed = app.backend.remove_class_member(&class, &member);
+        if app.report(removed).is_some() {
+            app.status = Some(Status::Info(if last {
+                format!("{} removed from {}, which is gone now it's empty", member, class)
+            } else {
+                format!("{} removed from {}", member, class)
+            }));
+        }
+        app.refresh_printers();
+        Classes::clamp_selection(app);
+    }
+
+    fn confirm_remove(app:&mut App) {
+        let Some(class) = Classes::current_class(app) else {
+            return;
+        };
+        app.confirm = Some(Confirm::new(
+            format!("Remove {}", class.name),
+            format!("Remove the class {}? Its printers stay. It can be added back with {} in the Printers view until you quit.", class.name, app.config.keymap.key(Action::Undo)),
+            ConfirmAction::RemoveClass(class.name.clone()),
+        ));
+    }
+
+    /// Removes the class, keeping its members so undo can gather them again.
+    pub fn remove_class(app:&mut App, name: &str) {
+        let snapshot = app.printers.iter().find(|printer| printer.name == name).cloned();
+        let removed = app.backend.remove_class(name);
+        if app.report(removed).is_some() {
+            app.status = Some(Status::Info(format!("Class {} removed", name)));
+            app.removed_printers.extend(snapshot);
+        }
+        app.refresh_printers();
+        Classes::clamp_selection(app);
+        if app.selected_printer >= app.printers.len() {
+            app.selected_printer = app.printers.len().saturating_sub(1);
+        }
+    }
+
+    fn clamp_selection(app:&mut App) {
+        let count = Classes::classes(app).len();
+        if app.selected_class >= count {
+            app.selected_class = count.saturating_sub(1);
+        }
+        let members = Classes::current_class(app).map_or(0, |class| class.members.len());
+        if members == 0 {
+            app.selected_class_block = ClassBlock::Classes;
+        }
+        if app.selected_member >= members {
+            app.selected_member = members.saturating_sub(1);
+        }
+    }
+
+    fn next_class(app:&mut App) {
+        let count = Classes::classes(app).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_class = if app.selected_class >= count - 1 { 0 } else { app.selected_class + 1 };
+        app.selected_member = 0;
+    }
+
+    fn previous_class(app:&mut App) {
+        let count = Classes::classes(app).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_class = if app.selected_class == 0 { count - 1 } else { app.selected_class - 1 };
+        app.selected_member = 0;
+    }
+
+    fn classes_to_rows(&self) -> Vec<Row<'static>> {
+        self.classes
+            .iter()
+            .map(|class| {
+                Row::new(vec![
+                    Cell::from(class.name.clone()),
+                    Cell::from(class.state.to_string()),
+                    Cell::from(class.members.len().to_string()),
+                    Cell::from(class.description.clone()),
+                ])
+                .style(self.theme.printer_state(class.state))
+            })
+            .collect()
+    }
+
+    fn members_to_rows(&self) -> Vec<Row<'static>> {
+        let Some(class) = self.classes.get(self.selected_class) else {
+            return vec![];
+        };
+        class.members
+            .iter()
+            .map(|member| {
+                let printer = self.printers.iter().find(|printer| &printer.name == member);
+                let state = printer.map_or(PrinterState::Unknown, |printer| printer.state);
+                Row::new(vec![
+                    Cell::from(member.clone()),
+                    Cell::from(state.to_string()),
+                    Cell::from(printer.map_or(String::new(), |printer| printer.location.clone())),
+                ])
+                .style(self.theme.printer_state(state))
+            })
+            .collect()
+    }
+
+    /// The keys the pane or picker in front answers, and whether it takes typed text.
+    fn context(&self) -> (Context, bool) {
+        match (self.picker, self.selected_block) {
+            (Some(picker), _) => (keymap::MEMBER_PICKER, picker.naming),
+            (None, ClassBlock::Classes) => (keymap::CLASSES, false),
+            (None, ClassBlock::Members) => (keymap::MEMBERS, false),
+        }
+    }
+
+    fn get_instructions(&self, width: u16) -> Line<'static> {
+        let (context, typing) = self.context();
+        let creating = self.picker.is_some_and(|picker| picker.class.is_none());
+        let entries: Vec<(Action, &str)> = context.iter()
+            .filter(|(action, _)| creating || *action != Action::NextField)
+            .map(|(action, label)| match action {
+                Action::Select if !creating => (*action, "Add"),
+                _ => (*action, *label),
+            })
+            .collect();
+        self.keymap.hints(self.theme, &entries, typing, width)
+    }
+
+    fn render_picker(&mut self, picker: &MemberPicker, area: Rect, buf: &mut Buffer) {
+        let naming = picker.class.is_none();
+        let title = Line::from(match &picker.class {
+            Some(class) => format!(" Add printers to {} ", class),
+            None => " New class ".to_string(),
+        }.bold());
+        let block = Block::bordered()
+            .title(title.centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.popup());
+        let name_height = if naming {3} else {0};
+        let popup = popup_area(area, 50, picker.printers.len() as u16 + name_height + 2);
+        Clear.render(popup, buf);
+        let inner = block.inner(popup);
+        block.render(popup, buf);
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(name_height),
+                Constraint::Min(0),
+            ])
+            .split(inner);
+
+        if naming {
+            let mut name_block = Block::bordered()
+                .title(Line::from(" Name ".fg(self.theme.text).bold()))
+                .border_style(self.theme.border(picker.naming));
+            if let Some(error) = &self.name_error && !picker.name.is_empty() {
+                name_block = name_block.title_bottom(Line::from(Span::styled(format!(" {} ", error), self.theme.error())));
+            }
+            Paragraph::new(picker.name.as_str())
+                .block(name_block)
+                .render(layout[0], buf);
+        }
+
+        let rows: Vec<Row> = picker.printers
+            .iter()
+            .zip(&picker.checked)
+            .map(|(printer, checked)| Row::new(vec![
+                Cell::from(if *checked {"[x]"} else {"[ ]"}),
+                Cell::from(printer.clone()),
+            ]))
+            .collect();
+        let table = Table::new(rows, [Constraint::Length(3), Constraint::Percentage(100)])
+            .row_highlight_style(if picker.naming {Style::new()} else {self.theme.highlight()})
+            .highlight_symbol("  ");
+        StatefulWidget::render(table, layout[1], buf, &mut self.picker_state);
+    }
+
+    pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
+        let instructions = self.get_instructions(area.width.saturating_sub(2));
+        let title = Line::from(" Classes ".fg(self.theme.text).bold());
+        let outer_block = Block::default().borders(Borders::ALL)
+            .title(title.centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+        let inner_area = outer_block.inner(area);
+        outer_block.render(area, buf);
+        let layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Percentage(55),
+                Constraint::Percentage(45),
+            ])
+            .split(inner_area);
+
+        let block = Block::bordered()
+            .title(Line::from(" Classes ".fg(self.theme.text).bold()))
+            .border_set(border::THICK)
+            .border_style(self.theme.border(self.selected_block == ClassBlock::Classes));
+        let header = Row::new(vec![
+                Cell::from("Name"),
+                Cell::from("State"),
+                Cell::from("Members"),
+                Cell::from("Description"),
+            ])
+            .style(Style::new().bold());
+        let widths = [
+            Constraint::Percentage(30),
+            Constraint::Percentage(20),
+            Constraint::Length(8),
+            Constraint::Percentage(50),
+        ];
+        let table = Table::new(self.classes_to_rows(), widths)
+            .header(header)
+            .column_spacing(1)
+            .row_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
+            .block(block);
+        StatefulWidget::render(table, layout[0], buf, &mut self.state);
+
+        if self.classes.is_empty() {
+            Paragraph::new(Line::from(format!(" No classes yet, create one with {} ", self.keymap.key(Action::NewClass)).fg(self.theme.text)))
+                .centered()
+                .render(Block::bordered().inner(layout[0]).inner(Margin::new(0, 2)), buf);
+        }
+
+        let title = match self.classes.get(self.selected_class) {
+            Some(class) => format!(" Members of {} ", class.name),
+            None => " Members ".to_string(),
+        };
+        let block = Block::bordered()
+            .title(Line::from(title.fg(self.theme.text).bold()).centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.border(self.selected_block == ClassBlock::Members));
+        let header = Row::new(vec![
+                Cell::from("Printer"),
+                Cell::from("State"),
+                Cell::from("Location"),
+            ])
+            .style(Style::new().bold());
+        let widths = [
+            Constraint::Percentage(40),
+            Constraint::Percentage(20),
+            Constraint::Percentage(40),
+        ];
+        let table = Table::new(self.members_to_rows(), widths)
+            .header(header)
+            .column_spacing(1)
+            .row_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
+            .block(block);
+        StatefulWidget::render(table, layout[1], buf, &mut self.members_state);
+
+        if let Some(picker) = self.picker {
+            self.render_picker(picker, area, buf);
+        }
+
+        if self.help {
+            self.keymap.render_help(self.theme, self.context().0, area, buf);
+        }
+    }
+}
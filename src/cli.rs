@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::cups::{
+    self,
+    driver::Driver,
+    error::CupsError,
+    printer,
+    uri,
+};
+use crate::utils::fuzzy_match;
+
+pub const USAGE: &str = "\
+Usage: printer-tui [COMMAND]
+
+Without a command, opens the TUI.
+
+Commands:
+  list [--json]                   List printers and classes
+  add NAME --uri URI --driver PPD [--description TEXT] [--location TEXT]
+                                  Add a printer
+  remove NAME                     Remove a printer or class
+  devices [--json]                Discover devices, taking the discovery timeout
+  drivers [--search TEXT] [--json]
+                                  List drivers, best matches first when searching
+  help                            Show this help
+
+Exit status: 0 on success, 1 when CUPS fails, 2 on bad arguments.
+";
+
+/// Exit status when a command fails or the config can't be loaded.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status for bad arguments.
+pub const EXIT_USAGE: i32 = 2;
+
+/// Runs the command in `args` with the config `load_config` reads, returning
+/// the exit status, `None` when there is no command and the TUI should open.
+pub fn run<E: Display>(args: impl Iterator<Item = String>, load_config: impl FnOnce() -> Result<Config, E>) -> Option<i32> {
+    let command = match Command::parse(args) {
+        Ok(command) => command?,
+        Err(error) => {
+            eprintln!("printer-tui: {}\n\n{}", error, USAGE);
+            return Some(EXIT_USAGE);
+        }
+    };
+    if command == Command::Help {
+        print!("{}", USAGE);
+        return Some(0);
+    }
+    let result = match load_config() {
+        Ok(config) => command.run(&config).map_err(|error| error.to_string()),
+        Err(error) => Err(error.to_string()),
+    };
+    match result {
+        Ok(()) => Some(0),
+        Err(error) => {
+            eprintln!("printer-tui: {}", error);
+            Some(EXIT_FAILURE)
+        }
+    }
+}
+
+/// What printer-tui was asked to do from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    List { json: bool },
+    Add {
+        name: String,
+        uri: String,
+        driver: String,
+        description: String,
+        location: String,
+    },
+    Remove { name: String },
+    Devices { json: bool },
+    Drivers { search: String, json: bool },
+    Help,
+}
+
+impl Command {
+    /// Reads the arguments after the program name, `None` when there are
+    /// none and the TUI should open.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Command>, String> {
+        let Some(command) = args.next() else {
+            return Ok(None);
+        };
+        let command = match command.as_str() {
+            "list" => {
+                let args = Args::parse(args, &[], &["--json"])?;
+                args.no_more()?;
+                Command::List { json: args.flag("--json") }
+            }
+            "add" => {
+                let mut args = Args::parse(args, &["--uri", "--driver", "--description", "--location"], &[])?;
+                let command = Command::Add {
+                    name: args.positional("NAME")?,
+                    uri: args.required("--uri")?,
+                    driver: args.required("--driver")?,
+                    description: args.value("--description"),
+                    location: args.value("--location"),
+                };
+                args.no_more()?;
+                command
+            }
+            "remove" => {
+                let mut args = Args::parse(args, &[], &[])?;
+                let command = Command::Remove { name: args.positional("NAME")? };
+                args.no_more()?;
+                command
+            }
+            "devices" => {
+                let args = Args::parse(args, &[], &["--json"])?;
+                args.no_more()?;
+                Command::Devices { json: args.flag("--json") }
+            }
+            "drivers" => {
+                let mut args = Args::parse(args, &["--search"], &["--json"])?;
+                args.no_more()?;
+                Command::Drivers { search: args.value("--search"), json: args.flag("--json") }
+            }
+            "help" | "--help" | "-h" => Command::Help,
+            _ => return Err(format!("unknown command \"{}\"", command)),
+        };
+        Ok(Some(command))
+    }
+
+    /// Runs the command against the backend of `config`, printing to stdout.
+    pub fn run(self, config: &Config) -> Result<(), CupsError> {
+        let backend = cups::backend(config.cups.backend, &config.cups.server);
+        match self {
+            Command::List { json } => {
+                let mut printers = backend.get_all_printers()?;
+                for printer in &mut printers {
+                    printer.device_uri = uri::mask_credentials(&printer.device_uri);
+                }
+                if json {
+                    print_json(&printers);
+                    return Ok(());
+                }
+                print_table(["NAME", "STATE", "ACCEPTING", "DEVICE", "LOCATION"], printers.iter().map(|printer| [
+                    if printer.is_class { format!("{} [class]", printer.name) } else { printer.name.clone() },
+                    printer.state.to_string(),
+                    if printer.accepting { "accepting" } else { "rejecting" }.to_string(),
+                    if printer.is_class { printer.members.join(",") } else { printer.device_uri.clone() },
+                    printer.location.clone(),
+                ]));
+            }
+            Command::Add { name, uri, driver, description, location } => {
+                let printers = backend.get_all_printers()?;
+                printer::validate_name(&name, printers.iter().map(|printer| printer.name.as_str()))?;
+                uri::validate(&uri)?;
+                backend.create_printer(&name, &uri, &driver, &description, &location)?;
+                println!("Printer {} added", name);
+            }
+            Command::Remove { name } => {
+                backend.remove_printer(&name)?;
+                println!("Printer {} removed", name);
+            }
+            Command::Devices { json } => {
+                let options = config.discovery.clone().with_env();
+                let mut devices = vec![];
+                backend.discover_devices(&options, &mut |mut device| {
+                    device.uri = uri::mask_credentials(&device.uri);
+                    devices.push(device);
+                    true
+                })?;
+                if json {
+                    print_json(&devices);
+                    return Ok(());
+                }
+                print_table(["URI", "CLASS", "NAME"], devices.iter().map(|device| [
+                    device.uri.clone(),
+                    device.class.clone(),
+                    device.name().to_string(),
+                ]));
+            }
+            Command::Drivers { search, json } => {
+                let mut matches: Vec<(i64, Driver)> = backend.get_all_drivers()?
+                    .into_iter()
+                    .filter_map(|driver| fuzzy_match(&search, &driver.search_text()).map(|(score, _)| (score, driver)))
+                    .collect();
+                if !search.is_empty() {
+                    matches.sort_by_key(|(score, _)| -score);
+                }
+                let drivers: Vec<_> = matches.into_iter().map(|(_, driver)| driver).collect();
+                if json {
+                    print_json(&drivers);
+                    return Ok(());
+                }
+                print_table(["PPD", "TYPE", "MODEL"], drivers.iter().map(|driver| [
+                    driver.ppd_name.clone(),
+                    driver.driver_type.to_string(),
+                    driver.make_and_model.clone(),
+                ]));
+            }
+            Command::Help => print!("{}", USAGE),
+        }
+        Ok(())
+    }
+}
+
+/// The arguments after a command: `--option VALUE` or `--option=VALUE` for
+/// the options it takes, bare flags, and positionals in order.
+#[derive(Debug, Default)]
+struct Args {
+    positionals: Vec<String>,
+    values: BTreeMap<String, String>,
+    flags: Vec<String>,
+}
+
+impl Args {
+    fn parse(mut args: impl Iterator<Item = String>, options: &[&str], flags: &[&str]) -> Result<Args, String> {
+        let mut parsed = Args::default();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                parsed.positionals.push(arg);
+                continue;
+            }
+            let (option, inline) = match arg.split_once('=') {
+                Some((option, value)) => (option.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            if flags.contains(&option.as_str()) && inline.is_none() {
+                parsed.flags.push(option);
+            } else if options.contains(&option.as_str()) {
+                let value = inline.or_else(|| args.next())
+                    .ok_or_else(|| format!("{} needs a value", option))?;
+                parsed.values.insert(option, value);
+            } else {
+                return Err(format!("unknown option \"{}\"", option));
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn flag(&self, flag: &str) -> bool {
+        self.flags.iter().any(|given| given == flag)
+    }
+
+    /// The value of `option`, empty when it wasn't given.
+    fn value(&mut self, option: &str) -> String {
+        self.values.remove(option).unwrap_or_default()
+    }
+
+    fn required(&mut self, option: &str) -> Result<String, String> {
+        self.values.remove(option).ok_or_else(|| format!("missing {}", option))
+    }
+
+    /// The next positional, named `name` in errors.
+    fn positional(&mut self, name: &str) -> Result<String, String> {
+        if self.positionals.is_empty() {
+            return Err(format!("missing {}", name));
+        }
+        Ok(self.positionals.remove(0))
+    }
+
+    /// Fails on positionals the command didn't take.
+    fn no_more(&self) -> Result<(), String> {
+        match self.positionals.first() {
+            Some(extra) => Err(format!("unexpected argument \"{}\"", extra)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Prints the models as JSON, which can't fail for their strings, numbers and lists.
+fn print_json(value: &impl Serialize) {
+    write_out(serde_json::to_string_pretty(value).expect("models serialize to JSON") + "\n");
+}
+
+/// Prints `rows` in columns as wide as their longest value.
+fn print_table<const N: usize>(header: [&str; N], rows: impl Iterator<Item = [String; N]>) {
+    let rows: Vec<[String; N]> = std::iter::once(header.map(str::to_string)).chain(rows).collect();
+    let mut widths = [0; N];
+    for row in &rows {
+        for (width, value) in widths.iter_mut().zip(row) {
+            *width = (*width).max(value.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let line = row.iter()
+            .zip(widths)
+            .map(|(value, width)| format!("{:width$}", value, width = width))
+            .collect::<Vec<String>>()
+            .join("  ");
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    write_out(table);
+}
+
+/// Writes to stdout, stopping quietly when the reader goes away like `head` does.
+fn write_out(text: String) {
+    if let Err(error) = io::stdout().write_all(text.as_bytes())
+        && error.kind() != io::ErrorKind::BrokenPipe
+    {
+        eprintln!("printer-tui: {}", error);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CupsConfig;
+    use crate::cups::BackendKind;
+
+    fn parse(args: &[&str]) -> Result<Option<Command>, String> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn run_with(args: &[&str], config: Result<Config, &str>) -> Option<i32> {
+        run(args.iter().map(|arg| arg.to_string()), || config)
+    }
+
+    fn fake_config() -> Config {
+        Config {
+            cups: CupsConfig {
+                backend: BackendKind::Fake,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_arguments_open_the_tui() {
+        assert_eq!(parse(&[]), Ok(None));
+    }
+
+    #[test]
+    fn add_takes_separate_and_inline_values() {
+        assert_eq!(
+            parse(&["add", "Lab", "--uri", "socket://10.0.0.5", "--driver=everywhere", "--location=Floor 2"]),
+            Ok(Some(Command::Add {
+                name: "Lab".to_string(),
+                uri: "socket://10.0.0.5".to_string(),
+                driver: "everywhere".to_string(),
+                description: String::new(),
+                location: "Floor 2".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn flags_and_search_are_read() {
+        assert_eq!(parse(&["list", "--json"]), Ok(Some(Command::List { json: true })));
+        assert_eq!(parse(&["devices"]), Ok(Some(Command::Devices { json: false })));
+        assert_eq!(
+            parse(&["drivers", "--json", "--search", "laser jet"]),
+            Ok(Some(Command::Drivers { search: "laser jet".to_string(), json: true }))
+        );
+        assert_eq!(parse(&["--help"]), Ok(Some(Command::Help)));
+    }
+
+    #[test]
+    fn bad_arguments_are_refused() {
+        for (args, error) in [
+            (&["print"][..], "unknown command \"print\""),
+            (&["list", "--all"], "unknown option \"--all\""),
+            (&["list", "--json=yes"], "unknown option \"--json\""),
+            (&["add", "Lab", "--driver", "everywhere"], "missing --uri"),
+            (&["add", "--uri", "socket://10.0.0.5", "--driver", "everywhere"], "missing NAME"),
+            (&["add", "Lab", "--driver"], "--driver needs a value"),
+            (&["remove", "Lab", "Office"], "unexpected argument \"Office\""),
+            (&["list", "Lab"], "unexpected argument \"Lab\""),
+            (&["remove"], "missing NAME"),
+        ] {
+            assert_eq!(parse(args), Err(error.to_string()), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn exit_status_tells_bad_arguments_from_failures() {
+        assert_eq!(run_with(&[], Ok(fake_config())), None);
+        assert_eq!(run_with(&["help"], Err("unused")), Some(0));
+        assert_eq!(run_with(&["list", "--all"], Ok(fake_config())), Some(EXIT_USAGE));
+        assert_eq!(run_with(&["remove", "Lab"], Err("bad config")), Some(EXIT_FAILURE));
+        assert_eq!(run_with(&["remove", "Nowhere"], Ok(fake_config())), Some(EXIT_FAILURE));
+        assert_eq!(run_with(&["add", "Lab", "--uri", "socket://10.0.0.5", "--driver", "everywhere"], Ok(fake_config())), Some(EXIT_FAILURE));
+        assert_eq!(run_with(&["remove", "Lab"], Ok(fake_config())), Some(0));
+    }
+}
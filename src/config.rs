@@ -0,0 +1,351 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+
+use crate::cups::{
+    BackendKind,
+    device::DiscoveryOptions,
+    driver::DriverPreference,
+};
+use crate::keymap::{KeyList, Keymap};
+use crate::theme::{CustomTheme, Theme};
+
+/// Written to `config.toml` the first time printer-tui runs without a config file.
+const DEFAULT_CONFIG: &str = r##"# printer-tui configuration, every setting is optional.
+
+# Colors of the TUI: dark, light, high-contrast, monochrome or one of the
+# [themes] below. Setting NO_COLOR in the environment forces monochrome.
+theme = "dark"
+
+# Directory the print file browser opens in, the current one when empty.
+# Paths accept ~ and $VARIABLES.
+print_directory = ""
+
+[cups]
+# lp drives the lp* tools, ipp talks to cupsd directly, fake is an in-memory sample.
+backend = "lp"
+# cupsd socket path or host[:port] for the ipp backend and the job history
+# of the lp one, CUPS_SERVER or the local socket when empty.
+server = ""
+
+[discovery]
+# Seconds lpinfo backends get to find devices, uncomment to override the CUPS default.
+# timeout = 10
+# Backends to run, all of them when empty, e.g. ["dnssd", "usb"].
+include_schemes = []
+exclude_schemes = []
+
+[drivers]
+# Drivers suggested first for IPP printers: driverless (IPP Everywhere and
+# driverless PPDs) or vendor (the manufacturer's PPDs).
+prefer = "driverless"
+
+[keys]
+# Key bindings preset: vim or arrows.
+preset = "vim"
+
+# Rebinds actions over the preset, with one key or a list of them: a single
+# character, Enter, Esc, Tab, BackTab, Space, Up, Down, Left, Right, F1..F12,
+# Ctrl+<key>... The instructions at the bottom of each screen follow along.
+[keys.bindings]
+# quit = ["q", "Ctrl+c"]
+# add = "+"
+
+# Themes of your own, changing some colors of a built-in one. Colors are
+# names (red, light-blue, dark-gray...), "#rrggbb" or 0-255 indexes, for:
+# text, border, focus, highlight, key, error, success, active, muted, class
+# and directory.
+# [themes.solarized]
+# base = "dark"
+# focus = "#b58900"
+# key = "#268bd2"
+"##;
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CupsConfig {
+    pub backend: BackendKind,
+    /// A socket path or `host[:port]`, like `CUPS_SERVER`.
+    pub server: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DriversConfig {
+    pub prefer: DriverPreference,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct KeysConfig {
+    pub preset: String,
+    /// Keys of actions, by action name, replacing the preset's.
+    pub bindings: BTreeMap<String, KeyList>,
+}
+
+impl Default for KeysConfig {
+    fn default() -> Self {
+        KeysConfig {
+            preset: "vim".to_string(),
+            bindings: BTreeMap::new(),
+        }
+    }
+}
+
+/// Settings read from `~/.config/printer-tui/config.toml` or `config.json`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub theme: String,
+    pub print_directory: String,
+    pub cups: CupsConfig,
+    pub discovery: DiscoveryOptions,
+    pub drivers: DriversConfig,
+    pub keys: KeysConfig,
+    pub themes: BTreeMap<String, CustomTheme>,
+    /// Built from `keys` once the file is read.
+    #[serde(skip)]
+    pub keymap: Keymap,
+    /// The colors of `theme`.
+    #[serde(skip)]
+    pub palette: Theme,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            theme: "dark".to_string(),
+            print_directory: String::new(),
+            cups: CupsConfig::default(),
+            discovery: DiscoveryOptions::default(),
+            drivers: DriversConfig::default(),
+            keys: KeysConfig::default(),
+            themes: BTreeMap::new(),
+            keymap: Keymap::default(),
+            palette: Theme::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read, or the default one written.
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// Not valid TOML or JSON, or not the settings we know.
+    Parse {
+        path: PathBuf,
+        message: String,
+    },
+    /// Well formed, but with a value we can't use.
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, error } => write!(f, "Failed to access {}: {}", path.display(), error),
+            ConfigError::Parse { path, message } => write!(f, "Malformed config file {}: {}", path.display(), message.trim_end()),
+            ConfigError::Invalid { path, message } => write!(f, "Invalid config file {}: {}", path.display(), message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// `~/.config/printer-tui`, or the platform's equivalent.
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("printer-tui"))
+    }
+
+    /// Loads `config.toml`, else `config.json`, writing a commented
+    /// `config.toml` with the defaults when there is neither.
+    pub fn load() -> Result<Config, ConfigError> {
+        let Some(dir) = Config::dir() else {
+            return Ok(Config::default());
+        };
+        let toml_path = dir.join("config.toml");
+        let json_path = dir.join("config.json");
+        if toml_path.exists() {
+            Config::load_from(&toml_path)
+        } else if json_path.exists() {
+            Config::load_from(&json_path)
+        } else {
+            fs::create_dir_all(&dir)
+                .and_then(|_| fs::write(&toml_path, DEFAULT_CONFIG))
+                .map_err(|error| ConfigError::Io { path: toml_path, error })?;
+            Ok(Config::default())
+        }
+    }
+
+    /// Parses a config file as JSON when it ends in `.json`, as TOML otherwise.
+    pub fn load_from(path: &Path) -> Result<Config, ConfigError> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| ConfigError::Io { path: path.to_path_buf(), error })?;
+        let parsed = if path.extension().is_some_and(|extension| extension == "json") {
+            serde_json::from_str(&text).map_err(|error| error.to_string())
+        } else {
+            toml::from_str(&text).map_err(|error| error.to_string())
+        };
+        let config: Config = parsed
+            .map_err(|message| ConfigError::Parse { path: path.to_path_buf(), message })?;
+        config.validated()
+            .map_err(|message| ConfigError::Invalid { path: path.to_path_buf(), message })
+    }
+
+    /// Checks the names serde can't, builds the keymap and palette, and expands `~` and
+    /// `$VARIABLES` in paths.
+    fn validated(mut self) -> Result<Config, String> {
+        self.palette = Theme::new(&self.theme, &self.themes)?;
+        self.keymap = Keymap::new(&self.keys.preset, &self.keys.bindings)?;
+        self.print_directory = expand("print_directory", &self.print_directory)?;
+        self.cups.server = expand("cups.server", &self.cups.server)?;
+        Ok(self)
+    }
+
+    /// Directory the print file browser opens in.
+    pub fn print_directory(&self) -> Option<PathBuf> {
+        (!self.print_directory.is_empty()).then(|| PathBuf::from(&self.print_directory))
+    }
+}
+
+fn expand(setting: &str, path: &str) -> Result<String, String> {
+    shellexpand::full(path)
+        .map(|path| path.into_owned())
+        .map_err(|error| format!("{}: {}", setting, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `text` to `name` in a fresh directory of the test's own.
+    fn config_file(test: &str, name: &str, text: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("printer-tui-config-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, text).unwrap();
+        path
+    }
+
+    #[test]
+    fn default_config_file_loads_as_the_defaults() {
+        let path = config_file("default", "config.toml", DEFAULT_CONFIG);
+        assert_eq!(Config::load_from(&path).unwrap(), Config::default());
+    }
+
+    #[test]
+    fn toml_and_json_files_are_told_apart_by_extension() {
+        let toml = config_file("toml", "config.toml", "\
+theme = \"light\"
+[cups]
+backend = \"ipp\"
+server = \"printhost:631\"
+[discovery]
+timeout = 5
+include_schemes = [\"dnssd\"]
+[drivers]
+prefer = \"vendor\"
+");
+        let json = config_file("json", "config.json", r#"{
+            "theme": "light",
+            "cups": { "backend": "ipp", "server": "printhost:631" },
+            "discovery": { "timeout": 5, "include_schemes": ["dnssd"] },
+            "drivers": { "prefer": "vendor" }
+        }"#);
+        for path in [toml, json] {
+            let config = Config::load_from(&path).unwrap();
+            assert_eq!(config.theme, "light");
+            assert_eq!(config.cups, CupsConfig { backend: BackendKind::Ipp, server: "printhost:631".to_string() });
+            assert_eq!(config.discovery, DiscoveryOptions {
+                timeout: Some(5),
+                include_schemes: vec!["dnssd".to_string()],
+                exclude_schemes: vec![],
+            });
+            assert_eq!(config.drivers.prefer, DriverPreference::Vendor);
+        }
+    }
+
+    #[test]
+    fn json_in_a_toml_file_is_malformed() {
+        let path = config_file("json-in-toml", "config.toml", r#"{ "theme": "light" }"#);
+        assert!(matches!(Config::load_from(&path), Err(ConfigError::Parse { .. })));
+    }
+
+    #[test]
+    fn unknown_settings_are_malformed() {
+        for (test, name, text, unknown) in [
+            ("unknown-toml", "config.toml", "colour = \"dark\"", "colour"),
+            ("unknown-toml-table", "config.toml", "[cups]\nhost = \"printhost\"", "host"),
+            ("unknown-json", "config.json", r#"{ "drivers": { "preferred": "vendor" } }"#, "preferred"),
+            ("unknown-backend", "config.toml", "[cups]\nbackend = \"lpr\"", "lpr"),
+        ] {
+            let path = config_file(test, name, text);
+            match Config::load_from(&path) {
+                Err(ConfigError::Parse { path: error_path, message }) => {
+                    assert_eq!(error_path, path);
+                    assert!(message.contains(unknown), "{}: {}", test, message);
+                }
+                other => panic!("{}: {:?}", test, other),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_names_are_invalid() {
+        for (test, text, message) in [
+            ("unknown-theme", "theme = \"solarized\"", "unknown theme \"solarized\", expected one of dark, light, high-contrast, monochrome"),
+            ("unknown-base", "theme = \"mine\"\n[themes.mine]\nbase = \"sepia\"", "themes.mine.base: unknown theme \"sepia\", expected a built-in one"),
+            ("unknown-preset", "[keys]\npreset = \"emacs\"", "unknown keys preset \"emacs\", expected one of vim, arrows"),
+            ("unknown-action", "[keys.bindings]\nfly = \"f\"", "unknown action \"fly\" in keys.bindings"),
+        ] {
+            let path = config_file(test, "config.toml", text);
+            match Config::load_from(&path) {
+                Err(ConfigError::Invalid { message: error, .. }) => assert_eq!(error, message, "{}", test),
+                other => panic!("{}: {:?}", test, other),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_files_cannot_be_read() {
+        let path = config_file("missing", "config.toml", "").with_file_name("other.toml");
+        assert!(matches!(Config::load_from(&path), Err(ConfigError::Io { .. })));
+    }
+
+    #[test]
+    fn paths_are_shell_expanded() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        let path = config_file("expand", "config.toml", "\
+print_directory = \"~/Documents\"
+[cups]
+server = \"$HOME/cups.sock\"
+");
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.print_directory, format!("{}/Documents", home));
+        assert_eq!(config.print_directory(), Some(PathBuf::from(format!("{}/Documents", home))));
+        assert_eq!(config.cups.server, format!("{}/cups.sock", std::env::var("HOME").unwrap()));
+    }
+
+    #[test]
+    fn undefined_variables_are_invalid() {
+        let path = config_file("undefined", "config.toml", "print_directory = \"$PRINTER_TUI_UNDEFINED/prints\"");
+        match Config::load_from(&path) {
+            Err(ConfigError::Invalid { message, .. }) => {
+                assert!(message.starts_with("print_directory: "), "{}", message);
+                assert!(message.contains("PRINTER_TUI_UNDEFINED"), "{}", message);
+            }
+            other => panic!("{:?}", other),
+        }
+    }
+}
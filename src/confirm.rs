@@ -0,0 +1,54 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block,Clear,Paragraph,Wrap},
+};
+use ratatui::prelude::*;
+
+use crate::keymap::{self, Keymap};
+use crate::theme::Theme;
+use crate::utils::popup_area;
+
+/// What runs once the user says yes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfirmAction {
+    RemovePrinter(String),
+    RemoveClass(String),
+}
+
+/// A yes/no question shown over any screen, holding every key until answered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Confirm {
+    pub title: String,
+    pub message: String,
+    pub action: ConfirmAction,
+}
+
+impl Confirm {
+    pub fn new(title: impl Into<String>, message: impl Into<String>, action: ConfirmAction) -> Self {
+        Confirm {
+            title: title.into(),
+            message: message.into(),
+            action,
+        }
+    }
+
+    pub fn render(&self, keymap: &Keymap, theme: &Theme, area: Rect, buf: &mut Buffer) {
+        let popup = popup_area(area, 60, 5);
+        let block = Block::bordered()
+            .title(Line::from(format!(" {} ", self.title).bold()).centered())
+            .title_bottom(keymap.hints(theme, keymap::CONFIRM, false, popup.width.saturating_sub(2)).centered())
+            .border_set(border::THICK)
+            .border_style(theme.error());
+        Clear.render(popup, buf);
+        Paragraph::new(self.message.as_str())
+            .wrap(Wrap { trim: true })
+            .centered()
+            .block(block)
+            .style(theme.text())
+            .render(popup, buf);
+    }
+}
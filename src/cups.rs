@@ -1,3 +1,43 @@
+pub mod backend;
+pub mod class;
 pub mod device;
 pub mod driver;
+pub mod error;
+pub mod fake;
+pub mod ipp;
+pub mod job;
+pub mod options;
 pub mod printer;
+pub mod uri;
+
+use serde::Deserialize;
+
+use backend::{Backend, LpBackend};
+use fake::FakeBackend;
+use ipp::{IppBackend, Server};
+
+/// Which `CupsBackend` to drive.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    #[default]
+    Lp,
+    Ipp,
+    Fake,
+}
+
+/// Builds the backend of `kind`, `PRINTER_TUI_BACKEND` (`lp`, `ipp` or `fake`)
+/// taking precedence. `server` is where the backends find cupsd.
+pub fn backend(kind: BackendKind, server: &str) -> Backend {
+    let kind = match std::env::var("PRINTER_TUI_BACKEND").as_deref() {
+        Ok("lp") => BackendKind::Lp,
+        Ok("ipp") => BackendKind::Ipp,
+        Ok("fake") => BackendKind::Fake,
+        _ => kind,
+    };
+    match kind {
+        BackendKind::Lp => Backend::new(LpBackend::new(Server::from_env_or(server))),
+        BackendKind::Ipp => Backend::new(IppBackend::new(Server::from_env_or(server))),
+        BackendKind::Fake => Backend::new(FakeBackend::sample()),
+    }
+}
@@ -0,0 +1,213 @@
+use std::fmt::Debug;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::Arc;
+
+use super::{
+    class,
+    error::CupsError,
+    ipp::{IppBackend, Server},
+    device::{self, Device, DiscoveryOptions},
+    driver::{self, Driver},
+    job::{self, Job},
+    options::OptionChoices,
+    printer::{self, DefaultScope, Printer},
+};
+
+/// Everything the TUI needs from a CUPS server.
+///
+/// `LpBackend` drives the `lp*` command line tools, other implementations
+/// can stand in for it when no CUPS install is around.
+pub trait CupsBackend: Debug + Send + Sync {
+    fn get_all_printers(&self) -> Result<Vec<Printer>, CupsError>;
+    /// Settable options of a printer with their choices, like `lpoptions -l`.
+    fn get_printer_option_choices(&self, name: &str) -> Result<Vec<OptionChoices>, CupsError>;
+    /// Persists a default option on the server, like `lpadmin -o`.
+    fn set_printer_option(&self, name: &str, option: &str, value: &str) -> Result<(), CupsError>;
+    fn enable_printer(&self, name: &str) -> Result<(), CupsError>;
+    fn disable_printer(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError>;
+    fn accept_jobs(&self, name: &str) -> Result<(), CupsError>;
+    fn reject_jobs(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError>;
+    /// The default destination, the user's own when they set one.
+    fn get_default_printer(&self) -> Result<Option<String>, CupsError>;
+    fn set_default_printer(&self, name: &str, scope: DefaultScope) -> Result<(), CupsError>;
+    /// Queued and active jobs, for every printer when `printer` is `None`.
+    fn get_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError>;
+    /// Job history, for every printer when `printer` is `None`.
+    fn get_completed_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError>;
+    /// Prints a file on `printer`, returning the job id.
+    fn print_file(&self, printer: &str, path: &Path, title: &str) -> Result<u32, CupsError>;
+    fn cancel_job(&self, id: u32) -> Result<(), CupsError>;
+    fn cancel_all_jobs(&self, printer: &str) -> Result<(), CupsError>;
+    fn hold_job(&self, id: u32) -> Result<(), CupsError>;
+    fn release_job(&self, id: u32) -> Result<(), CupsError>;
+    fn restart_job(&self, id: u32) -> Result<(), CupsError>;
+    fn move_job(&self, id: u32, printer: &str) -> Result<(), CupsError>;
+    /// Probes for devices, handing each to `found` as it turns up until it returns false.
+    fn discover_devices(&self, options: &DiscoveryOptions, found: &mut dyn FnMut(Device) -> bool) -> Result<(), CupsError>;
+    fn get_all_drivers(&self) -> Result<Vec<Driver>, CupsError>;
+    /// Drivers made for `device`, best match first.
+    fn get_matching_drivers(&self, device: &Device) -> Result<Vec<Driver>, CupsError>;
+    fn create_printer(&self, name: &str, device: &str, driver: &str, description: &str, location: &str) -> Result<(), CupsError>;
+    /// Changes an existing printer, switching its driver only when `driver` is given.
+    fn modify_printer(&self, name: &str, device: &str, driver: Option<&str>, description: &str, location: &str) -> Result<(), CupsError>;
+    fn remove_printer(&self, name: &str) -> Result<(), CupsError>;
+    /// Adds `printer` to `class`, creating the class when it doesn't exist.
+    fn add_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError>;
+    /// Takes `printer` out of `class`, removing the class once it is empty.
+    fn remove_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError>;
+    fn remove_class(&self, class: &str) -> Result<(), CupsError>;
+}
+
+/// Backend shelling out to `lpstat`, `lpoptions`, `lpinfo` and `lpadmin`.
+#[derive(Debug, Clone)]
+pub struct LpBackend {
+    /// cupsd, for what the tools leave out.
+    server: Server,
+}
+
+impl LpBackend {
+    pub fn new(server: Server) -> Self {
+        LpBackend { server }
+    }
+}
+
+impl CupsBackend for LpBackend {
+    fn get_all_printers(&self) -> Result<Vec<Printer>, CupsError> {
+        printer::get_all_printers()
+    }
+
+    fn get_printer_option_choices(&self, name: &str) -> Result<Vec<OptionChoices>, CupsError> {
+        printer::get_printer_option_choices(name)
+    }
+
+    fn set_printer_option(&self, name: &str, option: &str, value: &str) -> Result<(), CupsError> {
+        printer::set_printer_option(name, option, value)
+    }
+
+    fn enable_printer(&self, name: &str) -> Result<(), CupsError> {
+        printer::enable_printer(name)
+    }
+
+    fn disable_printer(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        printer::disable_printer(name, reason)
+    }
+
+    fn accept_jobs(&self, name: &str) -> Result<(), CupsError> {
+        printer::accept_jobs(name)
+    }
+
+    fn reject_jobs(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        printer::reject_jobs(name, reason)
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, CupsError> {
+        printer::get_default_printer()
+    }
+
+    fn set_default_printer(&self, name: &str, scope: DefaultScope) -> Result<(), CupsError> {
+        printer::set_default_printer(name, scope)
+    }
+
+    fn get_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+        job::get_jobs(printer)
+    }
+
+    /// `lpstat` leaves out the title, pages, copies and submission time of
+    /// finished jobs, so they come from cupsd over IPP, `lpstat` standing in
+    /// when it can't be reached.
+    fn get_completed_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+        match IppBackend::new(self.server.clone()).get_completed_jobs(printer) {
+            Err(CupsError::Unreachable(_)) => job::get_completed_jobs(printer),
+            jobs => jobs,
+        }
+    }
+
+    fn print_file(&self, printer: &str, path: &Path, title: &str) -> Result<u32, CupsError> {
+        job::print_file(printer, path, title)
+    }
+
+    fn cancel_job(&self, id: u32) -> Result<(), CupsError> {
+        job::cancel_job(id)
+    }
+
+    fn cancel_all_jobs(&self, printer: &str) -> Result<(), CupsError> {
+        job::cancel_all_jobs(printer)
+    }
+
+    fn hold_job(&self, id: u32) -> Result<(), CupsError> {
+        job::hold_job(id)
+    }
+
+    fn release_job(&self, id: u32) -> Result<(), CupsError> {
+        job::release_job(id)
+    }
+
+    fn restart_job(&self, id: u32) -> Result<(), CupsError> {
+        job::restart_job(id)
+    }
+
+    fn move_job(&self, id: u32, printer: &str) -> Result<(), CupsError> {
+        job::move_job(id, printer)
+    }
+
+    fn discover_devices(&self, options: &DiscoveryOptions, found: &mut dyn FnMut(Device) -> bool) -> Result<(), CupsError> {
+        device::discover_devices(options, found)
+    }
+
+    fn get_all_drivers(&self) -> Result<Vec<Driver>, CupsError> {
+        driver::get_all_drivers()
+    }
+
+    fn get_matching_drivers(&self, device: &Device) -> Result<Vec<Driver>, CupsError> {
+        driver::get_matching_drivers(device)
+    }
+
+    fn create_printer(&self, name: &str, device: &str, driver: &str, description: &str, location: &str) -> Result<(), CupsError> {
+        printer::create_printer(name, device, driver, description, location)
+    }
+
+    fn modify_printer(&self, name: &str, device: &str, driver: Option<&str>, description: &str, location: &str) -> Result<(), CupsError> {
+        printer::modify_printer(name, device, driver, description, location)
+    }
+
+    fn remove_printer(&self, name: &str) -> Result<(), CupsError> {
+        printer::remove_printer(name)
+    }
+
+    fn add_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError> {
+        class::add_class_member(class, printer)
+    }
+
+    fn remove_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError> {
+        class::remove_class_member(class, printer)
+    }
+
+    fn remove_class(&self, class: &str) -> Result<(), CupsError> {
+        class::remove_class(class)
+    }
+}
+
+/// Shared handle on the backend held by `App`, defaults to `LpBackend`.
+#[derive(Debug, Clone)]
+pub struct Backend(Arc<dyn CupsBackend>);
+
+impl Backend {
+    pub fn new(backend: impl CupsBackend + 'static) -> Self {
+        Backend(Arc::new(backend))
+    }
+}
+
+impl Default for Backend {
+    fn default() -> Self {
+        Backend::new(LpBackend::new(Server::from_env_or("")))
+    }
+}
+
+impl Deref for Backend {
+    type Target = dyn CupsBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use super::error::{CupsError, run};
+
+/// Parses `lpstat -c`, the members of each class indented under a
+/// `members of class NAME:` line.
+pub fn get_class_members() -> Result<HashMap<String, Vec<String>>, CupsError> {
+    let output = match run("lpstat", &["-c"]) {
+        Ok(output) => output,
+        Err(CupsError::Failed { stderr, .. }) if stderr.contains("No destinations") => String::new(),
+        Err(error) => return Err(error),
+    };
+    let mut classes: HashMap<String, Vec<String>> = HashMap::new();
+    let mut class: Option<String> = None;
+    for line in output.lines() {
+        if let Some(name) = line.strip_prefix("members of class ") {
+            let name = name.trim().trim_end_matches(':').to_string();
+            classes.entry(name.clone()).or_default();
+            class = Some(name);
+        } else if line.starts_with(char::is_whitespace) && let Some(class) = &class {
+            let member = line.trim();
+            if !member.is_empty() {
+                classes.entry(class.clone()).or_default().push(member.to_string());
+            }
+        } else {
+            class = None;
+        }
+    }
+    Ok(classes)
+}
+
+/// Adds `printer` to `class`, which cupsd creates if it doesn't exist yet.
+pub fn add_class_member(class: &str, printer: &str) -> Result<(), CupsError> {
+    run("lpadmin", &["-p", printer, "-c", class])?;
+    Ok(())
+}
+
+/// Takes `printer` out of `class`, cupsd deleting the class once it is empty.
+pub fn remove_class_member(class: &str, printer: &str) -> Result<(), CupsError> {
+    run("lpadmin", &["-p", printer, "-r", class])?;
+    Ok(())
+}
+
+pub fn remove_class(class: &str) -> Result<(), CupsError> {
+    run("lpadmin", &["-x", class])?;
+    Ok(())
+}
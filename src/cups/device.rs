@@ -1,32 +1,273 @@
-use serde::Deserialize;
-use std::process::Command;
+use std::io::{self, BufRead, BufReader};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug,Default, Clone, Deserialize)]
+use super::error::{CupsError, spawn, wait};
+
+#[derive(Debug,Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Device{
-    pub value : String
+    /// Device URI, or just the scheme for backends needing one typed in.
+    pub uri: String,
+    /// `network`, `direct`, `serial` or `file`.
+    pub class: String,
+    pub info: String,
+    pub make_and_model: String,
+    /// IEEE 1284 device id, for devices that report one.
+    pub device_id: String,
+    pub location: String,
+}
+
+impl Device {
+    /// A readable name, the backend's description of the device when it gives one.
+    pub fn name(&self) -> &str {
+        [&self.info, &self.make_and_model, &self.uri]
+            .into_iter()
+            .find(|name| !name.is_empty() && *name != "Unknown")
+            .map_or("", |name| name.as_str())
+    }
+
+    pub fn scheme(&self) -> &str {
+        self.uri.split_once(':').map_or(self.uri.as_str(), |(scheme, _)| scheme)
+    }
+
+    /// Whether `options` lets the device's backend run.
+    pub fn allowed_by(&self, options: &DiscoveryOptions) -> bool {
+        let scheme = self.scheme().to_string();
+        (options.include_schemes.is_empty() || options.include_schemes.contains(&scheme))
+            && !options.exclude_schemes.contains(&scheme)
+    }
+
+    /// Sets one `key = value` line of `lpinfo -l -v`.
+    fn set_field(&mut self, line: &str) {
+        let Some((key, value)) = line.split_once('=') else {
+            return;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "uri" => self.uri = value,
+            "class" => self.class = value,
+            "info" => self.info = value,
+            "make-and-model" => self.make_and_model = value,
+            "device-id" => self.device_id = value,
+            "location" => self.location = value,
+            _ => {}
+        }
+    }
+
+    /// Whether the device speaks IPP, so IPP Everywhere can drive it.
+    pub fn is_ipp(&self) -> bool {
+        match self.scheme() {
+            "ipp" | "ipps" => true,
+            "dnssd" => self.uri.contains("._ipp"),
+            _ => false,
+        }
+    }
+}
+
+/// What `lpinfo -v` probes and for how long.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryOptions {
+    /// Seconds backends get to find devices, the CUPS default when `None`.
+    pub timeout: Option<u32>,
+    /// Backends to run, every one when empty.
+    pub include_schemes: Vec<String>,
+    pub exclude_schemes: Vec<String>,
+}
+
+impl DiscoveryOptions {
+    /// Overrides the options with `PRINTER_TUI_LPINFO_TIMEOUT` and the comma
+    /// separated `PRINTER_TUI_INCLUDE_SCHEMES` and `PRINTER_TUI_EXCLUDE_SCHEMES`
+    /// when they are set.
+    pub fn with_env(self) -> Self {
+        let schemes = |name: &str| std::env::var(name)
+            .ok()
+            .map(|value| value.split(',')
+                .map(|scheme| scheme.trim().to_string())
+                .filter(|scheme| !scheme.is_empty())
+                .collect());
+        DiscoveryOptions {
+            timeout: std::env::var("PRINTER_TUI_LPINFO_TIMEOUT").ok()
+                .and_then(|value| value.parse().ok())
+                .or(self.timeout),
+            include_schemes: schemes("PRINTER_TUI_INCLUDE_SCHEMES").unwrap_or(self.include_schemes),
+            exclude_schemes: schemes("PRINTER_TUI_EXCLUDE_SCHEMES").unwrap_or(self.exclude_schemes),
+        }
+    }
+
+    fn lpinfo_args(&self) -> Vec<String> {
+        let mut args = vec!["-l".to_string(), "-v".to_string()];
+        if let Some(timeout) = self.timeout {
+            args.extend(["--timeout".to_string(), timeout.to_string()]);
+        }
+        if !self.include_schemes.is_empty() {
+            args.extend(["--include-schemes".to_string(), self.include_schemes.join(",")]);
+        }
+        if !self.exclude_schemes.is_empty() {
+            args.extend(["--exclude-schemes".to_string(), self.exclude_schemes.join(",")]);
+        }
+        args
+    }
+}
+
+/// Runs `lpinfo -l -v`, handing each device to `found` as soon as lpinfo prints it.
+/// Stops lpinfo early when `found` returns false.
+pub fn discover_devices(options: &DiscoveryOptions, found: &mut dyn FnMut(Device) -> bool) -> Result<(), CupsError> {
+    let args = options.lpinfo_args();
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    let mut child = spawn("lpinfo", &args)?;
+    let Some(stdout) = child.stdout.take() else {
+        return wait("lpinfo", child);
+    };
+    match read_devices(BufReader::new(stdout).lines(), found) {
+        Ok(true) => wait("lpinfo", child),
+        // Stopped early or the pipe broke, lpinfo must not linger either way.
+        read => {
+            let _ = child.kill();
+            let _ = child.wait();
+            read?;
+            Ok(())
+        }
+    }
 }
 
-pub fn get_all_devices() -> Vec<Device> {
-    let output = Command::new("lpinfo")
-        .arg("-v")
-        .output().expect("Failed to execute lpstat command check if CUPS is installed");
-    let stdout = String::from_utf8(output.stdout).expect("Failed to convert output to string");
-    let available_printers:Vec<Device> = stdout.lines()
-    .filter_map(|input| {
-        let line = input.trim();
-        if line.is_empty() {
-            return None;
-        }
-        let words:Vec<&str> = line.split(' ').collect();
-        if words.len() < 2 { 
-            None
-        }else{
-            Some(Device{
-                value:words[1].to_string(),
-            })
-        }
-    })
-    .collect();
-    available_printers
+/// Groups `lpinfo -l -v` lines into devices, handing each to `found` once
+/// complete. Returns false when `found` asked to stop.
+fn read_devices(lines: impl Iterator<Item = io::Result<String>>, found: &mut dyn FnMut(Device) -> bool) -> io::Result<bool> {
+    let mut device: Option<Device> = None;
+    for line in lines {
+        let line = line?;
+        let line = line.trim();
+        let field = match line.strip_prefix("Device:") {
+            Some(rest) => {
+                if let Some(device) = device.replace(Device::default()) && !found(device) {
+                    return Ok(false);
+                }
+                rest.trim()
+            }
+            None => line,
+        };
+        let Some(current) = device.as_mut() else {
+            continue;
+        };
+        current.set_field(field);
+        // `location` closes each block, so hand the device over right away
+        // rather than when the next one starts, which can be seconds later.
+        if field.starts_with("location") && let Some(device) = device.take() && !found(device) {
+            return Ok(false);
+        }
+    }
+    Ok(device.is_none_or(found))
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lpinfo -l -v` with a backend that only prints its scheme and a
+    /// network printer found over DNS-SD.
+    const LPINFO: &str = "\
+Device: uri = socket
+        class = network
+        info = AppSocket/HP JetDirect
+        make-and-model = Unknown
+        device-id = 
+        location = 
+Device: uri = dnssd://HP%20LaserJet%204000._ipp._tcp.local/?uuid=1e8a
+        class = network
+        info = HP LaserJet 4000
+        make-and-model = HP LaserJet 4000
+        device-id = MFG:HP;MDL:LaserJet 4000;CMD:PCL,POSTSCRIPT;
+        location = Floor 2, room 214
+Device: uri = usb://Zebra/ZTC%20GK420d?serial=28J1
+        class = direct
+        info = Zebra ZTC GK420d
+";
+
+    fn read(output: &str) -> (Vec<Device>, io::Result<bool>) {
+        let mut devices = vec![];
+        let read = read_devices(output.lines().map(|line| Ok(line.to_string())), &mut |device| {
+            devices.push(device);
+            true
+        });
+        (devices, read)
+    }
+
+    #[test]
+    fn records_fill_every_field() {
+        let (devices, read) = read(LPINFO);
+        assert!(read.unwrap());
+        assert_eq!(devices.len(), 3);
+        assert_eq!(devices[1], Device {
+            uri: "dnssd://HP%20LaserJet%204000._ipp._tcp.local/?uuid=1e8a".to_string(),
+            class: "network".to_string(),
+            info: "HP LaserJet 4000".to_string(),
+            make_and_model: "HP LaserJet 4000".to_string(),
+            device_id: "MFG:HP;MDL:LaserJet 4000;CMD:PCL,POSTSCRIPT;".to_string(),
+            location: "Floor 2, room 214".to_string(),
+        });
+        assert!(devices[1].is_ipp());
+    }
+
+    #[test]
+    fn scheme_only_backends_are_devices_too() {
+        let (devices, _) = read(LPINFO);
+        let socket = &devices[0];
+        assert_eq!(socket.uri, "socket");
+        assert_eq!(socket.scheme(), "socket");
+        assert_eq!(socket.class, "network");
+        assert_eq!(socket.device_id, "");
+        assert_eq!(socket.name(), "AppSocket/HP JetDirect");
+    }
+
+    #[test]
+    fn the_last_record_is_kept_without_a_location() {
+        let (devices, _) = read(LPINFO);
+        assert_eq!(devices[2].uri, "usb://Zebra/ZTC%20GK420d?serial=28J1");
+        assert_eq!(devices[2].class, "direct");
+        assert_eq!(devices[2].location, "");
+    }
+
+    #[test]
+    fn lines_before_the_first_record_are_skipped() {
+        let (devices, _) = read("lpinfo: Scanning...\nclass = network\nDevice: uri = lpd\n");
+        assert_eq!(devices, vec![Device {
+            uri: "lpd".to_string(),
+            ..Default::default()
+        }]);
+    }
+
+    #[test]
+    fn reading_stops_when_asked() {
+        let mut uris = vec![];
+        let read = read_devices(LPINFO.lines().map(|line| Ok(line.to_string())), &mut |device| {
+            uris.push(device.uri);
+            false
+        });
+        assert!(!read.unwrap());
+        assert_eq!(uris, vec!["socket"]);
+    }
+
+    #[test]
+    fn read_errors_are_passed_on() {
+        let lines = vec![Ok("Device: uri = socket".to_string()), Err(io::Error::other("broken pipe"))];
+        let read = read_devices(lines.into_iter(), &mut |_| true);
+        assert_eq!(read.unwrap_err().to_string(), "broken pipe");
+    }
+
+    #[test]
+    fn schemes_are_filtered_by_the_options() {
+        let device = Device {
+            uri: "usb://Zebra/ZTC".to_string(),
+            ..Default::default()
+        };
+        let options = |include: &[&str], exclude: &[&str]| DiscoveryOptions {
+            timeout: None,
+            include_schemes: include.iter().map(|scheme| scheme.to_string()).collect(),
+            exclude_schemes: exclude.iter().map(|scheme| scheme.to_string()).collect(),
+        };
+        assert!(device.allowed_by(&options(&[], &[])));
+        assert!(device.allowed_by(&options(&["usb"], &[])));
+        assert!(!device.allowed_by(&options(&["dnssd"], &[])));
+        assert!(!device.allowed_by(&options(&[], &["usb"])));
+    }
+}
@@ -1,23 +1,316 @@
-use serde::Deserialize;
-use std::process::Command;
+use std::fmt;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug,Default, Clone, Deserialize)]
+use super::{
+    device::Device,
+    error::{CupsError, run},
+};
+
+/// How cupsd gets the PPD of a driver.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum DriverType {
+    /// A static PPD file shipped by a driver package.
+    #[default]
+    Ppd,
+    /// The built-in `everywhere` model, set up from the printer's own IPP attributes.
+    IppEverywhere,
+    /// A `driverless:` PPD generated by cups-filters for an IPP printer.
+    Driverless,
+    /// A PPD compiled on the fly by a driver program, like `drv:///` or `foomatic:` ones.
+    Classic,
+}
+
+/// Which drivers `recommend` puts first for IPP printers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriverPreference {
+    /// IPP Everywhere and the printer's driverless PPD.
+    #[default]
+    Driverless,
+    /// The manufacturer's drivers cups-driverd matched to the printer.
+    Vendor,
+}
+
+impl DriverType {
+    pub fn from_ppd_name(ppd_name: &str) -> Self {
+        if ppd_name == "everywhere" {
+            DriverType::IppEverywhere
+        } else if ppd_name.starts_with("driverless:") {
+            DriverType::Driverless
+        } else if ppd_name.split_once(':').is_some_and(|(scheme, _)| is_driver_scheme(scheme)) {
+            DriverType::Classic
+        } else {
+            DriverType::Ppd
+        }
+    }
+}
+
+/// Whether `scheme` names the driver program of a generated PPD, like the
+/// `drv`, `foomatic` or `gutenprint.5.3` of `lpinfo -m`, rather than being
+/// part of a PPD file path that happens to hold a `:`.
+fn is_driver_scheme(scheme: &str) -> bool {
+    !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.' | '_'))
+}
+
+impl fmt::Display for DriverType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DriverType::Ppd => write!(f, "PPD"),
+            DriverType::IppEverywhere => write!(f, "IPP Everywhere"),
+            DriverType::Driverless => write!(f, "driverless"),
+            DriverType::Classic => write!(f, "classic"),
+        }
+    }
+}
+
+#[derive(Debug,Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Driver{
-    pub value: String,
-}
-
-pub fn get_all_drivers() -> Vec<Driver> {
-    let output = Command::new("lpinfo")
-        .arg("-m")
-        .output().expect("Failed to execute lpstat command check if CUPS is installed");
-    let stdout = String::from_utf8(output.stdout).expect("Failed to convert output to string");
-    let drivers:Vec<Driver> = stdout.lines()
-    .filter_map(|input| {
-        let value = input.trim().to_string();
-        Some(Driver{
-            value,
-        })
-    })
-    .collect();
-    drivers 
+    /// What `lpadmin -m` takes.
+    pub ppd_name: String,
+    pub make: String,
+    pub make_and_model: String,
+    pub natural_language: String,
+    /// IEEE 1284 device id the driver was written for, often empty.
+    pub device_id: String,
+    pub driver_type: DriverType,
+}
+
+impl Driver {
+    /// Fills `make` and `driver_type` when the source doesn't tell them, the
+    /// manufacturer coming from the device id or the first word of the model.
+    pub fn complete(mut self) -> Self {
+        if self.make.is_empty() {
+            self.make = device_id_field(&self.device_id, &["MFG", "MANUFACTURER"])
+                .or_else(|| self.make_and_model.split_whitespace().next())
+                .unwrap_or_default()
+                .to_string();
+        }
+        self.driver_type = DriverType::from_ppd_name(&self.ppd_name);
+        self
+    }
+
+    /// The text the driver search matches against, model first.
+    pub fn search_text(&self) -> String {
+        format!("{} {}", self.make_and_model, self.ppd_name)
+    }
+}
+
+/// A field of an IEEE 1284 device id like `MFG:HP;MDL:LaserJet 4;`.
+pub fn device_id_field<'a>(device_id: &'a str, keys: &[&str]) -> Option<&'a str> {
+    device_id.split(';')
+        .filter_map(|field| field.split_once(':'))
+        .find(|(key, _)| keys.iter().any(|wanted| key.trim().eq_ignore_ascii_case(wanted)))
+        .map(|(_, value)| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+/// Every driver `lpinfo -l -m` lists, sorted by manufacturer then model.
+pub fn get_all_drivers() -> Result<Vec<Driver>, CupsError> {
+    let stdout = run("lpinfo", &["-l", "-m"])?;
+    let mut drivers = parse_lpinfo_drivers(&stdout);
+    sort_drivers(&mut drivers);
+    Ok(drivers)
+}
+
+/// Drivers cups-driverd matches to the device's IEEE 1284 id, or failing
+/// that its make and model, best match first.
+pub fn get_matching_drivers(device: &Device) -> Result<Vec<Driver>, CupsError> {
+    let (key, value) = if !device.device_id.is_empty() {
+        ("--device-id", device.device_id.as_str())
+    } else if !device.make_and_model.is_empty() && device.make_and_model != "Unknown" {
+        ("--make-and-model", device.make_and_model.as_str())
+    } else {
+        return Ok(vec![]);
+    };
+    let stdout = run("lpinfo", &["-l", "-m", key, value])?;
+    Ok(parse_lpinfo_drivers(&stdout))
+}
+
+/// Ranks the PPD names to recommend for a device: IPP Everywhere and the
+/// device's own driverless PPD for IPP printers, and the drivers `matching`
+/// it in the order cups-driverd gave them, whichever `preference` says first.
+pub fn recommend(device: &Device, matching: &[Driver], drivers: &[Driver], preference: DriverPreference) -> Vec<String> {
+    let driverless: Vec<&Driver> = if device.is_ipp() {
+        drivers.iter()
+            .filter(|driver| driver.driver_type == DriverType::IppEverywhere
+                || (driver.driver_type == DriverType::Driverless && driver.ppd_name.ends_with(&device.uri)))
+            .collect()
+    } else {
+        vec![]
+    };
+    let vendor: Vec<&Driver> = matching.iter().collect();
+    let (first, second) = match preference {
+        DriverPreference::Driverless => (driverless, vendor),
+        DriverPreference::Vendor => (vendor, driverless),
+    };
+    let mut ranked: Vec<String> = vec![];
+    for driver in first.into_iter().chain(second) {
+        if !ranked.contains(&driver.ppd_name) {
+            ranked.push(driver.ppd_name.clone());
+        }
+    }
+    ranked
+}
+
+pub fn sort_drivers(drivers: &mut [Driver]) {
+    drivers.sort_by(|a, b| {
+        a.make.to_lowercase().cmp(&b.make.to_lowercase())
+            .then_with(|| a.make_and_model.cmp(&b.make_and_model))
+    });
+}
+
+/// Parses the `Model: name = ...` blocks of `lpinfo -l -m`.
+fn parse_lpinfo_drivers(output: &str) -> Vec<Driver> {
+    let mut drivers: Vec<Driver> = vec![];
+    for line in output.lines() {
+        let line = line.trim();
+        let line = match line.strip_prefix("Model:") {
+            Some(rest) => {
+                drivers.push(Driver::default());
+                rest.trim()
+            }
+            None => line,
+        };
+        let (Some(driver), Some((key, value))) = (drivers.last_mut(), line.split_once('=')) else {
+            continue;
+        };
+        let value = value.trim().to_string();
+        match key.trim() {
+            "name" => driver.ppd_name = value,
+            "natural_language" => driver.natural_language = value,
+            "make-and-model" => driver.make_and_model = value,
+            "device_id" => driver.device_id = value,
+            _ => {}
+        }
+    }
+    drivers.into_iter().map(Driver::complete).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lpinfo -l -m` with a static PPD, classic drivers, IPP Everywhere and
+    /// the driverless PPD of a printer found over DNS-SD.
+    const LPINFO: &str = "\
+Model:  name = lsb/usr/HP/hp-laserjet_4000_series-ps.ppd.gz
+        natural_language = en
+        make-and-model = HP LaserJet 4000 Series Postscript (recommended)
+        device_id = MFG:HP;MDL:hp laserjet 4000 series;DES:hp laserjet 4000 series;
+Model:  name = drv:///sample.drv/laserjet.ppd
+        natural_language = en
+        make-and-model = HP LaserJet Series PCL 4/5
+        device_id = 
+Model:  name = gutenprint.5.3://bjc-PIXMA-iP4000/expert
+        natural_language = en
+        make-and-model = Canon PIXMA iP4000 - CUPS+Gutenprint v5.3.4
+        device_id = MFG:Canon;MDL:PIXMA iP4000;
+Model:  name = everywhere
+        natural_language = en
+        make-and-model = IPP Everywhere
+        device_id = 
+Model:  name = driverless:ipp://HP%20LaserJet%204000._ipp._tcp.local/
+        natural_language = en
+        make-and-model = HP LaserJet 4000, driverless, cups-filters 1.28.17
+        device_id = MFG:HP;MDL:LaserJet 4000;CMD:PCL,PDF;
+";
+
+    #[test]
+    fn models_are_read_with_their_fields() {
+        let drivers = parse_lpinfo_drivers(LPINFO);
+        assert_eq!(drivers.len(), 5);
+        assert_eq!(drivers[0], Driver {
+            ppd_name: "lsb/usr/HP/hp-laserjet_4000_series-ps.ppd.gz".to_string(),
+            make: "HP".to_string(),
+            make_and_model: "HP LaserJet 4000 Series Postscript (recommended)".to_string(),
+            natural_language: "en".to_string(),
+            device_id: "MFG:HP;MDL:hp laserjet 4000 series;DES:hp laserjet 4000 series;".to_string(),
+            driver_type: DriverType::Ppd,
+        });
+        assert_eq!(drivers[1].make, "HP");
+        assert_eq!(drivers[1].device_id, "");
+        assert_eq!(drivers[2].make, "Canon");
+    }
+
+    #[test]
+    fn types_come_from_the_ppd_name() {
+        let types: Vec<DriverType> = parse_lpinfo_drivers(LPINFO).iter().map(|driver| driver.driver_type).collect();
+        assert_eq!(types, vec![
+            DriverType::Ppd,
+            DriverType::Classic,
+            DriverType::Classic,
+            DriverType::IppEverywhere,
+            DriverType::Driverless,
+        ]);
+        assert_eq!(DriverType::from_ppd_name("foomatic:HP-LaserJet_4000-Postscript.ppd"), DriverType::Classic);
+        assert_eq!(DriverType::from_ppd_name("Ricoh/Ricoh-MP_C3003:PS.ppd"), DriverType::Ppd);
+        assert_eq!(DriverType::from_ppd_name(":odd.ppd"), DriverType::Ppd);
+    }
+
+    #[test]
+    fn drivers_sort_by_make_then_model() {
+        let mut drivers = parse_lpinfo_drivers(LPINFO);
+        sort_drivers(&mut drivers);
+        let models: Vec<&str> = drivers.iter().map(|driver| driver.make_and_model.as_str()).collect();
+        assert_eq!(models, vec![
+            "Canon PIXMA iP4000 - CUPS+Gutenprint v5.3.4",
+            "HP LaserJet 4000 Series Postscript (recommended)",
+            "HP LaserJet 4000, driverless, cups-filters 1.28.17",
+            "HP LaserJet Series PCL 4/5",
+            "IPP Everywhere",
+        ]);
+    }
+
+    #[test]
+    fn device_id_fields_are_found_by_any_key() {
+        let device_id = "MANUFACTURER:Hewlett-Packard;MDL:LaserJet 4000; CMD:PCL;";
+        assert_eq!(device_id_field(device_id, &["MFG", "MANUFACTURER"]), Some("Hewlett-Packard"));
+        assert_eq!(device_id_field(device_id, &["cmd"]), Some("PCL"));
+        assert_eq!(device_id_field(device_id, &["DES"]), None);
+        assert_eq!(device_id_field("MFG:;", &["MFG"]), None);
+    }
+
+    #[test]
+    fn ipp_printers_get_driverless_drivers_first_by_default() {
+        let drivers = parse_lpinfo_drivers(LPINFO);
+        let device = Device {
+            uri: "ipp://HP%20LaserJet%204000._ipp._tcp.local/".to_string(),
+            ..Default::default()
+        };
+        let matching = vec![drivers[0].clone(), drivers[4].clone()];
+        assert_eq!(recommend(&device, &matching, &drivers, DriverPreference::Driverless), vec![
+            "everywhere",
+            "driverless:ipp://HP%20LaserJet%204000._ipp._tcp.local/",
+            "lsb/usr/HP/hp-laserjet_4000_series-ps.ppd.gz",
+        ]);
+        assert_eq!(recommend(&device, &matching, &drivers, DriverPreference::Vendor), vec![
+            "lsb/usr/HP/hp-laserjet_4000_series-ps.ppd.gz",
+            "driverless:ipp://HP%20LaserJet%204000._ipp._tcp.local/",
+            "everywhere",
+        ]);
+    }
+
+    #[test]
+    fn other_printers_only_get_matching_drivers() {
+        let drivers = parse_lpinfo_drivers(LPINFO);
+        let device = Device {
+            uri: "usb://Canon/PIXMA%20iP4000".to_string(),
+            ..Default::default()
+        };
+        let matching = vec![drivers[2].clone()];
+        assert_eq!(recommend(&device, &matching, &drivers, DriverPreference::Driverless), vec![
+            "gutenprint.5.3://bjc-PIXMA-iP4000/expert",
+        ]);
+        assert!(recommend(&device, &[], &drivers, DriverPreference::Driverless).is_empty());
+    }
+
+    #[test]
+    fn driverless_ppds_of_other_printers_are_left_out() {
+        let drivers = parse_lpinfo_drivers(LPINFO);
+        let device = Device {
+            uri: "ipps://Office._ipps._tcp.local/".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(recommend(&device, &[], &drivers, DriverPreference::Driverless), vec!["everywhere"]);
+    }
 }
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::io;
+use std::process::{Child, Command, Stdio};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CupsError {
+    /// A CUPS command line tool is not installed or not in `PATH`.
+    BinaryMissing(String),
+    PermissionDenied(String),
+    /// A command exited with a non-zero status.
+    Failed {
+        command: String,
+        code: Option<i32>,
+        stderr: String,
+    },
+    /// Output we could not make sense of.
+    Parse(String),
+    /// cupsd rejected an IPP request or broke off the exchange.
+    Server(String),
+    /// Nothing answered at the cupsd address.
+    Unreachable(String),
+    /// A device URI that `lpadmin -v` would not take.
+    InvalidUri(String),
+    /// A printer name `lpadmin -p` would refuse or that is already taken.
+    InvalidName(String),
+}
+
+impl fmt::Display for CupsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CupsError::BinaryMissing(command) => write!(f, "{} not found, check if CUPS is installed", command),
+            CupsError::PermissionDenied(message) => write!(f, "Permission denied: {}", message),
+            CupsError::Failed { command, code: Some(code), stderr } => write!(f, "{} exited with {}: {}", command, code, stderr),
+            CupsError::Failed { command, code: None, stderr } => write!(f, "{} was interrupted: {}", command, stderr),
+            CupsError::Parse(message) => write!(f, "Failed to parse CUPS output: {}", message),
+            CupsError::Server(message) => write!(f, "CUPS server error: {}", message),
+            CupsError::Unreachable(message) => write!(f, "Cannot reach the CUPS server: {}", message),
+            CupsError::InvalidUri(message) => write!(f, "Invalid device URI: {}", message),
+            CupsError::InvalidName(message) => write!(f, "Invalid printer name: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CupsError {}
+
+impl From<io::Error> for CupsError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => CupsError::PermissionDenied(error.to_string()),
+            io::ErrorKind::InvalidData => CupsError::Parse(error.to_string()),
+            _ => CupsError::Server(error.to_string()),
+        }
+    }
+}
+
+/// Runs a CUPS command line tool and returns its standard output, untranslated so it can be parsed.
+pub fn run(program: &str, args: &[&str]) -> Result<String, CupsError> {
+    let output = Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .output()
+        .map_err(|error| spawn_error(program, error))?;
+
+    if !output.status.success() {
+        return Err(exit_error(program, output.status.code(), &String::from_utf8_lossy(&output.stderr)));
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|_| CupsError::Parse(format!("{} output is not valid UTF-8", program)))
+}
+
+/// Starts a CUPS command line tool with its output piped, for callers reading it as it comes.
+pub fn spawn(program: &str, args: &[&str]) -> Result<Child, CupsError> {
+    Command::new(program)
+        .args(args)
+        .env("LC_ALL", "C")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| spawn_error(program, error))
+}
+
+/// Waits for a spawned tool, failing like `run` when it exits non-zero.
+pub fn wait(program: &str, child: Child) -> Result<(), CupsError> {
+    let output = child.wait_with_output().map_err(|error| spawn_error(program, error))?;
+    if !output.status.success() {
+        return Err(exit_error(program, output.status.code(), &String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
+}
+
+fn spawn_error(program: &str, error: io::Error) -> CupsError {
+    match error.kind() {
+        io::ErrorKind::NotFound => CupsError::BinaryMissing(program.to_string()),
+        io::ErrorKind::PermissionDenied => CupsError::PermissionDenied(program.to_string()),
+        _ => CupsError::Failed {
+            command: program.to_string(),
+            code: None,
+            stderr: error.to_string(),
+        },
+    }
+}
+
+fn exit_error(program: &str, code: Option<i32>, stderr: &str) -> CupsError {
+    let stderr = stderr.trim().to_string();
+    let lowercase = stderr.to_lowercase();
+    if lowercase.contains("forbidden") || lowercase.contains("not authorized") || lowercase.contains("permission denied") {
+        return CupsError::PermissionDenied(stderr);
+    }
+    CupsError::Failed {
+        command: program.to_string(),
+        code,
+        stderr,
+    }
+}
@@ -0,0 +1,511 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+use super::{
+    backend::CupsBackend,
+    error::CupsError,
+    device::{Device, DiscoveryOptions},
+    driver::{Driver, device_id_field},
+    job::{Job, JobState},
+    options::{OptionChoices, OptionValue, PrinterOptions},
+    printer::{DefaultScope, Printer, PrinterState},
+};
+
+/// In-memory backend, useful to try the TUI or script against it without CUPS.
+#[derive(Debug, Default)]
+pub struct FakeBackend {
+    printers: Mutex<Vec<Printer>>,
+    jobs: Mutex<Vec<Job>>,
+    devices: Vec<Device>,
+    drivers: Vec<Driver>,
+    system_default: Mutex<Option<String>>,
+    user_default: Mutex<Option<String>>,
+}
+
+impl FakeBackend {
+    pub fn new(printers: Vec<Printer>, jobs: Vec<Job>, devices: Vec<Device>, drivers: Vec<Driver>) -> Self {
+        FakeBackend {
+            printers: Mutex::new(printers),
+            jobs: Mutex::new(jobs),
+            devices,
+            drivers,
+            ..Default::default()
+        }
+    }
+
+    /// A couple of queues, devices and drivers to play with.
+    pub fn sample() -> Self {
+        let backend = FakeBackend::new(
+            vec![
+                Printer {
+                    name: "Office".to_string(),
+                    options: PrinterOptions::parse("copies=1 device-uri=ipp://192.168.1.20/ipp/print media=iso_a4_210x297mm printer-info='Office Laser' printer-location='Floor 2, room 214' printer-is-shared=false printer-make-and-model='IPP Everywhere'"),
+                    description: "Office Laser".to_string(),
+                    location: "Floor 2, room 214".to_string(),
+                    device_uri: "ipp://192.168.1.20/ipp/print".to_string(),
+                    make_and_model: "IPP Everywhere".to_string(),
+                    state: PrinterState::Idle,
+                    accepting: true,
+                    ..Default::default()
+                },
+                Printer {
+                    name: "Lab".to_string(),
+                    options: PrinterOptions::parse("copies=1 device-uri=socket://192.168.1.21:9100 sides=two-sided-long-edge printer-location='Basement lab' printer-make-and-model='HP LaserJet Series PCL 4/5' printer-state-reasons=media-low"),
+                    description: String::new(),
+                    location: "Basement lab".to_string(),
+                    device_uri: "socket://192.168.1.21:9100".to_string(),
+                    make_and_model: "HP LaserJet Series PCL 4/5".to_string(),
+                    state: PrinterState::Stopped,
+                    state_message: "Paused for maintenance".to_string(),
+                    state_reasons: vec!["media-low".to_string(), "paused".to_string()],
+                    accepting: false,
+                    is_class: false,
+                    members: vec![],
+                },
+                Printer {
+                    name: "Lasers".to_string(),
+                    options: PrinterOptions::parse("member-names=Office,Lab printer-info='Any laser printer' printer-type=1"),
+                    description: "Any laser printer".to_string(),
+                    state: PrinterState::Idle,
+                    accepting: true,
+                    is_class: true,
+                    members: vec!["Office".to_string(), "Lab".to_string()],
+                    ..Default::default()
+                },
+            ],
+            vec![
+                Job {
+                    id: 12,
+                    printer: "Office".to_string(),
+                    user: "alice".to_string(),
+                    title: "report.pdf".to_string(),
+                    size: 284_672,
+                    state: JobState::Processing,
+                    submitted: "2026-10-18 09:12:44".to_string(),
+                    copies: Some(1),
+                    ..Default::default()
+                },
+                Job {
+                    id: 13,
+                    printer: "Office".to_string(),
+                    user: "bob".to_string(),
+                    title: "slides.pdf".to_string(),
+                    size: 3_145_728,
+                    state: JobState::Pending,
+                    submitted: "2026-10-18 09:15:02".to_string(),
+                    copies: Some(2),
+                    ..Default::default()
+                },
+                Job {
+                    id: 14,
+                    printer: "Lab".to_string(),
+                    user: "carol".to_string(),
+                    title: "poster.ps".to_string(),
+                    size: 12_582_912,
+                    state: JobState::Held,
+                    submitted: "2026-10-18 09:31:57".to_string(),
+                    copies: Some(1),
+                    state_reasons: vec!["job-hold-until-specified".to_string()],
+                    ..Default::default()
+                },
+                Job {
+                    id: 9,
+                    printer: "Office".to_string(),
+                    user: "alice".to_string(),
+                    title: "invoice-0932.pdf".to_string(),
+                    size: 96_256,
+                    state: JobState::Completed,
+                    submitted: "2026-10-16 14:02:11".to_string(),
+                    completed: "2026-10-16 14:02:39".to_string(),
+                    pages: Some(3),
+                    copies: Some(1),
+                    state_reasons: vec!["job-completed-successfully".to_string()],
+                },
+                Job {
+                    id: 10,
+                    printer: "Lab".to_string(),
+                    user: "bob".to_string(),
+                    title: "thesis.pdf".to_string(),
+                    size: 8_388_608,
+                    state: JobState::Aborted,
+                    submitted: "2026-10-17 11:45:00".to_string(),
+                    completed: "2026-10-17 11:47:21".to_string(),
+                    pages: Some(41),
+                    copies: Some(2),
+                    state_reasons: vec!["job-aborted-by-system".to_string()],
+                },
+                Job {
+                    id: 11,
+                    printer: "Office".to_string(),
+                    user: "carol".to_string(),
+                    title: "badge.png".to_string(),
+                    size: 512_000,
+                    state: JobState::Canceled,
+                    submitted: "2026-10-17 16:20:05".to_string(),
+                    completed: "2026-10-17 16:20:30".to_string(),
+                    pages: Some(0),
+                    copies: Some(1),
+                    state_reasons: vec!["job-canceled-by-user".to_string()],
+                },
+            ],
+            vec![
+                Device {
+                    uri: "ipp://192.168.1.20/ipp/print".to_string(),
+                    class: "network".to_string(),
+                    info: "Brother HL-L2350DW series".to_string(),
+                    make_and_model: "Brother HL-L2350DW series".to_string(),
+                    device_id: "MFG:Brother;MDL:HL-L2350DW series;CMD:PWGRaster,URF;".to_string(),
+                    location: "2nd floor".to_string(),
+                },
+                Device {
+                    uri: "socket://192.168.1.21:9100".to_string(),
+                    class: "network".to_string(),
+                    info: "HP LaserJet 4".to_string(),
+                    make_and_model: "HP LaserJet 4".to_string(),
+                    device_id: "MFG:HP;MDL:LaserJet 4;CMD:PCL;".to_string(),
+                    ..Default::default()
+                },
+                Device {
+                    uri: "usb://HP/LaserJet%204".to_string(),
+                    class: "direct".to_string(),
+                    info: "HP LaserJet 4 USB".to_string(),
+                    make_and_model: "HP LaserJet 4".to_string(),
+                    device_id: "MFG:HP;MDL:LaserJet 4;CMD:PCL;".to_string(),
+                    ..Default::default()
+                },
+                Device {
+                    uri: "socket".to_string(),
+                    class: "network".to_string(),
+                    info: "AppSocket/HP JetDirect".to_string(),
+                    make_and_model: "Unknown".to_string(),
+                    ..Default::default()
+                },
+            ],
+            vec![
+                Driver {
+                    ppd_name: "driverless:ipp://192.168.1.20/ipp/print".to_string(),
+                    make_and_model: "Brother HL-L2350DW series, driverless, cups-filters 2.0.0".to_string(),
+                    natural_language: "en".to_string(),
+                    device_id: "MFG:Brother;MDL:HL-L2350DW series;CMD:PWGRaster,URF;".to_string(),
+                    ..Default::default()
+                }.complete(),
+                Driver {
+                    ppd_name: "drv:///sample.drv/generic.ppd".to_string(),
+                    make_and_model: "Generic PostScript Printer".to_string(),
+                    natural_language: "en".to_string(),
+                    ..Default::default()
+                }.complete(),
+                Driver {
+                    ppd_name: "drv:///sample.drv/laserjet.ppd".to_string(),
+                    make_and_model: "HP LaserJet Series PCL 4/5".to_string(),
+                    natural_language: "en".to_string(),
+                    device_id: "MFG:HP;MDL:LaserJet;CMD:PCL;".to_string(),
+                    ..Default::default()
+                }.complete(),
+                Driver {
+                    ppd_name: "everywhere".to_string(),
+                    make: "IPP".to_string(),
+                    make_and_model: "IPP Everywhere".to_string(),
+                    natural_language: "en".to_string(),
+                    ..Default::default()
+                }.complete(),
+            ],
+        );
+        *backend.system_default.lock().unwrap() = Some("Office".to_string());
+        backend
+    }
+
+    fn update(&self, name: &str, change: impl FnOnce(&mut Printer)) -> Result<(), CupsError> {
+        self.find(name)?;
+        let mut printers = self.printers.lock().unwrap();
+        if let Some(printer) = printers.iter_mut().find(|printer| printer.name == name) {
+            change(printer);
+        }
+        Ok(())
+    }
+
+    /// The model of the driver named `ppd_name`, what CUPS reports as the printer's make and model.
+    fn driver_model(&self, ppd_name: &str) -> String {
+        self.drivers.iter()
+            .find(|driver| driver.ppd_name == ppd_name)
+            .map_or(ppd_name.to_string(), |driver| driver.make_and_model.clone())
+    }
+
+    fn cancel(job: &mut Job) {
+        job.state = JobState::Canceled;
+        job.completed = job.submitted.clone();
+        job.state_reasons = vec!["job-canceled-by-user".to_string()];
+    }
+
+    fn update_job(&self, id: u32, change: impl FnOnce(&mut Job)) -> Result<(), CupsError> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = jobs.iter_mut()
+            .find(|job| job.id == id)
+            .ok_or_else(|| CupsError::Failed {
+                command: "lp".to_string(),
+                code: Some(1),
+                stderr: format!("Job #{} does not exist", id),
+            })?;
+        change(job);
+        Ok(())
+    }
+
+    /// Fails the way `lpadmin` does for unknown queues.
+    fn find(&self, name: &str) -> Result<Printer, CupsError> {
+        self.printers.lock().unwrap()
+            .iter()
+            .find(|printer| printer.name == name)
+            .cloned()
+            .ok_or_else(|| CupsError::Failed {
+                command: "lpadmin".to_string(),
+                code: Some(1),
+                stderr: format!("The printer or class does not exist: {}", name),
+            })
+    }
+}
+
+impl CupsBackend for FakeBackend {
+    fn get_all_printers(&self) -> Result<Vec<Printer>, CupsError> {
+        Ok(self.printers.lock().unwrap().clone())
+    }
+
+    fn get_printer_option_choices(&self, name: &str) -> Result<Vec<OptionChoices>, CupsError> {
+        self.find(name)?;
+        Ok(OptionChoices::parse(
+            "PageSize/Media Size: Letter *A4 Legal A5\nDuplex/2-Sided Printing: *None DuplexNoTumble DuplexTumble\n"
+        ))
+    }
+
+    fn set_printer_option(&self, name: &str, option: &str, value: &str) -> Result<(), CupsError> {
+        self.update(name, |printer| printer.options.insert(option, OptionValue::from_raw(value, false)))
+    }
+
+    fn enable_printer(&self, name: &str) -> Result<(), CupsError> {
+        self.update(name, |printer| {
+            printer.state = PrinterState::Idle;
+            printer.state_message.clear();
+            printer.state_reasons.retain(|reason| reason != "paused");
+        })
+    }
+
+    fn disable_printer(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        self.update(name, |printer| {
+            printer.state = PrinterState::Stopped;
+            printer.state_message = reason.unwrap_or("Paused").to_string();
+            if !printer.state_reasons.iter().any(|reason| reason == "paused") {
+                printer.state_reasons.push("paused".to_string());
+            }
+        })
+    }
+
+    fn accept_jobs(&self, name: &str) -> Result<(), CupsError> {
+        self.update(name, |printer| printer.accepting = true)
+    }
+
+    fn reject_jobs(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        self.update(name, |printer| {
+            printer.accepting = false;
+            if let Some(reason) = reason {
+                printer.state_message = reason.to_string();
+            }
+        })
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, CupsError> {
+        let user_default = self.user_default.lock().unwrap().clone();
+        Ok(user_default.or_else(|| self.system_default.lock().unwrap().clone()))
+    }
+
+    fn set_default_printer(&self, name: &str, scope: DefaultScope) -> Result<(), CupsError> {
+        self.find(name)?;
+        let default = match scope {
+            DefaultScope::System => &self.system_default,
+            DefaultScope::User => &self.user_default,
+        };
+        *default.lock().unwrap() = Some(name.to_string());
+        Ok(())
+    }
+
+    fn get_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+        Ok(self.jobs.lock().unwrap()
+            .iter()
+            .filter(|job| job.completed.is_empty())
+            .filter(|job| printer.is_none_or(|printer| job.printer == printer))
+            .cloned()
+            .collect())
+    }
+
+    fn get_completed_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+        Ok(self.jobs.lock().unwrap()
+            .iter()
+            .filter(|job| !job.completed.is_empty())
+            .filter(|job| printer.is_none_or(|printer| job.printer == printer))
+            .cloned()
+            .collect())
+    }
+
+    fn print_file(&self, printer: &str, path: &Path, title: &str) -> Result<u32, CupsError> {
+        self.find(printer)?;
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        jobs.push(Job {
+            id,
+            printer: printer.to_string(),
+            user: std::env::var("USER").unwrap_or_else(|_| "root".to_string()),
+            title: title.to_string(),
+            size: fs::metadata(path).map_or(0, |metadata| metadata.len()),
+            copies: Some(1),
+            ..Default::default()
+        });
+        Ok(id)
+    }
+
+    fn cancel_job(&self, id: u32) -> Result<(), CupsError> {
+        self.update_job(id, FakeBackend::cancel)
+    }
+
+    fn cancel_all_jobs(&self, printer: &str) -> Result<(), CupsError> {
+        self.find(printer)?;
+        self.jobs.lock().unwrap()
+            .iter_mut()
+            .filter(|job| job.printer == printer && job.completed.is_empty())
+            .for_each(FakeBackend::cancel);
+        Ok(())
+    }
+
+    fn hold_job(&self, id: u32) -> Result<(), CupsError> {
+        self.update_job(id, |job| job.state = JobState::Held)
+    }
+
+    fn release_job(&self, id: u32) -> Result<(), CupsError> {
+        self.update_job(id, |job| job.state = JobState::Pending)
+    }
+
+    fn restart_job(&self, id: u32) -> Result<(), CupsError> {
+        self.update_job(id, |job| job.state = JobState::Pending)
+    }
+
+    fn move_job(&self, id: u32, printer: &str) -> Result<(), CupsError> {
+        self.find(printer)?;
+        self.update_job(id, |job| job.printer = printer.to_string())
+    }
+
+    fn discover_devices(&self, options: &DiscoveryOptions, found: &mut dyn FnMut(Device) -> bool) -> Result<(), CupsError> {
+        for device in self.devices.iter().filter(|device| device.allowed_by(options)) {
+            if !found(device.clone()) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_all_drivers(&self) -> Result<Vec<Driver>, CupsError> {
+        Ok(self.drivers.clone())
+    }
+
+    /// Drivers from the device's manufacturer, a stand-in for cups-driverd's scoring.
+    fn get_matching_drivers(&self, device: &Device) -> Result<Vec<Driver>, CupsError> {
+        let make = device_id_field(&device.device_id, &["MFG", "MANUFACTURER"])
+            .or_else(|| device.make_and_model.split_whitespace().next())
+            .unwrap_or_default();
+        Ok(self.drivers.iter()
+            .filter(|driver| !make.is_empty() && driver.make.eq_ignore_ascii_case(make))
+            .cloned()
+            .collect())
+    }
+
+    fn create_printer(&self, name: &str, device: &str, driver: &str, description: &str, location: &str) -> Result<(), CupsError> {
+        let mut printers = self.printers.lock().unwrap();
+        printers.retain(|printer| printer.name != name);
+        let mut options = PrinterOptions::default();
+        options.insert("device-uri", OptionValue::String(device.to_string()));
+        options.insert("printer-make-and-model", OptionValue::String(self.driver_model(driver)));
+        options.insert("printer-info", OptionValue::String(description.to_string()));
+        options.insert("printer-location", OptionValue::String(location.to_string()));
+        printers.push(Printer {
+            name: name.to_string(),
+            options,
+            state: PrinterState::Idle,
+            ..Default::default()
+        }.with_state_from_options());
+        Ok(())
+    }
+
+    fn modify_printer(&self, name: &str, device: &str, driver: Option<&str>, description: &str, location: &str) -> Result<(), CupsError> {
+        let make_and_model = driver.map(|driver| self.driver_model(driver));
+        self.update(name, |printer| {
+            printer.device_uri = device.to_string();
+            printer.description = description.to_string();
+            printer.location = location.to_string();
+            printer.options.insert("device-uri", OptionValue::String(device.to_string()));
+            printer.options.insert("printer-info", OptionValue::String(description.to_string()));
+            printer.options.insert("printer-location", OptionValue::String(location.to_string()));
+            if let Some(make_and_model) = make_and_model {
+                printer.options.insert("printer-make-and-model", OptionValue::String(make_and_model.clone()));
+                printer.make_and_model = make_and_model;
+            }
+        })
+    }
+
+    fn remove_printer(&self, name: &str) -> Result<(), CupsError> {
+        self.find(name)?;
+        let mut printers = self.printers.lock().unwrap();
+        printers.retain(|printer| printer.name != name);
+        // cupsd drops a deleted printer from its classes, and empty classes with it.
+        for printer in printers.iter_mut() {
+            printer.members.retain(|member| member != name);
+        }
+        printers.retain(|printer| !printer.is_class || !printer.members.is_empty());
+        for default in [&self.system_default, &self.user_default] {
+            default.lock().unwrap().take_if(|default| default == name);
+        }
+        Ok(())
+    }
+
+    fn add_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError> {
+        if self.find(printer)?.is_class {
+            return Err(CupsError::Failed {
+                command: "lpadmin".to_string(),
+                code: Some(1),
+                stderr: format!("{} is a class, only printers can be members", printer),
+            });
+        }
+        let mut printers = self.printers.lock().unwrap();
+        match printers.iter_mut().find(|existing| existing.name == class) {
+            Some(existing) if !existing.is_class => Err(CupsError::Failed {
+                command: "lpadmin".to_string(),
+                code: Some(1),
+                stderr: format!("{} is a printer, not a class", class),
+            }),
+            Some(existing) => {
+                if !existing.members.iter().any(|member| member == printer) {
+                    existing.members.push(printer.to_string());
+                }
+                Ok(())
+            }
+            None => {
+                printers.push(Printer {
+                    name: class.to_string(),
+                    state: PrinterState::Idle,
+                    accepting: true,
+                    is_class: true,
+                    members: vec![printer.to_string()],
+                    ..Default::default()
+                });
+                Ok(())
+            }
+        }
+    }
+
+    fn remove_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError> {
+        self.update(class, |class| class.members.retain(|member| member != printer))?;
+        self.printers.lock().unwrap()
+            .retain(|existing| existing.name != class || !existing.members.is_empty());
+        Ok(())
+    }
+
+    fn remove_class(&self, class: &str) -> Result<(), CupsError> {
+        self.remove_printer(class)
+    }
+}
@@ -0,0 +1,1974 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+
+use super::{
+    backend::CupsBackend,
+    error::CupsError,
+    device::{Device, DiscoveryOptions},
+    driver::{self, Driver},
+    job::{Job, JobState},
+    options::{OptionChoices, OptionValue, PrinterOptions},
+    printer::{self, DefaultScope, Printer},
+    uri::{percent_decode, percent_encode},
+};
+
+pub mod operation {
+    pub const PRINT_JOB: u16 = 0x0002;
+    pub const CANCEL_JOB: u16 = 0x0008;
+    pub const GET_JOBS: u16 = 0x000A;
+    pub const GET_PRINTER_ATTRIBUTES: u16 = 0x000B;
+    pub const HOLD_JOB: u16 = 0x000C;
+    pub const RELEASE_JOB: u16 = 0x000D;
+    pub const RESTART_JOB: u16 = 0x000E;
+    pub const PAUSE_PRINTER: u16 = 0x0010;
+    pub const RESUME_PRINTER: u16 = 0x0011;
+    pub const CANCEL_JOBS: u16 = 0x0038;
+    pub const CUPS_GET_DEFAULT: u16 = 0x4001;
+    pub const CUPS_GET_PRINTERS: u16 = 0x4002;
+    pub const CUPS_ADD_MODIFY_PRINTER: u16 = 0x4003;
+    pub const CUPS_DELETE_PRINTER: u16 = 0x4004;
+    pub const CUPS_ADD_MODIFY_CLASS: u16 = 0x4006;
+    pub const CUPS_DELETE_CLASS: u16 = 0x4007;
+    pub const CUPS_REJECT_JOBS: u16 = 0x4009;
+    pub const CUPS_ACCEPT_JOBS: u16 = 0x4008;
+    pub const CUPS_SET_DEFAULT: u16 = 0x400A;
+    pub const CUPS_GET_DEVICES: u16 = 0x400B;
+    pub const CUPS_GET_PPDS: u16 = 0x400C;
+    pub const CUPS_MOVE_JOB: u16 = 0x400D;
+}
+
+pub mod tag {
+    pub const OPERATION: u8 = 0x01;
+    pub const JOB: u8 = 0x02;
+    pub const END: u8 = 0x03;
+    pub const PRINTER: u8 = 0x04;
+
+    pub const INTEGER: u8 = 0x21;
+    pub const BOOLEAN: u8 = 0x22;
+    pub const ENUM: u8 = 0x23;
+    pub const OCTET_STRING: u8 = 0x30;
+    pub const DATE_TIME: u8 = 0x31;
+    pub const RESOLUTION: u8 = 0x32;
+    pub const RANGE: u8 = 0x33;
+    pub const BEGIN_COLLECTION: u8 = 0x34;
+    pub const TEXT_WITH_LANGUAGE: u8 = 0x35;
+    pub const NAME_WITH_LANGUAGE: u8 = 0x36;
+    pub const END_COLLECTION: u8 = 0x37;
+    pub const TEXT: u8 = 0x41;
+    pub const NAME: u8 = 0x42;
+    pub const KEYWORD: u8 = 0x44;
+    pub const URI: u8 = 0x45;
+    pub const CHARSET: u8 = 0x47;
+    pub const MIME_TYPE: u8 = 0x49;
+    pub const LANGUAGE: u8 = 0x48;
+    pub const MEMBER_NAME: u8 = 0x4A;
+}
+
+const PRINTER_STATE_IDLE: i32 = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IppValue {
+    Integer(i32),
+    Boolean(bool),
+    Enum(i32),
+    /// Any of the character string syntaxes, tagged with its value tag.
+    String(u8, String),
+    OctetString(Vec<u8>),
+    DateTime(Vec<u8>),
+    Resolution(i32, i32, i8),
+    Range(i32, i32),
+    Collection(Vec<IppAttribute>),
+    /// Out-of-band values (`no-value`, `unknown`...) and tags we don't decode.
+    Other(u8, Vec<u8>),
+}
+
+impl IppValue {
+    pub fn keyword(value: &str) -> Self {
+        IppValue::String(tag::KEYWORD, value.to_string())
+    }
+
+    pub fn uri(value: &str) -> Self {
+        IppValue::String(tag::URI, value.to_string())
+    }
+
+    pub fn name(value: &str) -> Self {
+        IppValue::String(tag::NAME, value.to_string())
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match self {
+            IppValue::Integer(value) | IppValue::Enum(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            IppValue::String(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    fn tag(&self) -> u8 {
+        match self {
+            IppValue::Integer(_) => tag::INTEGER,
+            IppValue::Boolean(_) => tag::BOOLEAN,
+            IppValue::Enum(_) => tag::ENUM,
+            IppValue::String(tag, _) => *tag,
+            IppValue::OctetString(_) => tag::OCTET_STRING,
+            IppValue::DateTime(_) => tag::DATE_TIME,
+            IppValue::Resolution(..) => tag::RESOLUTION,
+            IppValue::Range(..) => tag::RANGE,
+            IppValue::Collection(_) => tag::BEGIN_COLLECTION,
+            IppValue::Other(tag, _) => *tag,
+        }
+    }
+}
+
+impl fmt::Display for IppValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IppValue::Integer(value) | IppValue::Enum(value) => write!(f, "{}", value),
+            IppValue::Boolean(value) => write!(f, "{}", value),
+            IppValue::String(_, value) => write!(f, "{}", value),
+            IppValue::OctetString(value) => write!(f, "{}", String::from_utf8_lossy(value)),
+            IppValue::DateTime(value) if let Some(time) = local_time(value) => write!(f, "{}", time.format("%Y-%m-%d %H:%M:%S")),
+            IppValue::DateTime(value) if value.len() >= 7 => write!(
+                f,
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+                u16::from_be_bytes([value[0], value[1]]),
+                value[2], value[3], value[4], value[5], value[6]
+            ),
+            IppValue::DateTime(_) => Ok(()),
+            IppValue::Resolution(x, y, 3) => write!(f, "{}x{}dpi", x, y),
+            IppValue::Resolution(x, y, _) => write!(f, "{}x{}dpcm", x, y),
+            IppValue::Range(lower, upper) => write!(f, "{}-{}", lower, upper),
+            IppValue::Collection(members) => {
+                let members: Vec<String> = members.iter()
+                    .map(|member| format!("{}={}", member.name, join_values(&member.values)))
+                    .collect();
+                write!(f, "{{{}}}", members.join(" "))
+            }
+            IppValue::Other(..) => Ok(()),
+        }
+    }
+}
+
+/// A `dateTime` in the local time zone, the way lpstat shows times, cupsd
+/// sending them in UTC. `None` when the value is malformed.
+fn local_time(value: &[u8]) -> Option<DateTime<Local>> {
+    let [year_high, year_low, month, day, hour, minute, second, _deciseconds, direction, offset_hours, offset_minutes] = *value else {
+        return None;
+    };
+    let time = NaiveDate::from_ymd_opt(u16::from_be_bytes([year_high, year_low]) as i32, month as u32, day as u32)?
+        .and_hms_opt(hour as u32, minute as u32, second as u32)?;
+    let offset = (offset_hours as i32 * 60 + offset_minutes as i32) * 60;
+    let offset = FixedOffset::east_opt(if direction == b'-' { -offset } else { offset })?;
+    Some(time.and_local_timezone(offset).single()?.with_timezone(&Local))
+}
+
+fn join_values(values: &[IppValue]) -> String {
+    values.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IppAttribute {
+    pub name: String,
+    pub values: Vec<IppValue>,
+}
+
+impl IppAttribute {
+    pub fn new(name: &str, value: IppValue) -> Self {
+        IppAttribute {
+            name: name.to_string(),
+            values: vec![value],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IppGroup {
+    pub tag: u8,
+    pub attributes: Vec<IppAttribute>,
+}
+
+impl IppGroup {
+    pub fn get(&self, name: &str) -> Option<&IppAttribute> {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i32> {
+        self.get(name)
+            .and_then(|attribute| attribute.values.first())
+            .and_then(|value| value.as_int())
+    }
+
+    pub fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name)
+            .and_then(|attribute| attribute.values.first())
+            .and_then(|value| value.as_str())
+    }
+}
+
+/// An IPP request or response, `code` being the operation or status code.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IppMessage {
+    pub version: (u8, u8),
+    pub code: u16,
+    pub request_id: u32,
+    pub groups: Vec<IppGroup>,
+    pub data: Vec<u8>,
+}
+
+impl IppMessage {
+    /// A request carrying the mandatory charset and natural language attributes.
+    pub fn request(operation: u16, request_id: u32) -> Self {
+        IppMessage {
+            version: (2, 0),
+            code: operation,
+            request_id,
+            groups: vec![IppGroup {
+                tag: tag::OPERATION,
+                attributes: vec![
+                    IppAttribute::new("attributes-charset", IppValue::String(tag::CHARSET, "utf-8".to_string())),
+                    IppAttribute::new("attributes-natural-language", IppValue::String(tag::LANGUAGE, "en".to_string())),
+                ],
+            }],
+            data: vec![],
+        }
+    }
+
+    /// Adds an attribute to the last group with the given tag, opening it if needed.
+    pub fn add(&mut self, group_tag: u8, name: &str, values: Vec<IppValue>) -> &mut Self {
+        let attribute = IppAttribute {
+            name: name.to_string(),
+            values,
+        };
+        match self.groups.iter_mut().rev().find(|group| group.tag == group_tag) {
+            Some(group) => group.attributes.push(attribute),
+            None => self.groups.push(IppGroup {
+                tag: group_tag,
+                attributes: vec![attribute],
+            }),
+        }
+        self
+    }
+
+    pub fn is_successful(&self) -> bool {
+        self.code < 0x0100
+    }
+
+    pub fn groups(&self, group_tag: u8) -> impl Iterator<Item = &IppGroup> {
+        self.groups.iter().filter(move |group| group.tag == group_tag)
+    }
+
+    pub fn status_message(&self) -> Option<&str> {
+        self.groups(tag::OPERATION).find_map(|group| group.get_str("status-message"))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = vec![self.version.0, self.version.1];
+        buf.extend_from_slice(&self.code.to_be_bytes());
+        buf.extend_from_slice(&self.request_id.to_be_bytes());
+        for group in &self.groups {
+            buf.push(group.tag);
+            for attribute in &group.attributes {
+                encode_attribute(&mut buf, attribute);
+            }
+        }
+        buf.push(tag::END);
+        buf.extend_from_slice(&self.data);
+        buf
+    }
+
+    pub fn decode(bytes: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let version = (reader.u8()?, reader.u8()?);
+        let code = reader.u16()?;
+        let request_id = reader.u32()?;
+        let mut groups: Vec<IppGroup> = vec![];
+        loop {
+            let value_tag = reader.u8()?;
+            match value_tag {
+                tag::END => break,
+                0x00..=0x0F => groups.push(IppGroup {
+                    tag: value_tag,
+                    attributes: vec![],
+                }),
+                _ => {
+                    let group = groups.last_mut().ok_or_else(|| invalid("attribute outside of a group"))?;
+                    let name = reader.string()?;
+                    let value = reader.value(value_tag)?;
+                    if name.is_empty() {
+                        let attribute = group.attributes.last_mut()
+                            .ok_or_else(|| invalid("additional value without attribute"))?;
+                        attribute.values.push(value);
+                    } else {
+                        group.attributes.push(IppAttribute {
+                            name,
+                            values: vec![value],
+                        });
+                    }
+                }
+            }
+        }
+        Ok(IppMessage {
+            version,
+            code,
+            request_id,
+            groups,
+            data: bytes[reader.pos..].to_vec(),
+        })
+    }
+}
+
+fn encode_attribute(buf: &mut Vec<u8>, attribute: &IppAttribute) {
+    for (index, value) in attribute.values.iter().enumerate() {
+        let name = if index == 0 { attribute.name.as_str() } else { "" };
+        encode_value(buf, name, value);
+    }
+}
+
+fn encode_value(buf: &mut Vec<u8>, name: &str, value: &IppValue) {
+    buf.push(value.tag());
+    push_bytes(buf, name.as_bytes());
+    match value {
+        IppValue::Integer(value) | IppValue::Enum(value) => push_bytes(buf, &value.to_be_bytes()),
+        IppValue::Boolean(value) => push_bytes(buf, &[*value as u8]),
+        IppValue::String(_, value) => push_bytes(buf, value.as_bytes()),
+        IppValue::OctetString(value) | IppValue::DateTime(value) | IppValue::Other(_, value) => push_bytes(buf, value),
+        IppValue::Resolution(x, y, units) => {
+            let mut bytes = x.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&y.to_be_bytes());
+            bytes.push(*units as u8);
+            push_bytes(buf, &bytes);
+        }
+        IppValue::Range(lower, upper) => {
+            let mut bytes = lower.to_be_bytes().to_vec();
+            bytes.extend_from_slice(&upper.to_be_bytes());
+            push_bytes(buf, &bytes);
+        }
+        IppValue::Collection(members) => {
+            push_bytes(buf, &[]);
+            for member in members {
+                for (index, value) in member.values.iter().enumerate() {
+                    if index == 0 {
+                        buf.push(tag::MEMBER_NAME);
+                        push_bytes(buf, &[]);
+                        push_bytes(buf, member.name.as_bytes());
+                    }
+                    encode_value(buf, "", value);
+                }
+            }
+            buf.push(tag::END_COLLECTION);
+            push_bytes(buf, &[]);
+            push_bytes(buf, &[]);
+        }
+    }
+}
+
+fn push_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+    buf.extend_from_slice(bytes);
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Malformed IPP message: {}", message))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> io::Result<&[u8]> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            return Err(invalid("unexpected end of data"));
+        }
+        let slice = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> io::Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.u16()? as usize;
+        Ok(self.take(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        Ok(String::from_utf8_lossy(&self.bytes()?).into_owned())
+    }
+
+    fn value(&mut self, value_tag: u8) -> io::Result<IppValue> {
+        let bytes = self.bytes()?;
+        let int = |bytes: &[u8]| -> io::Result<i32> {
+            bytes.get(..4)
+                .map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or_else(|| invalid("integer value too short"))
+        };
+        Ok(match value_tag {
+            tag::INTEGER => IppValue::Integer(int(&bytes)?),
+            tag::ENUM => IppValue::Enum(int(&bytes)?),
+            tag::BOOLEAN => IppValue::Boolean(bytes.first().is_some_and(|b| *b != 0)),
+            tag::OCTET_STRING => IppValue::OctetString(bytes),
+            tag::DATE_TIME => IppValue::DateTime(bytes),
+            tag::RESOLUTION if bytes.len() == 9 => IppValue::Resolution(int(&bytes)?, int(&bytes[4..])?, bytes[8] as i8),
+            tag::RANGE if bytes.len() == 8 => IppValue::Range(int(&bytes)?, int(&bytes[4..])?),
+            tag::TEXT_WITH_LANGUAGE | tag::NAME_WITH_LANGUAGE => {
+                let mut inner = Reader { bytes: &bytes, pos: 0 };
+                let _language = inner.string()?;
+                let text = inner.string()?;
+                let value_tag = if value_tag == tag::TEXT_WITH_LANGUAGE { tag::TEXT } else { tag::NAME };
+                IppValue::String(value_tag, text)
+            }
+            tag::BEGIN_COLLECTION => IppValue::Collection(self.collection()?),
+            0x40..=0x5F => IppValue::String(value_tag, String::from_utf8_lossy(&bytes).into_owned()),
+            _ => IppValue::Other(value_tag, bytes),
+        })
+    }
+
+    fn collection(&mut self) -> io::Result<Vec<IppAttribute>> {
+        let mut members: Vec<IppAttribute> = vec![];
+        loop {
+            let value_tag = self.u8()?;
+            let _name = self.string()?;
+            match value_tag {
+                tag::END_COLLECTION => {
+                    self.bytes()?;
+                    return Ok(members);
+                }
+                tag::MEMBER_NAME => members.push(IppAttribute {
+                    name: self.string()?,
+                    values: vec![],
+                }),
+                _ => {
+                    let value = self.value(value_tag)?;
+                    members.last_mut()
+                        .ok_or_else(|| invalid("collection value without member name"))?
+                        .values.push(value);
+                }
+            }
+        }
+    }
+}
+
+/// Where cupsd listens, a socket path or `host[:port]` like `CUPS_SERVER`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Server {
+    Socket(PathBuf),
+    Host(String),
+}
+
+impl Server {
+    pub const DEFAULT_SOCKET: &'static str = "/run/cups/cups.sock";
+
+    pub fn parse(value: &str) -> Self {
+        if value.starts_with('/') {
+            Server::Socket(PathBuf::from(value))
+        } else if value.contains(':') {
+            Server::Host(value.to_string())
+        } else {
+            Server::Host(format!("{}:631", value))
+        }
+    }
+
+    /// `CUPS_SERVER` when set, else `configured` when not empty, else the local socket.
+    pub fn from_env_or(configured: &str) -> Self {
+        match std::env::var("CUPS_SERVER") {
+            Ok(value) if !value.is_empty() => Server::parse(&value),
+            _ if !configured.is_empty() => Server::parse(configured),
+            _ if Path::new(Server::DEFAULT_SOCKET).exists() => Server::Socket(PathBuf::from(Server::DEFAULT_SOCKET)),
+            _ => Server::Host("localhost:631".to_string()),
+        }
+    }
+
+    fn host(&self) -> &str {
+        match self {
+            Server::Socket(_) => "localhost",
+            Server::Host(host) => host,
+        }
+    }
+
+    /// POSTs an `application/ipp` body and returns the response body,
+    /// failing with `CupsError::Unreachable` when nothing answers.
+    pub fn post(&self, path: &str, body: &[u8], timeout: Duration) -> Result<Vec<u8>, CupsError> {
+        let unreachable = |error: io::Error| CupsError::Unreachable(format!("{}: {}", self, error));
+        let response = match self {
+            Server::Socket(socket) => {
+                let stream = UnixStream::connect(socket).map_err(unreachable)?;
+                stream.set_read_timeout(Some(timeout))?;
+                http_post(stream, self.host(), path, body)
+            }
+            Server::Host(host) => {
+                let stream = TcpStream::connect(host).map_err(unreachable)?;
+                stream.set_read_timeout(Some(timeout))?;
+                http_post(stream, self.host(), path, body)
+            }
+        };
+        Ok(response?)
+    }
+}
+
+impl fmt::Display for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Server::Socket(socket) => write!(f, "{}", socket.display()),
+            Server::Host(host) => write!(f, "{}", host),
+        }
+    }
+}
+
+fn http_post<S: Read + Write>(mut stream: S, host: &str, path: &str, body: &[u8]) -> io::Result<Vec<u8>> {
+    let header = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path, host, body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    stream.write_all(body)?;
+    stream.flush()?;
+
+    let mut response = vec![];
+    stream.read_to_end(&mut response)?;
+    let split = response.windows(4).position(|window| window == b"\r\n\r\n")
+        .ok_or_else(|| invalid("missing HTTP header"))?;
+    let head = String::from_utf8_lossy(&response[..split]).to_string();
+    let body = &response[split + 4..];
+
+    let mut lines = head.lines();
+    let status = lines.next().unwrap_or_default();
+    let code = status.split_whitespace().nth(1).unwrap_or_default();
+    match code {
+        "200" => {}
+        "401" | "403" => return Err(io::Error::new(io::ErrorKind::PermissionDenied, status.to_string())),
+        _ => return Err(io::Error::other(status.to_string())),
+    }
+    let chunked = lines.any(|line| {
+        let line = line.to_ascii_lowercase();
+        line.starts_with("transfer-encoding:") && line.contains("chunked")
+    });
+    if chunked { dechunk(body) } else { Ok(body.to_vec()) }
+}
+
+fn dechunk(mut body: &[u8]) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    loop {
+        let line_end = body.windows(2).position(|window| window == b"\r\n")
+            .ok_or_else(|| invalid("truncated chunk"))?;
+        let size = String::from_utf8_lossy(&body[..line_end]);
+        let size = usize::from_str_radix(size.split(';').next().unwrap_or_default().trim(), 16)
+            .map_err(|_| invalid("bad chunk size"))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(data);
+        }
+        if body.len() < size {
+            return Err(invalid("truncated chunk"));
+        }
+        data.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or_default();
+    }
+}
+
+/// Attributes shown as a printer's options, like `lpoptions -p` does.
+const OPTION_ATTRIBUTES: [&str; 19] = [
+    "copies-default",
+    "device-uri",
+    "finishings-default",
+    "job-sheets-default",
+    "media-default",
+    "member-names",
+    "number-up-default",
+    "orientation-requested-default",
+    "print-color-mode-default",
+    "printer-info",
+    "printer-is-accepting-jobs",
+    "printer-is-shared",
+    "printer-location",
+    "printer-make-and-model",
+    "printer-state",
+    "printer-state-message",
+    "printer-state-reasons",
+    "printer-type",
+    "sides-default",
+];
+
+/// Attributes making up a `Job`.
+const JOB_ATTRIBUTES: [&str; 11] = [
+    "job-id",
+    "job-printer-uri",
+    "job-originating-user-name",
+    "job-name",
+    "job-k-octets",
+    "job-state",
+    "date-time-at-creation",
+    "date-time-at-completed",
+    "job-impressions-completed",
+    "copies",
+    "job-state-reasons",
+];
+
+/// Job template attributes offered for editing, read from `<name>-supported`.
+const CHOICE_ATTRIBUTES: [&str; 5] = [
+    "media",
+    "sides",
+    "print-color-mode",
+    "print-quality",
+    "output-bin",
+];
+
+const DRIVER_ATTRIBUTES: [&str; 5] = [
+    "ppd-name",
+    "ppd-make",
+    "ppd-make-and-model",
+    "ppd-natural-language",
+    "ppd-device-id",
+];
+
+/// Backend speaking IPP to cupsd directly, without the `lp*` binaries.
+#[derive(Debug)]
+pub struct IppBackend {
+    server: Server,
+    user: String,
+    request_id: AtomicU32,
+    timeout: Duration,
+    /// The user's lpoptions file, holding their own default destination.
+    lpoptions: Option<PathBuf>,
+}
+
+impl IppBackend {
+    pub fn new(server: Server) -> Self {
+        IppBackend {
+            server,
+            user: std::env::var("USER").unwrap_or_else(|_| "root".to_string()),
+            request_id: AtomicU32::new(1),
+            timeout: Duration::from_secs(60),
+            lpoptions: printer::user_lpoptions(),
+        }
+    }
+
+    fn printer_uri(&self, name: &str) -> String {
+        format!("ipp://localhost/printers/{}", percent_encode(name))
+    }
+
+    fn class_uri(&self, name: &str) -> String {
+        format!("ipp://localhost/classes/{}", percent_encode(name))
+    }
+
+    fn job_uri(&self, id: u32) -> String {
+        format!("ipp://localhost/jobs/{}", id)
+    }
+
+    fn request(&self, operation: u16) -> IppMessage {
+        let mut request = IppMessage::request(operation, self.request_id.fetch_add(1, Ordering::Relaxed));
+        request.add(tag::OPERATION, "requesting-user-name", vec![IppValue::name(&self.user)]);
+        request
+    }
+
+    /// Posts a request and decodes the response, whatever its status.
+    fn exchange(&self, path: &str, request: &IppMessage) -> Result<IppMessage, CupsError> {
+        let body = self.server.post(path, &request.encode(), self.timeout)?;
+        Ok(IppMessage::decode(&body)?)
+    }
+
+    pub fn send(&self, path: &str, request: &IppMessage) -> Result<IppMessage, CupsError> {
+        IppBackend::check(self.exchange(path, request)?)
+    }
+
+    /// Turns an unsuccessful response into the matching error.
+    fn check(response: IppMessage) -> Result<IppMessage, CupsError> {
+        if response.is_successful() {
+            Ok(response)
+        } else {
+            let message = response.status_message()
+                .map_or(format!("IPP status 0x{:04x}", response.code), |message| message.to_string());
+            match response.code {
+                0x0401..=0x0403 => Err(CupsError::PermissionDenied(message)),
+                _ => Err(CupsError::Server(message)),
+            }
+        }
+    }
+
+    /// Every printer and class with its options, in a single CUPS-Get-Printers.
+    fn printers(&self) -> Result<Vec<Printer>, CupsError> {
+        let mut request = self.request(operation::CUPS_GET_PRINTERS);
+        request.add(tag::OPERATION, "requested-attributes", std::iter::once("printer-name")
+            .chain(OPTION_ATTRIBUTES)
+            .map(IppValue::keyword)
+            .collect());
+        let response = self.send("/", &request)?;
+        Ok(response.groups(tag::PRINTER)
+            .filter_map(|group| Some(Printer {
+                name: group.get_str("printer-name")?.to_string(),
+                options: printer_options(group),
+                ..Default::default()
+            }.with_state_from_options()))
+            .collect())
+    }
+
+    fn option_choices(&self, name: &str) -> Result<Vec<OptionChoices>, CupsError> {
+        let mut request = self.request(operation::GET_PRINTER_ATTRIBUTES);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(name))]);
+        request.add(tag::OPERATION, "requested-attributes", CHOICE_ATTRIBUTES.iter()
+            .flat_map(|name| [format!("{}-supported", name), format!("{}-default", name)])
+            .map(|name| IppValue::keyword(&name))
+            .collect());
+        let response = self.send("/", &request)?;
+        let Some(group) = response.groups(tag::PRINTER).next() else {
+            return Ok(vec![]);
+        };
+        Ok(CHOICE_ATTRIBUTES.iter()
+            .filter_map(|name| {
+                let supported = group.get(&format!("{}-supported", name))?;
+                Some(OptionChoices {
+                    name: name.to_string(),
+                    label: name.to_string(),
+                    choices: supported.values.iter().map(|value| value.to_string()).collect(),
+                    default: group.get_str(&format!("{}-default", name)).map(|value| value.to_string()),
+                })
+            })
+            .collect())
+    }
+
+    fn set_option(&self, name: &str, option: &str, value: &str) -> Result<(), CupsError> {
+        let attribute = if option.starts_with("printer-") {
+            option.to_string()
+        } else {
+            format!("{}-default", option)
+        };
+        let value = match OptionValue::from_raw(value, false) {
+            OptionValue::Bool(value) => IppValue::Boolean(value),
+            OptionValue::Int(value) => IppValue::Integer(value as i32),
+            OptionValue::Enum(value) => IppValue::keyword(&value),
+            _ if option.starts_with("printer-") => IppValue::String(tag::TEXT, value.to_string()),
+            _ => IppValue::name(value),
+        };
+        let mut request = self.request(operation::CUPS_ADD_MODIFY_PRINTER);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(name))]);
+        request.add(tag::PRINTER, &attribute, vec![value]);
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+
+    /// Sends one of the printer control operations, with an optional reason as state message.
+    fn control_printer(&self, operation: u16, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        let mut request = self.request(operation);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(name))]);
+        if let Some(reason) = reason {
+            request.add(tag::OPERATION, "printer-state-message", vec![IppValue::String(tag::TEXT, reason.to_string())]);
+        }
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+
+    fn jobs(&self, printer: Option<&str>, which_jobs: &str) -> Result<Vec<Job>, CupsError> {
+        let printer_uri = printer.map_or("ipp://localhost/".to_string(), |printer| self.printer_uri(printer));
+        let mut request = self.request(operation::GET_JOBS);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&printer_uri)]);
+        request.add(tag::OPERATION, "which-jobs", vec![IppValue::keyword(which_jobs)]);
+        request.add(tag::OPERATION, "requested-attributes",
+            JOB_ATTRIBUTES.iter().map(|name| IppValue::keyword(name)).collect());
+        let response = self.send("/", &request)?;
+        Ok(response.groups(tag::JOB)
+            .filter_map(|group| Some(Job {
+                id: group.get_int("job-id")? as u32,
+                printer: group.get_str("job-printer-uri")
+                    .and_then(|uri| uri.rsplit('/').next())
+                    .map_or(String::new(), percent_decode),
+                user: group.get_str("job-originating-user-name").unwrap_or_default().to_string(),
+                title: group.get_str("job-name").unwrap_or_default().to_string(),
+                size: group.get_int("job-k-octets").unwrap_or(0) as u64 * 1024,
+                state: JobState::from_ipp(group.get_int("job-state").unwrap_or(3)),
+                submitted: group.get("date-time-at-creation")
+                    .and_then(|attribute| attribute.values.first())
+                    .map_or(String::new(), |value| value.to_string()),
+                completed: group.get("date-time-at-completed")
+                    .and_then(|attribute| attribute.values.first())
+                    .filter(|value| matches!(value, IppValue::DateTime(_)))
+                    .map_or(String::new(), |value| value.to_string()),
+                pages: group.get_int("job-impressions-completed").map(|pages| pages as u32),
+                copies: group.get_int("copies").map(|copies| copies as u32),
+                state_reasons: group.get("job-state-reasons")
+                    .map_or(vec![], |attribute| attribute.values.iter().map(|value| value.to_string()).collect()),
+            }))
+            .collect())
+    }
+
+    /// Sends one of the job operations, addressed by job URI.
+    fn control_job(&self, operation: u16, id: u32) -> Result<(), CupsError> {
+        let mut request = self.request(operation);
+        request.add(tag::OPERATION, "job-uri", vec![IppValue::uri(&self.job_uri(id))]);
+        self.send("/jobs/", &request)?;
+        Ok(())
+    }
+
+    fn devices(&self, options: &DiscoveryOptions) -> Result<Vec<Device>, CupsError> {
+        let mut request = self.request(operation::CUPS_GET_DEVICES);
+        if let Some(timeout) = options.timeout {
+            request.add(tag::OPERATION, "timeout", vec![IppValue::Integer(timeout as i32)]);
+        }
+        for (name, schemes) in [("include-schemes", &options.include_schemes), ("exclude-schemes", &options.exclude_schemes)] {
+            if !schemes.is_empty() {
+                request.add(tag::OPERATION, name, schemes.iter().map(|scheme| IppValue::name(scheme)).collect());
+            }
+        }
+        let response = self.send("/", &request)?;
+        Ok(response.groups(tag::PRINTER)
+            .filter_map(|group| {
+                let text = |name: &str| group.get_str(name).unwrap_or_default().to_string();
+                Some(Device {
+                    uri: group.get_str("device-uri")?.to_string(),
+                    class: text("device-class"),
+                    info: text("device-info"),
+                    make_and_model: text("device-make-and-model"),
+                    device_id: text("device-id"),
+                    location: text("device-location"),
+                })
+            })
+            .collect())
+    }
+
+    /// PPDs cupsd knows, those matching `filter` (an attribute and its value) when given.
+    fn drivers(&self, filter: Option<(&str, &str)>) -> Result<Vec<Driver>, CupsError> {
+        let mut request = self.request(operation::CUPS_GET_PPDS);
+        if let Some((name, value)) = filter {
+            request.add(tag::OPERATION, name, vec![IppValue::String(tag::TEXT, value.to_string())]);
+        }
+        request.add(tag::OPERATION, "requested-attributes",
+            DRIVER_ATTRIBUTES.iter().map(|name| IppValue::keyword(name)).collect());
+        let response = self.send("/", &request)?;
+        Ok(response.groups(tag::PRINTER)
+            .filter_map(|group| {
+                let text = |name: &str| group.get_str(name).unwrap_or_default().to_string();
+                Some(Driver {
+                    ppd_name: group.get_str("ppd-name")?.to_string(),
+                    make: text("ppd-make"),
+                    make_and_model: text("ppd-make-and-model"),
+                    natural_language: text("ppd-natural-language"),
+                    device_id: text("ppd-device-id"),
+                    ..Default::default()
+                }.complete())
+            })
+            .collect())
+    }
+
+    fn add_printer(&self, name: &str, device: &str, driver: &str, description: &str, location: &str) -> Result<(), CupsError> {
+        let mut request = self.request(operation::CUPS_ADD_MODIFY_PRINTER);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(name))]);
+        request.add(tag::OPERATION, "ppd-name", vec![IppValue::name(driver)]);
+        request.add(tag::PRINTER, "device-uri", vec![IppValue::uri(device)]);
+        if !description.is_empty() {
+            request.add(tag::PRINTER, "printer-info", vec![IppValue::String(tag::TEXT, description.to_string())]);
+        }
+        if !location.is_empty() {
+            request.add(tag::PRINTER, "printer-location", vec![IppValue::String(tag::TEXT, location.to_string())]);
+        }
+        request.add(tag::PRINTER, "printer-is-accepting-jobs", vec![IppValue::Boolean(true)]);
+        request.add(tag::PRINTER, "printer-state", vec![IppValue::Enum(PRINTER_STATE_IDLE)]);
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+
+    /// Changes an existing printer, only sending a `ppd-name` when switching drivers.
+    fn modify_printer_attributes(&self, name: &str, device: &str, driver: Option<&str>, description: &str, location: &str) -> Result<(), CupsError> {
+        let mut request = self.request(operation::CUPS_ADD_MODIFY_PRINTER);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(name))]);
+        if let Some(driver) = driver {
+            request.add(tag::OPERATION, "ppd-name", vec![IppValue::name(driver)]);
+        }
+        request.add(tag::PRINTER, "device-uri", vec![IppValue::uri(device)]);
+        request.add(tag::PRINTER, "printer-info", vec![IppValue::String(tag::TEXT, description.to_string())]);
+        request.add(tag::PRINTER, "printer-location", vec![IppValue::String(tag::TEXT, location.to_string())]);
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+
+    /// Members of a class, none when it doesn't exist yet.
+    fn class_members(&self, class: &str) -> Result<Vec<String>, CupsError> {
+        let mut request = self.request(operation::GET_PRINTER_ATTRIBUTES);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.class_uri(class))]);
+        request.add(tag::OPERATION, "requested-attributes", vec![IppValue::keyword("member-names")]);
+        let response = self.exchange("/", &request)?;
+        if response.code == 0x0406 {
+            return Ok(vec![]);
+        }
+        Ok(IppBackend::check(response)?.groups(tag::PRINTER)
+            .filter_map(|group| group.get("member-names"))
+            .flat_map(|attribute| attribute.values.iter().map(|value| value.to_string()))
+            .collect())
+    }
+
+    /// Replaces the members of a class, creating it if needed.
+    fn set_class_members(&self, class: &str, members: &[String]) -> Result<(), CupsError> {
+        let mut request = self.request(operation::CUPS_ADD_MODIFY_CLASS);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.class_uri(class))]);
+        request.add(tag::PRINTER, "member-uris",
+            members.iter().map(|member| IppValue::uri(&self.printer_uri(member))).collect());
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+
+    fn delete_class(&self, class: &str) -> Result<(), CupsError> {
+        let mut request = self.request(operation::CUPS_DELETE_CLASS);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.class_uri(class))]);
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+
+    fn delete_printer(&self, name: &str) -> Result<(), CupsError> {
+        let mut request = self.request(operation::CUPS_DELETE_PRINTER);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(name))]);
+        self.send("/admin/", &request)?;
+        Ok(())
+    }
+}
+
+/// The option attributes of a printer group, sorted by name and without
+/// their `-default` suffix, like `lpoptions -p` shows them.
+fn printer_options(group: &IppGroup) -> PrinterOptions {
+    let mut attributes: Vec<&IppAttribute> = group.attributes.iter()
+        .filter(|attribute| OPTION_ATTRIBUTES.contains(&attribute.name.as_str()))
+        .collect();
+    attributes.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut options = PrinterOptions::default();
+    for attribute in attributes {
+        let name = attribute.name.strip_suffix("-default").unwrap_or(&attribute.name);
+        options.insert(name, option_value(&attribute.values));
+    }
+    options
+}
+
+fn option_value(values: &[IppValue]) -> OptionValue {
+    match values {
+        [IppValue::Integer(value)] | [IppValue::Enum(value)] => OptionValue::Int(*value as i64),
+        [IppValue::Boolean(value)] => OptionValue::Bool(*value),
+        [IppValue::String(tag::KEYWORD, value)] => OptionValue::Enum(value.clone()),
+        [value] => OptionValue::String(value.to_string()),
+        _ => OptionValue::List(values.iter().map(|value| value.to_string()).collect()),
+    }
+}
+
+impl CupsBackend for IppBackend {
+    fn get_all_printers(&self) -> Result<Vec<Printer>, CupsError> {
+        self.printers()
+    }
+
+    fn get_printer_option_choices(&self, name: &str) -> Result<Vec<OptionChoices>, CupsError> {
+        self.option_choices(name)
+    }
+
+    fn set_printer_option(&self, name: &str, option: &str, value: &str) -> Result<(), CupsError> {
+        self.set_option(name, option, value)
+    }
+
+    fn enable_printer(&self, name: &str) -> Result<(), CupsError> {
+        self.control_printer(operation::RESUME_PRINTER, name, None)
+    }
+
+    fn disable_printer(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        self.control_printer(operation::PAUSE_PRINTER, name, reason)
+    }
+
+    fn accept_jobs(&self, name: &str) -> Result<(), CupsError> {
+        self.control_printer(operation::CUPS_ACCEPT_JOBS, name, None)
+    }
+
+    fn reject_jobs(&self, name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+        self.control_printer(operation::CUPS_REJECT_JOBS, name, reason)
+    }
+
+    fn get_default_printer(&self) -> Result<Option<String>, CupsError> {
+        if let Some(name) = self.lpoptions.as_deref().and_then(printer::get_user_default) {
+            return Ok(Some(name));
+        }
+        let mut request = self.request(operation::CUPS_GET_DEFAULT);
+        request.add(tag::OPERATION, "requested-attributes", vec![IppValue::keyword("printer-name")]);
+        let response = self.exchange("/", &request)?;
+        // cupsd answers client-error-not-found when there is no default.
+        if response.code == 0x0406 {
+            return Ok(None);
+        }
+        Ok(IppBackend::check(response)?.groups(tag::PRINTER)
+            .find_map(|group| group.get_str("printer-name"))
+            .map(|name| name.to_string()))
+    }
+
+    fn set_default_printer(&self, name: &str, scope: DefaultScope) -> Result<(), CupsError> {
+        match scope {
+            DefaultScope::System => self.control_printer(operation::CUPS_SET_DEFAULT, name, None),
+            DefaultScope::User => {
+                let lpoptions = self.lpoptions.as_deref()
+                    .ok_or_else(|| CupsError::Parse("no home directory for ~/.cups/lpoptions".to_string()))?;
+                printer::set_user_default(lpoptions, name)
+            }
+        }
+    }
+
+    fn get_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+        self.jobs(printer, "not-completed")
+    }
+
+    fn get_completed_jobs(&self, printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+        self.jobs(printer, "completed")
+    }
+
+    fn print_file(&self, printer: &str, path: &Path, title: &str) -> Result<u32, CupsError> {
+        let mut request = self.request(operation::PRINT_JOB);
+        request.add(tag::OPERATION, "printer-uri", vec![IppValue::uri(&self.printer_uri(printer))]);
+        request.add(tag::OPERATION, "job-name", vec![IppValue::name(title)]);
+        // Left to cupsd to auto-type, the way lp does.
+        request.add(tag::OPERATION, "document-format", vec![IppValue::String(tag::MIME_TYPE, "application/octet-stream".to_string())]);
+        request.data = fs::read(path)?;
+        let response = self.send("/", &request)?;
+        response.groups(tag::JOB)
+            .find_map(|group| group.get_int("job-id"))
+            .map(|id| id as u32)
+            .ok_or_else(|| CupsError::Parse("Print-Job response without a job-id".to_string()))
+    }
+
+    fn cancel_job(&self, id: u32) -> Result<(), CupsError> {
+        self.control_job(operation::CANCEL_JOB, id)
+    }
+
+    fn cancel_all_jobs(&self, printer: &str) -> Result<(), CupsError> {
+        self.control_printer(operation::CANCEL_JOBS, printer, None)
+    }
+
+    fn hold_job(&self, id: u32) -> Result<(), CupsError> {
+        self.control_job(operation::HOLD_JOB, id)
+    }
+
+    fn release_job(&self, id: u32) -> Result<(), CupsError> {
+        self.control_job(operation::RELEASE_JOB, id)
+    }
+
+    fn restart_job(&self, id: u32) -> Result<(), CupsError> {
+        self.control_job(operation::RESTART_JOB, id)
+    }
+
+    fn move_job(&self, id: u32, printer: &str) -> Result<(), CupsError> {
+        let mut request = self.request(operation::CUPS_MOVE_JOB);
+        request.add(tag::OPERATION, "job-uri", vec![IppValue::uri(&self.job_uri(id))]);
+        request.add(tag::JOB, "job-printer-uri", vec![IppValue::uri(&self.printer_uri(printer))]);
+        self.send("/jobs/", &request)?;
+        Ok(())
+    }
+
+    /// cupsd answers CUPS-Get-Devices once every backend is done, so devices all come at the end.
+    fn discover_devices(&self, options: &DiscoveryOptions, found: &mut dyn FnMut(Device) -> bool) -> Result<(), CupsError> {
+        for device in self.devices(options)? {
+            if !found(device) {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn get_all_drivers(&self) -> Result<Vec<Driver>, CupsError> {
+        let mut drivers = self.drivers(None)?;
+        driver::sort_drivers(&mut drivers);
+        Ok(drivers)
+    }
+
+    fn get_matching_drivers(&self, device: &Device) -> Result<Vec<Driver>, CupsError> {
+        if !device.device_id.is_empty() {
+            self.drivers(Some(("ppd-device-id", &device.device_id)))
+        } else if !device.make_and_model.is_empty() && device.make_and_model != "Unknown" {
+            self.drivers(Some(("ppd-make-and-model", &device.make_and_model)))
+        } else {
+            Ok(vec![])
+        }
+    }
+
+    fn create_printer(&self, name: &str, device: &str, driver: &str, description: &str, location: &str) -> Result<(), CupsError> {
+        self.add_printer(name, device, driver, description, location)
+    }
+
+    fn modify_printer(&self, name: &str, device: &str, driver: Option<&str>, description: &str, location: &str) -> Result<(), CupsError> {
+        self.modify_printer_attributes(name, device, driver, description, location)
+    }
+
+    fn remove_printer(&self, name: &str) -> Result<(), CupsError> {
+        self.delete_printer(name)
+    }
+
+    fn add_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError> {
+        let mut members = self.class_members(class)?;
+        if !members.iter().any(|member| member == printer) {
+            members.push(printer.to_string());
+        }
+        self.set_class_members(class, &members)
+    }
+
+    fn remove_class_member(&self, class: &str, printer: &str) -> Result<(), CupsError> {
+        let mut members = self.class_members(class)?;
+        members.retain(|member| member != printer);
+        // Like lpadmin -r, an emptied class goes away.
+        if members.is_empty() {
+            self.delete_class(class)
+        } else {
+            self.set_class_members(class, &members)
+        }
+    }
+
+    fn remove_class(&self, class: &str) -> Result<(), CupsError> {
+        self.delete_class(class)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+    use crate::cups::printer::PrinterState;
+
+    /// What the mock cupsd answers a request with.
+    enum Reply {
+        Ipp(IppMessage),
+        /// The response sent with `Transfer-Encoding: chunked`.
+        Chunked(IppMessage),
+        /// An HTTP error, like `401 Unauthorized`.
+        Status(&'static str),
+    }
+
+    /// A cupsd stand-in on a loopback port, answering every connection with
+    /// what `respond` makes of the request and keeping the requests it got.
+    struct MockServer {
+        backend: IppBackend,
+        requests: Arc<Mutex<Vec<(String, IppMessage)>>>,
+    }
+
+    impl MockServer {
+        fn start(respond: impl Fn(&IppMessage) -> Reply + Send + 'static) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            let requests = Arc::new(Mutex::new(vec![]));
+            let received = Arc::clone(&requests);
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let mut stream = stream.unwrap();
+                    let (path, request) = read_request(&mut stream);
+                    let reply = respond(&request);
+                    received.lock().unwrap().push((path, request));
+                    write_reply(&mut stream, reply);
+                }
+            });
+            MockServer {
+                backend: IppBackend::new(Server::Host(address)),
+                requests,
+            }
+        }
+
+        /// Answers every request with a bare successful response.
+        fn ok() -> Self {
+            MockServer::start(|request| Reply::Ipp(response(request, 0, vec![])))
+        }
+
+        fn requests(&self) -> Vec<(String, IppMessage)> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        /// The last request and the path it was posted to.
+        fn last(&self) -> (String, IppMessage) {
+            self.requests().pop().expect("no request received")
+        }
+    }
+
+    fn read_request(stream: &mut TcpStream) -> (String, IppMessage) {
+        let mut head = vec![];
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).unwrap();
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+        let path = head.split_whitespace().nth(1).unwrap().to_string();
+        let length: usize = head.lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut body = vec![0; length];
+        stream.read_exact(&mut body).unwrap();
+        (path, IppMessage::decode(&body).unwrap())
+    }
+
+    fn write_reply(stream: &mut TcpStream, reply: Reply) {
+        let bytes = match reply {
+            Reply::Ipp(response) => {
+                let body = response.encode();
+                let mut bytes = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+                    body.len()
+                ).into_bytes();
+                bytes.extend_from_slice(&body);
+                bytes
+            }
+            Reply::Chunked(response) => {
+                let mut bytes = b"HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+                for chunk in response.encode().chunks(7) {
+                    bytes.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+                    bytes.extend_from_slice(chunk);
+                    bytes.extend_from_slice(b"\r\n");
+                }
+                bytes.extend_from_slice(b"0\r\n\r\n");
+                bytes
+            }
+            Reply::Status(status) => format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).into_bytes(),
+        };
+        stream.write_all(&bytes).unwrap();
+    }
+
+    /// A response to `request` with the given status code and groups.
+    fn response(request: &IppMessage, status: u16, groups: Vec<IppGroup>) -> IppMessage {
+        let mut response = IppMessage::request(status, request.request_id);
+        response.groups.extend(groups);
+        response
+    }
+
+    fn group(group_tag: u8, attributes: Vec<(&str, Vec<IppValue>)>) -> IppGroup {
+        IppGroup {
+            tag: group_tag,
+            attributes: attributes.into_iter()
+                .map(|(name, values)| IppAttribute { name: name.to_string(), values })
+                .collect(),
+        }
+    }
+
+    fn text(value: &str) -> IppValue {
+        IppValue::String(tag::TEXT, value.to_string())
+    }
+
+    fn values<'a>(request: &'a IppMessage, group_tag: u8, name: &str) -> Option<&'a [IppValue]> {
+        request.groups(group_tag).find_map(|group| group.get(name)).map(|attribute| attribute.values.as_slice())
+    }
+
+    fn value<'a>(request: &'a IppMessage, group_tag: u8, name: &str) -> Option<&'a str> {
+        request.groups(group_tag).find_map(|group| group.get_str(name))
+    }
+
+    /// A stream reading a canned response and keeping what was written.
+    struct Loopback {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for Loopback {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Loopback {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn messages_round_trip_through_encode_and_decode() {
+        let mut message = IppMessage::request(operation::CUPS_ADD_MODIFY_PRINTER, 42);
+        message.add(tag::OPERATION, "printer-uri", vec![IppValue::uri("ipp://localhost/printers/Lab")]);
+        message.add(tag::PRINTER, "copies-default", vec![IppValue::Integer(-3)]);
+        message.add(tag::PRINTER, "printer-is-shared", vec![IppValue::Boolean(true)]);
+        message.add(tag::PRINTER, "printer-state", vec![IppValue::Enum(PRINTER_STATE_IDLE)]);
+        message.add(tag::PRINTER, "printer-info", vec![text("Front desk")]);
+        message.add(tag::PRINTER, "sides-supported", vec![IppValue::keyword("one-sided"), IppValue::keyword("two-sided-long-edge")]);
+        message.add(tag::PRINTER, "printer-uuid", vec![IppValue::OctetString(vec![0, 1, 255])]);
+        message.add(tag::PRINTER, "printer-current-time", vec![IppValue::DateTime(vec![7, 233, 3, 14, 9, 26, 53, 0, b'+', 1, 0])]);
+        message.add(tag::PRINTER, "printer-resolution-default", vec![IppValue::Resolution(600, 1200, 3)]);
+        message.add(tag::PRINTER, "copies-supported", vec![IppValue::Range(1, 9999)]);
+        message.add(tag::PRINTER, "media-col-default", vec![IppValue::Collection(vec![
+            IppAttribute::new("media-size", IppValue::Collection(vec![
+                IppAttribute::new("x-dimension", IppValue::Integer(21000)),
+                IppAttribute::new("y-dimension", IppValue::Integer(29700)),
+            ])),
+            IppAttribute {
+                name: "media-source".to_string(),
+                values: vec![IppValue::keyword("tray-1"), IppValue::keyword("tray-2")],
+            },
+        ])]);
+        message.add(tag::PRINTER, "printer-alert", vec![IppValue::Other(0x13, vec![])]);
+        message.add(tag::JOB, "job-id", vec![IppValue::Integer(7)]);
+        message.data = b"%PDF-1.7".to_vec();
+
+        assert_eq!(IppMessage::decode(&message.encode()).unwrap(), message);
+    }
+
+    #[test]
+    fn every_truncation_fails_to_decode() {
+        let mut message = IppMessage::request(operation::GET_JOBS, 1);
+        message.add(tag::OPERATION, "requested-attributes", vec![IppValue::keyword("job-id"), IppValue::keyword("job-name")]);
+        message.add(tag::OPERATION, "media-col", vec![IppValue::Collection(vec![
+            IppAttribute::new("media-type", IppValue::keyword("stationery")),
+        ])]);
+        let bytes = message.encode();
+        for len in 0..bytes.len() {
+            let error = IppMessage::decode(&bytes[..len]).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "truncated to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn values_with_a_language_decode_to_plain_strings() {
+        let mut bytes = vec![2, 0, 0, 0, 0, 0, 0, 1, tag::PRINTER];
+        for (value_tag, name, text) in [(tag::TEXT_WITH_LANGUAGE, "printer-info", "Büro"), (tag::NAME_WITH_LANGUAGE, "job-name", "report")] {
+            bytes.push(value_tag);
+            push_bytes(&mut bytes, name.as_bytes());
+            let mut value = vec![];
+            push_bytes(&mut value, b"de");
+            push_bytes(&mut value, text.as_bytes());
+            push_bytes(&mut bytes, &value);
+        }
+        bytes.push(tag::END);
+        let message = IppMessage::decode(&bytes).unwrap();
+        let group = &message.groups[0];
+        assert_eq!(group.get("printer-info").unwrap().values, vec![text("Büro")]);
+        assert_eq!(group.get("job-name").unwrap().values, vec![IppValue::name("report")]);
+    }
+
+    #[test]
+    fn stray_values_fail_to_decode() {
+        let outside_group = [2, 0, 0, 0, 0, 0, 0, 1, tag::KEYWORD, 0, 1, b'a', 0, 1, b'b', tag::END];
+        assert!(IppMessage::decode(&outside_group).is_err());
+        let additional_first = [2, 0, 0, 0, 0, 0, 0, 1, tag::PRINTER, tag::KEYWORD, 0, 0, 0, 1, b'b', tag::END];
+        assert!(IppMessage::decode(&additional_first).is_err());
+    }
+
+    #[test]
+    fn values_display_like_lpoptions() {
+        assert_eq!(IppValue::Resolution(600, 600, 3).to_string(), "600x600dpi");
+        assert_eq!(IppValue::Resolution(300, 300, 4).to_string(), "300x300dpcm");
+        assert_eq!(IppValue::Range(1, 5).to_string(), "1-5");
+        let collection = IppValue::Collection(vec![
+            IppAttribute::new("media-type", IppValue::keyword("stationery")),
+            IppAttribute {
+                name: "media-source".to_string(),
+                values: vec![IppValue::keyword("tray-1"), IppValue::keyword("tray-2")],
+            },
+        ]);
+        assert_eq!(collection.to_string(), "{media-type=stationery media-source=tray-1,tray-2}");
+    }
+
+    /// `utc` as the local time `Display` shows it in.
+    fn local(utc: &str) -> String {
+        chrono::NaiveDateTime::parse_from_str(utc, "%Y-%m-%d %H:%M:%S").unwrap()
+            .and_utc()
+            .with_timezone(&Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    }
+
+    #[test]
+    fn date_times_display_in_local_time() {
+        assert_eq!(IppValue::DateTime(vec![7, 233, 3, 14, 9, 26, 53, 0, b'+', 0, 0]).to_string(), local("2025-03-14 09:26:53"));
+        assert_eq!(IppValue::DateTime(vec![7, 233, 3, 14, 9, 26, 53, 0, b'+', 1, 30]).to_string(), local("2025-03-14 07:56:53"));
+        assert_eq!(IppValue::DateTime(vec![7, 233, 12, 31, 23, 30, 0, 0, b'-', 5, 0]).to_string(), local("2026-01-01 04:30:00"));
+        assert_eq!(IppValue::DateTime(vec![7, 233, 2, 30, 9, 26, 53, 0, b'+', 0, 0]).to_string(), "2025-02-30 09:26:53");
+        assert_eq!(IppValue::DateTime(vec![7, 233]).to_string(), "");
+    }
+
+    #[test]
+    fn chunks_are_joined() {
+        let body = b"5\r\nhello\r\n6;name=value\r\n world\r\n0\r\n\r\n";
+        assert_eq!(dechunk(body).unwrap(), b"hello world");
+        assert_eq!(dechunk(b"0\r\n\r\n").unwrap(), b"");
+    }
+
+    #[test]
+    fn truncated_or_malformed_chunks_fail() {
+        for body in [&b"5\r\nhel"[..], b"5\r\nhello\r\n", b"zz\r\nhello\r\n0\r\n\r\n", b""] {
+            let error = dechunk(body).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", String::from_utf8_lossy(body));
+        }
+    }
+
+    #[test]
+    fn status_codes_map_to_errors() {
+        let status = |code: u16, message: Option<&str>| {
+            let mut response = IppMessage::request(code, 1);
+            if let Some(message) = message {
+                response.add(tag::OPERATION, "status-message", vec![text(message)]);
+            }
+            IppBackend::check(response).map(|response| response.code)
+        };
+        assert_eq!(status(0x0000, None), Ok(0x0000));
+        assert_eq!(status(0x0001, None), Ok(0x0001));
+        assert_eq!(status(0x0401, Some("Forbidden")), Err(CupsError::PermissionDenied("Forbidden".to_string())));
+        assert_eq!(status(0x0403, None), Err(CupsError::PermissionDenied("IPP status 0x0403".to_string())));
+        assert_eq!(status(0x0406, None), Err(CupsError::Server("IPP status 0x0406".to_string())));
+        assert_eq!(status(0x0500, Some("Internal error")), Err(CupsError::Server("Internal error".to_string())));
+    }
+
+    #[test]
+    fn http_post_frames_the_request_and_returns_the_body() {
+        let mut stream = Loopback {
+            input: io::Cursor::new(b"HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: 4\r\n\r\nbody".to_vec()),
+            output: vec![],
+        };
+        assert_eq!(http_post(&mut stream, "localhost", "/admin/", b"ipp").unwrap(), b"body");
+        assert_eq!(
+            String::from_utf8(stream.output).unwrap(),
+            "POST /admin/ HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/ipp\r\nContent-Length: 3\r\nConnection: close\r\n\r\nipp"
+        );
+    }
+
+    #[test]
+    fn http_post_dechunks_and_rejects_bad_responses() {
+        let post = |response: &[u8]| {
+            let stream = Loopback {
+                input: io::Cursor::new(response.to_vec()),
+                output: vec![],
+            };
+            http_post(stream, "localhost", "/", b"")
+        };
+        assert_eq!(post(b"HTTP/1.1 200 OK\r\ntransfer-encoding: Chunked\r\n\r\n2\r\nok\r\n0\r\n\r\n").unwrap(), b"ok");
+        assert_eq!(post(b"HTTP/1.1 401 Unauthorized\r\n\r\n").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(post(b"HTTP/1.1 403 Forbidden\r\n\r\n").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(post(b"HTTP/1.1 500 Internal Server Error\r\n\r\n").unwrap_err().kind(), io::ErrorKind::Other);
+        assert_eq!(post(b"HTTP/1.1 200 OK\r\n").unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn http_errors_reach_the_caller() {
+        for (status, error) in [
+            ("401 Unauthorized", CupsError::PermissionDenied("HTTP/1.1 401 Unauthorized".to_string())),
+            ("403 Forbidden", CupsError::PermissionDenied("HTTP/1.1 403 Forbidden".to_string())),
+            ("500 Internal Server Error", CupsError::Server("HTTP/1.1 500 Internal Server Error".to_string())),
+        ] {
+            let server = MockServer::start(move |_| Reply::Status(status));
+            assert_eq!(server.backend.remove_printer("Lab"), Err(error));
+        }
+    }
+
+    #[test]
+    fn ipp_errors_reach_the_caller() {
+        let server = MockServer::start(|request| {
+            let mut response = response(request, 0x0401, vec![]);
+            response.add(tag::OPERATION, "status-message", vec![text("Forbidden")]);
+            Reply::Ipp(response)
+        });
+        assert_eq!(server.backend.remove_printer("Lab"), Err(CupsError::PermissionDenied("Forbidden".to_string())));
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0x0406, vec![])));
+        assert_eq!(server.backend.enable_printer("Lab"), Err(CupsError::Server("IPP status 0x0406".to_string())));
+    }
+
+    #[test]
+    fn closed_ports_are_unreachable() {
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let backend = IppBackend::new(Server::Host(address));
+        assert!(matches!(backend.get_all_printers(), Err(CupsError::Unreachable(_))));
+    }
+
+    fn printer_groups() -> Vec<IppGroup> {
+        vec![
+            group(tag::PRINTER, vec![
+                ("printer-name", vec![IppValue::name("Büro Laser")]),
+                ("printer-state", vec![IppValue::Enum(3)]),
+                ("printer-state-reasons", vec![IppValue::keyword("none")]),
+                ("printer-is-accepting-jobs", vec![IppValue::Boolean(true)]),
+                ("printer-info", vec![text("Office laser")]),
+                ("printer-location", vec![text("Floor 2")]),
+                ("device-uri", vec![IppValue::uri("socket://10.0.0.5")]),
+                ("printer-make-and-model", vec![text("HP LaserJet Series PCL 4/5")]),
+                ("printer-type", vec![IppValue::Enum(0x100c)]),
+                ("copies-default", vec![IppValue::Integer(1)]),
+                ("sides-default", vec![IppValue::keyword("one-sided")]),
+                ("printer-uuid", vec![IppValue::uri("urn:uuid:0")]),
+            ]),
+            group(tag::PRINTER, vec![
+                ("printer-name", vec![IppValue::name("Pool")]),
+                ("printer-state", vec![IppValue::Enum(5)]),
+                ("printer-state-reasons", vec![IppValue::keyword("paused")]),
+                ("printer-is-accepting-jobs", vec![IppValue::Boolean(false)]),
+                ("printer-type", vec![IppValue::Enum(0x100d)]),
+                ("member-names", vec![IppValue::name("Büro Laser"), IppValue::name("Lab")]),
+            ]),
+            group(tag::PRINTER, vec![("printer-state", vec![IppValue::Enum(3)])]),
+        ]
+    }
+
+    #[test]
+    fn printers_and_classes_come_from_one_request() {
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0, printer_groups())));
+        let printers = server.backend.get_all_printers().unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let (path, request) = &requests[0];
+        assert_eq!(path, "/");
+        assert_eq!(request.code, operation::CUPS_GET_PRINTERS);
+        assert!(values(request, tag::OPERATION, "requested-attributes").unwrap().contains(&IppValue::keyword("printer-name")));
+
+        assert_eq!(printers.len(), 2);
+        let printer = &printers[0];
+        assert_eq!(printer.name, "Büro Laser");
+        assert_eq!(printer.state, PrinterState::Idle);
+        assert!(printer.state_reasons.is_empty());
+        assert!(printer.accepting);
+        assert_eq!(printer.description, "Office laser");
+        assert_eq!(printer.location, "Floor 2");
+        assert_eq!(printer.device_uri, "socket://10.0.0.5");
+        assert_eq!(printer.make_and_model, "HP LaserJet Series PCL 4/5");
+        assert!(!printer.is_class);
+        assert_eq!(printer.options.get("copies"), Some(&OptionValue::Int(1)));
+        assert_eq!(printer.options.get("sides"), Some(&OptionValue::Enum("one-sided".to_string())));
+        assert_eq!(printer.options.get("printer-uuid"), None);
+
+        let class = &printers[1];
+        assert_eq!(class.name, "Pool");
+        assert_eq!(class.state, PrinterState::Stopped);
+        assert_eq!(class.state_reasons, vec!["paused"]);
+        assert!(!class.accepting);
+        assert!(class.is_class);
+        assert_eq!(class.members, vec!["Büro Laser", "Lab"]);
+    }
+
+    #[test]
+    fn chunked_responses_are_read() {
+        let server = MockServer::start(|request| Reply::Chunked(response(request, 0, printer_groups())));
+        let names: Vec<String> = server.backend.get_all_printers().unwrap()
+            .into_iter()
+            .map(|printer| printer.name)
+            .collect();
+        assert_eq!(names, vec!["Büro Laser", "Pool"]);
+    }
+
+    #[test]
+    fn queue_names_are_percent_encoded() {
+        let server = MockServer::ok();
+        server.backend.enable_printer("Büro Laser+1").unwrap();
+        let (path, request) = server.last();
+        assert_eq!(path, "/admin/");
+        assert_eq!(request.code, operation::RESUME_PRINTER);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/printers/B%C3%BCro%20Laser%2B1"));
+        assert_eq!(value(&request, tag::OPERATION, "requesting-user-name"), Some(server.backend.user.as_str()));
+    }
+
+    #[test]
+    fn printer_controls_send_their_operations() {
+        let server = MockServer::ok();
+        server.backend.disable_printer("Lab", Some("Out of toner")).unwrap();
+        server.backend.reject_jobs("Lab", None).unwrap();
+        server.backend.accept_jobs("Lab").unwrap();
+        server.backend.cancel_all_jobs("Lab").unwrap();
+        server.backend.set_default_printer("Lab", DefaultScope::System).unwrap();
+
+        let requests = server.requests();
+        let operations: Vec<u16> = requests.iter().map(|(_, request)| request.code).collect();
+        assert_eq!(operations, vec![
+            operation::PAUSE_PRINTER,
+            operation::CUPS_REJECT_JOBS,
+            operation::CUPS_ACCEPT_JOBS,
+            operation::CANCEL_JOBS,
+            operation::CUPS_SET_DEFAULT,
+        ]);
+        for (path, request) in &requests {
+            assert_eq!(path, "/admin/");
+            assert_eq!(value(request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/printers/Lab"));
+        }
+        assert_eq!(value(&requests[0].1, tag::OPERATION, "printer-state-message"), Some("Out of toner"));
+        assert_eq!(value(&requests[1].1, tag::OPERATION, "printer-state-message"), None);
+    }
+
+    /// A path for an lpoptions file in a directory of its own under the temp dir.
+    fn lpoptions(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("printer-tui-ipp-{}-{}", test, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("lpoptions")
+    }
+
+    #[test]
+    fn default_printer_comes_from_cups_get_default() {
+        let mut server = MockServer::start(|request| Reply::Ipp(response(request, 0, vec![
+            group(tag::PRINTER, vec![("printer-name", vec![IppValue::name("Lab")])]),
+        ])));
+        server.backend.lpoptions = Some(lpoptions("no-user-default"));
+        assert_eq!(server.backend.get_default_printer(), Ok(Some("Lab".to_string())));
+        assert_eq!(server.last().1.code, operation::CUPS_GET_DEFAULT);
+
+        let mut server = MockServer::start(|request| Reply::Ipp(response(request, 0x0406, vec![])));
+        server.backend.lpoptions = None;
+        assert_eq!(server.backend.get_default_printer(), Ok(None));
+    }
+
+    #[test]
+    fn user_default_comes_from_lpoptions() {
+        let path = lpoptions("user-default");
+        let mut server = MockServer::ok();
+        server.backend.lpoptions = Some(path.clone());
+        server.backend.set_default_printer("Office", DefaultScope::User).unwrap();
+        assert_eq!(server.backend.get_default_printer(), Ok(Some("Office".to_string())));
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Default Office\n");
+        assert!(server.requests().is_empty());
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn option_choices_come_from_supported_and_default_attributes() {
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0, vec![
+            group(tag::PRINTER, vec![
+                ("media-supported", vec![IppValue::keyword("iso_a4_210x297mm"), IppValue::keyword("na_letter_8.5x11in")]),
+                ("media-default", vec![IppValue::keyword("iso_a4_210x297mm")]),
+                ("sides-supported", vec![IppValue::keyword("one-sided")]),
+            ]),
+        ])));
+        let choices = server.backend.get_printer_option_choices("Lab").unwrap();
+        assert_eq!(server.last().1.code, operation::GET_PRINTER_ATTRIBUTES);
+        assert_eq!(choices, vec![
+            OptionChoices {
+                name: "media".to_string(),
+                label: "media".to_string(),
+                choices: vec!["iso_a4_210x297mm".to_string(), "na_letter_8.5x11in".to_string()],
+                default: Some("iso_a4_210x297mm".to_string()),
+            },
+            OptionChoices {
+                name: "sides".to_string(),
+                label: "sides".to_string(),
+                choices: vec!["one-sided".to_string()],
+                default: None,
+            },
+        ]);
+    }
+
+    #[test]
+    fn options_are_set_as_printer_attributes() {
+        let server = MockServer::ok();
+        server.backend.set_printer_option("Lab", "copies", "2").unwrap();
+        server.backend.set_printer_option("Lab", "sides", "two-sided-long-edge").unwrap();
+        server.backend.set_printer_option("Lab", "printer-info", "Front desk").unwrap();
+        server.backend.set_printer_option("Lab", "printer-is-shared", "false").unwrap();
+
+        let requests = server.requests();
+        for (path, request) in &requests {
+            assert_eq!(path, "/admin/");
+            assert_eq!(request.code, operation::CUPS_ADD_MODIFY_PRINTER);
+        }
+        assert_eq!(values(&requests[0].1, tag::PRINTER, "copies-default"), Some(&[IppValue::Integer(2)][..]));
+        assert_eq!(values(&requests[1].1, tag::PRINTER, "sides-default"), Some(&[IppValue::keyword("two-sided-long-edge")][..]));
+        assert_eq!(values(&requests[2].1, tag::PRINTER, "printer-info"), Some(&[text("Front desk")][..]));
+        assert_eq!(values(&requests[3].1, tag::PRINTER, "printer-is-shared"), Some(&[IppValue::Boolean(false)][..]));
+    }
+
+    #[test]
+    fn devices_are_read_from_cups_get_devices() {
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0, vec![
+            group(tag::PRINTER, vec![
+                ("device-uri", vec![IppValue::uri("ipp://10.0.0.5/ipp/print")]),
+                ("device-class", vec![IppValue::keyword("network")]),
+                ("device-info", vec![text("HP LaserJet")]),
+                ("device-make-and-model", vec![text("HP LaserJet 4000")]),
+                ("device-id", vec![text("MFG:HP;MDL:LaserJet 4000;")]),
+                ("device-location", vec![text("Floor 2")]),
+            ]),
+            group(tag::PRINTER, vec![("device-class", vec![IppValue::keyword("direct")])]),
+            group(tag::PRINTER, vec![("device-uri", vec![IppValue::uri("socket")])]),
+        ])));
+        let options = DiscoveryOptions {
+            timeout: Some(5),
+            include_schemes: vec![],
+            exclude_schemes: vec!["usb".to_string(), "serial".to_string()],
+        };
+        let mut devices = vec![];
+        server.backend.discover_devices(&options, &mut |device| {
+            devices.push(device);
+            true
+        }).unwrap();
+
+        let (path, request) = server.last();
+        assert_eq!(path, "/");
+        assert_eq!(request.code, operation::CUPS_GET_DEVICES);
+        assert_eq!(values(&request, tag::OPERATION, "timeout"), Some(&[IppValue::Integer(5)][..]));
+        assert_eq!(values(&request, tag::OPERATION, "exclude-schemes"), Some(&[IppValue::name("usb"), IppValue::name("serial")][..]));
+        assert_eq!(values(&request, tag::OPERATION, "include-schemes"), None);
+        assert_eq!(devices, vec![
+            Device {
+                uri: "ipp://10.0.0.5/ipp/print".to_string(),
+                class: "network".to_string(),
+                info: "HP LaserJet".to_string(),
+                make_and_model: "HP LaserJet 4000".to_string(),
+                device_id: "MFG:HP;MDL:LaserJet 4000;".to_string(),
+                location: "Floor 2".to_string(),
+            },
+            Device {
+                uri: "socket".to_string(),
+                ..Default::default()
+            },
+        ]);
+
+        let mut found = 0;
+        server.backend.discover_devices(&options, &mut |_| {
+            found += 1;
+            false
+        }).unwrap();
+        assert_eq!(found, 1);
+    }
+
+    #[test]
+    fn drivers_are_sorted_and_matched_by_device() {
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0, vec![
+            group(tag::PRINTER, vec![
+                ("ppd-name", vec![IppValue::name("lsb/usr/zebra/zpl.ppd")]),
+                ("ppd-make", vec![text("Zebra")]),
+                ("ppd-make-and-model", vec![text("Zebra ZPL Label Printer")]),
+            ]),
+            group(tag::PRINTER, vec![
+                ("ppd-name", vec![IppValue::name("drv:///sample.drv/laserjet.ppd")]),
+                ("ppd-make", vec![text("HP")]),
+                ("ppd-make-and-model", vec![text("HP LaserJet Series PCL 4/5")]),
+                ("ppd-natural-language", vec![IppValue::String(tag::LANGUAGE, "en".to_string())]),
+            ]),
+            group(tag::PRINTER, vec![("ppd-make", vec![text("Nameless")])]),
+        ])));
+
+        let drivers = server.backend.get_all_drivers().unwrap();
+        let (_, request) = server.last();
+        assert_eq!(request.code, operation::CUPS_GET_PPDS);
+        assert_eq!(values(&request, tag::OPERATION, "ppd-device-id"), None);
+        let names: Vec<&str> = drivers.iter().map(|driver| driver.ppd_name.as_str()).collect();
+        assert_eq!(names, vec!["drv:///sample.drv/laserjet.ppd", "lsb/usr/zebra/zpl.ppd"]);
+        assert_eq!(drivers[0].natural_language, "en");
+
+        let device = Device {
+            uri: "usb://HP/LaserJet".to_string(),
+            device_id: "MFG:HP;MDL:LaserJet 4000;".to_string(),
+            make_and_model: "HP LaserJet 4000".to_string(),
+            ..Default::default()
+        };
+        server.backend.get_matching_drivers(&device).unwrap();
+        assert_eq!(value(&server.last().1, tag::OPERATION, "ppd-device-id"), Some("MFG:HP;MDL:LaserJet 4000;"));
+
+        let device = Device {
+            device_id: String::new(),
+            ..device
+        };
+        server.backend.get_matching_drivers(&device).unwrap();
+        assert_eq!(value(&server.last().1, tag::OPERATION, "ppd-make-and-model"), Some("HP LaserJet 4000"));
+
+        let sent = server.requests().len();
+        let unknown = Device {
+            make_and_model: "Unknown".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(server.backend.get_matching_drivers(&unknown), Ok(vec![]));
+        assert_eq!(server.requests().len(), sent);
+    }
+
+    #[test]
+    fn printers_are_created_modified_and_removed() {
+        let server = MockServer::ok();
+        server.backend.create_printer("Lab", "socket://10.0.0.5", "drv:///sample.drv/laserjet.ppd", "", "Floor 2").unwrap();
+        let (path, request) = server.last();
+        assert_eq!(path, "/admin/");
+        assert_eq!(request.code, operation::CUPS_ADD_MODIFY_PRINTER);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/printers/Lab"));
+        assert_eq!(value(&request, tag::OPERATION, "ppd-name"), Some("drv:///sample.drv/laserjet.ppd"));
+        assert_eq!(value(&request, tag::PRINTER, "device-uri"), Some("socket://10.0.0.5"));
+        assert_eq!(value(&request, tag::PRINTER, "printer-info"), None);
+        assert_eq!(value(&request, tag::PRINTER, "printer-location"), Some("Floor 2"));
+        assert_eq!(values(&request, tag::PRINTER, "printer-is-accepting-jobs"), Some(&[IppValue::Boolean(true)][..]));
+        assert_eq!(values(&request, tag::PRINTER, "printer-state"), Some(&[IppValue::Enum(PRINTER_STATE_IDLE)][..]));
+
+        server.backend.modify_printer("Lab", "socket://10.0.0.6", None, "", "").unwrap();
+        let (_, request) = server.last();
+        assert_eq!(request.code, operation::CUPS_ADD_MODIFY_PRINTER);
+        assert_eq!(value(&request, tag::OPERATION, "ppd-name"), None);
+        assert_eq!(value(&request, tag::PRINTER, "device-uri"), Some("socket://10.0.0.6"));
+        assert_eq!(value(&request, tag::PRINTER, "printer-info"), Some(""));
+
+        server.backend.modify_printer("Lab", "socket://10.0.0.6", Some("everywhere"), "Front desk", "").unwrap();
+        assert_eq!(value(&server.last().1, tag::OPERATION, "ppd-name"), Some("everywhere"));
+
+        server.backend.remove_printer("Lab").unwrap();
+        let (path, request) = server.last();
+        assert_eq!(path, "/admin/");
+        assert_eq!(request.code, operation::CUPS_DELETE_PRINTER);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/printers/Lab"));
+    }
+
+    #[test]
+    fn job_controls_address_jobs_by_uri() {
+        let server = MockServer::ok();
+        server.backend.cancel_job(7).unwrap();
+        server.backend.hold_job(7).unwrap();
+        server.backend.release_job(7).unwrap();
+        server.backend.restart_job(7).unwrap();
+        server.backend.move_job(7, "Lab").unwrap();
+
+        let requests = server.requests();
+        let operations: Vec<u16> = requests.iter().map(|(_, request)| request.code).collect();
+        assert_eq!(operations, vec![
+            operation::CANCEL_JOB,
+            operation::HOLD_JOB,
+            operation::RELEASE_JOB,
+            operation::RESTART_JOB,
+            operation::CUPS_MOVE_JOB,
+        ]);
+        for (path, request) in &requests {
+            assert_eq!(path, "/jobs/");
+            assert_eq!(value(request, tag::OPERATION, "job-uri"), Some("ipp://localhost/jobs/7"));
+        }
+        assert_eq!(value(&requests[4].1, tag::JOB, "job-printer-uri"), Some("ipp://localhost/printers/Lab"));
+    }
+
+    #[test]
+    fn jobs_are_read_from_job_groups() {
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0, vec![
+            group(tag::JOB, vec![
+                ("job-id", vec![IppValue::Integer(12)]),
+                ("job-printer-uri", vec![IppValue::uri("ipp://localhost/printers/B%C3%BCro")]),
+                ("job-originating-user-name", vec![IppValue::name("alice")]),
+                ("job-name", vec![IppValue::name("report.pdf")]),
+                ("job-k-octets", vec![IppValue::Integer(3)]),
+                ("job-state", vec![IppValue::Enum(9)]),
+                ("date-time-at-creation", vec![IppValue::DateTime(vec![7, 233, 3, 14, 9, 26, 53, 0, b'+', 1, 0])]),
+                ("date-time-at-completed", vec![IppValue::DateTime(vec![7, 233, 3, 14, 9, 27, 2, 0, b'+', 1, 0])]),
+                ("job-impressions-completed", vec![IppValue::Integer(4)]),
+                ("copies", vec![IppValue::Integer(2)]),
+                ("job-state-reasons", vec![IppValue::keyword("job-completed-successfully")]),
+            ]),
+            group(tag::JOB, vec![
+                ("job-id", vec![IppValue::Integer(13)]),
+                ("job-state", vec![IppValue::Enum(4)]),
+                ("date-time-at-completed", vec![IppValue::Other(0x13, vec![])]),
+            ]),
+            group(tag::JOB, vec![("job-name", vec![IppValue::name("no id")])]),
+        ])));
+
+        let jobs = server.backend.get_completed_jobs(None).unwrap();
+        let (path, request) = server.last();
+        assert_eq!(path, "/");
+        assert_eq!(request.code, operation::GET_JOBS);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/"));
+        assert_eq!(value(&request, tag::OPERATION, "which-jobs"), Some("completed"));
+        assert_eq!(jobs, vec![
+            Job {
+                id: 12,
+                printer: "Büro".to_string(),
+                user: "alice".to_string(),
+                title: "report.pdf".to_string(),
+                size: 3 * 1024,
+                state: JobState::Completed,
+                submitted: local("2025-03-14 08:26:53"),
+                completed: local("2025-03-14 08:27:02"),
+                pages: Some(4),
+                copies: Some(2),
+                state_reasons: vec!["job-completed-successfully".to_string()],
+            },
+            Job {
+                id: 13,
+                state: JobState::Held,
+                ..Default::default()
+            },
+        ]);
+
+        server.backend.get_jobs(Some("Büro")).unwrap();
+        let (_, request) = server.last();
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/printers/B%C3%BCro"));
+        assert_eq!(value(&request, tag::OPERATION, "which-jobs"), Some("not-completed"));
+    }
+
+    #[test]
+    fn print_file_sends_the_document() {
+        let path = std::env::temp_dir().join(format!("printer-tui-ipp-test-{}", std::process::id()));
+        fs::write(&path, b"%PDF-1.7 document").unwrap();
+        let server = MockServer::start(|request| Reply::Ipp(response(request, 0, vec![
+            group(tag::JOB, vec![("job-id", vec![IppValue::Integer(42)])]),
+        ])));
+        let id = server.backend.print_file("Lab", &path, "Quarterly report");
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(id, Ok(42));
+        let (path, request) = server.last();
+        assert_eq!(path, "/");
+        assert_eq!(request.code, operation::PRINT_JOB);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/printers/Lab"));
+        assert_eq!(value(&request, tag::OPERATION, "job-name"), Some("Quarterly report"));
+        assert_eq!(request.data, b"%PDF-1.7 document");
+    }
+
+    #[test]
+    fn print_file_needs_a_job_id_back() {
+        let path = std::env::temp_dir().join(format!("printer-tui-ipp-test-no-id-{}", std::process::id()));
+        fs::write(&path, b"text").unwrap();
+        let server = MockServer::ok();
+        let id = server.backend.print_file("Lab", &path, "notes");
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(id, Err(CupsError::Parse(_))));
+    }
+
+    #[test]
+    fn class_members_are_added_and_removed() {
+        let server = MockServer::start(|request| {
+            if request.code != operation::GET_PRINTER_ATTRIBUTES {
+                return Reply::Ipp(response(request, 0, vec![]));
+            }
+            let members: &[&str] = match value(request, tag::OPERATION, "printer-uri") {
+                Some("ipp://localhost/classes/Pool") => &["A", "B"],
+                Some("ipp://localhost/classes/Solo") => &["A"],
+                _ => return Reply::Ipp(response(request, 0x0406, vec![])),
+            };
+            Reply::Ipp(response(request, 0, vec![
+                group(tag::PRINTER, vec![("member-names", members.iter().map(|member| IppValue::name(member)).collect())]),
+            ]))
+        });
+        let member_uris = |request: &IppMessage| -> Vec<String> {
+            values(request, tag::PRINTER, "member-uris").unwrap().iter().map(|uri| uri.to_string()).collect()
+        };
+
+        server.backend.add_class_member("New", "A").unwrap();
+        let (path, request) = server.last();
+        assert_eq!(path, "/admin/");
+        assert_eq!(request.code, operation::CUPS_ADD_MODIFY_CLASS);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/classes/New"));
+        assert_eq!(member_uris(&request), vec!["ipp://localhost/printers/A"]);
+
+        server.backend.add_class_member("Pool", "C").unwrap();
+        assert_eq!(member_uris(&server.last().1), vec![
+            "ipp://localhost/printers/A",
+            "ipp://localhost/printers/B",
+            "ipp://localhost/printers/C",
+        ]);
+
+        server.backend.remove_class_member("Pool", "A").unwrap();
+        assert_eq!(member_uris(&server.last().1), vec!["ipp://localhost/printers/B"]);
+
+        server.backend.remove_class_member("Solo", "A").unwrap();
+        let (_, request) = server.last();
+        assert_eq!(request.code, operation::CUPS_DELETE_CLASS);
+        assert_eq!(value(&request, tag::OPERATION, "printer-uri"), Some("ipp://localhost/classes/Solo"));
+
+        server.backend.remove_class("Pool").unwrap();
+        assert_eq!(server.last().1.code, operation::CUPS_DELETE_CLASS);
+    }
+}
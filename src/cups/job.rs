@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use serde::Deserialize;
+
+use super::error::{CupsError, run};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+pub enum JobState {
+    #[default]
+    Pending,
+    Held,
+    Processing,
+    Stopped,
+    Canceled,
+    Aborted,
+    Completed,
+}
+
+impl JobState {
+    /// Maps the IPP `job-state` enum.
+    pub fn from_ipp(state: i32) -> Self {
+        match state {
+            4 => JobState::Held,
+            5 => JobState::Processing,
+            6 => JobState::Stopped,
+            7 => JobState::Canceled,
+            8 => JobState::Aborted,
+            9 => JobState::Completed,
+            _ => JobState::Pending,
+        }
+    }
+}
+
+impl fmt::Display for JobState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobState::Pending => write!(f, "pending"),
+            JobState::Held => write!(f, "held"),
+            JobState::Processing => write!(f, "processing"),
+            JobState::Stopped => write!(f, "stopped"),
+            JobState::Canceled => write!(f, "canceled"),
+            JobState::Aborted => write!(f, "aborted"),
+            JobState::Completed => write!(f, "completed"),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct Job {
+    pub id: u32,
+    pub printer: String,
+    pub user: String,
+    pub title: String,
+    /// Size in bytes.
+    pub size: u64,
+    pub state: JobState,
+    /// Submission time as `YYYY-MM-DD HH:MM:SS`, in local time.
+    pub submitted: String,
+    /// Completion time, same format, empty for jobs still queued.
+    pub completed: String,
+    pub pages: Option<u32>,
+    pub copies: Option<u32>,
+    pub state_reasons: Vec<String>,
+}
+
+impl Job {
+    pub fn size_to_string(&self) -> String {
+        match self.size {
+            size if size >= 1024 * 1024 => format!("{:.1}M", size as f64 / (1024.0 * 1024.0)),
+            size if size >= 1024 => format!("{}k", size / 1024),
+            size => format!("{}", size),
+        }
+    }
+}
+
+/// Queued and active jobs, for every printer or just `printer`.
+pub fn get_jobs(printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+    let mut args = vec!["-l", "-o"];
+    args.extend(printer);
+    let output = run("lpstat", &args)?;
+    // Titles are a nicety, the queue still shows when lpq is missing or fails.
+    let titles = get_job_titles().unwrap_or_default();
+    Ok(parse_lpstat_jobs(&output)
+        .into_iter()
+        .map(|job| Job {
+            title: titles.get(&job.id).cloned().unwrap_or_default(),
+            ..job
+        })
+        .collect())
+}
+
+/// Completed, canceled and aborted jobs, the date lpstat shows being the completion time.
+/// It has no title, pages, copies or submission time for them.
+pub fn get_completed_jobs(printer: Option<&str>) -> Result<Vec<Job>, CupsError> {
+    let mut args = vec!["-W", "completed", "-l", "-o"];
+    args.extend(printer);
+    let output = run("lpstat", &args)?;
+    Ok(parse_lpstat_jobs(&output)
+        .into_iter()
+        .map(|job| Job {
+            state: if job.state == JobState::Pending { JobState::Completed } else { job.state },
+            completed: job.submitted.clone(),
+            submitted: String::new(),
+            ..job
+        })
+        .collect())
+}
+
+/// Parses `lpstat -l -o`, whose `Alerts:` line tells held and printing jobs apart.
+fn parse_lpstat_jobs(output: &str) -> Vec<Job> {
+    let mut jobs: Vec<Job> = vec![];
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            let Some(job) = jobs.last_mut() else {
+                continue;
+            };
+            if let Some(alerts) = line.trim().strip_prefix("Alerts:") {
+                job.state_reasons = alerts.split_whitespace()
+                    .filter(|alert| *alert != "none")
+                    .map(|alert| alert.to_string())
+                    .collect();
+                job.state = alerts.split_whitespace()
+                    .find_map(|alert| match alert {
+                        "job-printing" => Some(JobState::Processing),
+                        "job-hold-until-specified" => Some(JobState::Held),
+                        "job-stopped" => Some(JobState::Stopped),
+                        "job-canceled-by-user" | "job-canceled-by-operator" => Some(JobState::Canceled),
+                        "job-aborted-by-system" => Some(JobState::Aborted),
+                        "job-completed-successfully" => Some(JobState::Completed),
+                        _ => None,
+                    })
+                    .unwrap_or(job.state);
+            }
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [destination, user, size, date @ ..] = words.as_slice() else {
+            continue;
+        };
+        let Some((printer, id)) = destination.rsplit_once('-') else {
+            continue;
+        };
+        let Ok(id) = id.parse() else {
+            continue;
+        };
+        jobs.push(Job {
+            id,
+            printer: printer.to_string(),
+            user: user.to_string(),
+            size: size.parse().unwrap_or(0),
+            submitted: normalize_date(date),
+            ..Default::default()
+        });
+    }
+    jobs
+}
+
+/// Turns the C locale `%c` date lpstat prints (`Sat Oct 18 10:00:00 2026`) into `2026-10-18 10:00:00`.
+fn normalize_date(words: &[&str]) -> String {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    match words {
+        [_, month, day, time, year, ..] => match MONTHS.iter().position(|m| m == month) {
+            Some(month) => format!("{}-{:02}-{:02} {}", year, month + 1, day.parse::<u32>().unwrap_or(0), time),
+            None => words.join(" "),
+        },
+        _ => words.join(" "),
+    }
+}
+
+/// Job titles from `lpq -a`, which lpstat doesn't show.
+fn get_job_titles() -> Result<HashMap<u32, String>, CupsError> {
+    let output = run("lpq", &["-a"])?;
+    Ok(output.lines()
+        .skip_while(|line| !line.starts_with("Rank"))
+        .skip(1)
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() < 6 {
+                return None;
+            }
+            let id = words[2].parse().ok()?;
+            Some((id, words[3..words.len() - 2].join(" ")))
+        })
+        .collect())
+}
+
+/// The standard CUPS test page, a banner file cupsd renders itself.
+pub const TEST_PAGE: &str = "/usr/share/cups/data/testprint";
+
+/// Submits a file with `lp` and returns the new job id.
+pub fn print_file(printer: &str, path: &Path, title: &str) -> Result<u32, CupsError> {
+    let path = path.to_string_lossy();
+    let output = run("lp", &["-d", printer, "-t", title, "--", &path])?;
+    parse_request_id(&output)
+        .ok_or_else(|| CupsError::Parse(format!("no job id in lp output: {}", output.trim())))
+}
+
+/// Reads the id out of `request id is Office-42 (1 file(s))`.
+fn parse_request_id(output: &str) -> Option<u32> {
+    output.split_whitespace()
+        .skip_while(|word| *word != "is")
+        .nth(1)
+        .and_then(|request| request.rsplit_once('-'))
+        .and_then(|(_, id)| id.parse().ok())
+}
+
+pub fn cancel_job(id: u32) -> Result<(), CupsError> {
+    run("cancel", &[&id.to_string()])?;
+    Ok(())
+}
+
+pub fn cancel_all_jobs(printer: &str) -> Result<(), CupsError> {
+    run("cancel", &["-a", printer])?;
+    Ok(())
+}
+
+pub fn hold_job(id: u32) -> Result<(), CupsError> {
+    run("lp", &["-i", &id.to_string(), "-H", "hold"])?;
+    Ok(())
+}
+
+pub fn release_job(id: u32) -> Result<(), CupsError> {
+    run("lp", &["-i", &id.to_string(), "-H", "resume"])?;
+    Ok(())
+}
+
+pub fn restart_job(id: u32) -> Result<(), CupsError> {
+    run("lp", &["-i", &id.to_string(), "-H", "restart"])?;
+    Ok(())
+}
+
+pub fn move_job(id: u32, printer: &str) -> Result<(), CupsError> {
+    run("lpmove", &[&id.to_string(), printer])?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lpstat -l -o` with a printing job, a held one on a queue with
+    /// dashes in its name, and a pending one.
+    const LPSTAT: &str = "\
+Office-42               alice             2048   Sat Oct 18 10:00:00 2026
+\tStatus: Sending data to printer.
+\tAlerts: job-printing
+\tqueued for Office
+Lab-2nd-floor-7         bob               1048576   Sat Oct  4 09:05:12 2026
+\tAlerts: job-hold-until-specified
+\tqueued for Lab-2nd-floor
+Office-43               alice             12   Sat Oct 18 10:01:00 2026
+\tAlerts: none
+";
+
+    #[test]
+    fn jobs_are_read_with_their_state() {
+        let jobs = parse_lpstat_jobs(LPSTAT);
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[0].id, 42);
+        assert_eq!(jobs[0].printer, "Office");
+        assert_eq!(jobs[0].user, "alice");
+        assert_eq!(jobs[0].size, 2048);
+        assert_eq!(jobs[0].submitted, "2026-10-18 10:00:00");
+        assert_eq!(jobs[0].state, JobState::Processing);
+        assert_eq!(jobs[0].state_reasons, vec!["job-printing"]);
+        assert_eq!(jobs[1].state, JobState::Held);
+        assert_eq!(jobs[2].state, JobState::Pending);
+        assert!(jobs[2].state_reasons.is_empty());
+    }
+
+    #[test]
+    fn queue_names_keep_their_dashes() {
+        let jobs = parse_lpstat_jobs(LPSTAT);
+        assert_eq!(jobs[1].id, 7);
+        assert_eq!(jobs[1].printer, "Lab-2nd-floor");
+        assert_eq!(jobs[1].submitted, "2026-10-04 09:05:12");
+        assert_eq!(jobs[1].size_to_string(), "1.0M");
+    }
+
+    #[test]
+    fn lines_that_are_not_jobs_are_skipped() {
+        let jobs = parse_lpstat_jobs("\tAlerts: job-printing\nno-id-here bob 10 Sat Oct 18 10:00:00 2026\nshort line\n");
+        assert!(jobs.is_empty());
+    }
+
+    #[test]
+    fn dates_are_normalized() {
+        let words = |date: &str| normalize_date(&date.split_whitespace().collect::<Vec<&str>>());
+        assert_eq!(words("Sat Oct 18 10:00:00 2026"), "2026-10-18 10:00:00");
+        assert_eq!(words("Thu Jan  1 00:00:00 2026"), "2026-01-01 00:00:00");
+        assert_eq!(words("Sam 18 Okt 10:00:00 2026"), "Sam 18 Okt 10:00:00 2026");
+        assert_eq!(words("yesterday"), "yesterday");
+    }
+
+    #[test]
+    fn request_ids_are_read_from_lp_output() {
+        assert_eq!(parse_request_id("request id is Office-42 (1 file(s))\n"), Some(42));
+        assert_eq!(parse_request_id("request id is Lab-2nd-floor-7 (1 file(s))\n"), Some(7));
+        assert_eq!(parse_request_id("lp: The printer or class does not exist.\n"), None);
+        assert_eq!(parse_request_id("request id is Office (1 file(s))\n"), None);
+    }
+}
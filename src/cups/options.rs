@@ -0,0 +1,314 @@
+use std::fmt;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum OptionValue {
+    Bool(bool),
+    Int(i64),
+    /// A keyword out of a fixed set of choices, e.g. `two-sided-long-edge`.
+    Enum(String),
+    String(String),
+    List(Vec<String>),
+}
+
+impl OptionValue {
+    /// The value as a list, a single value giving a list of one.
+    pub fn to_list(&self) -> Vec<String> {
+        match self {
+            OptionValue::List(values) => values.clone(),
+            value => vec![value.to_string()],
+        }
+    }
+
+    /// Types a raw value, quoted values always staying strings.
+    pub fn from_raw(raw: &str, quoted: bool) -> Self {
+        if quoted {
+            return OptionValue::String(raw.to_string());
+        }
+        match raw {
+            "true" => return OptionValue::Bool(true),
+            "false" => return OptionValue::Bool(false),
+            _ => {}
+        }
+        if let Ok(value) = raw.parse::<i64>() {
+            return OptionValue::Int(value);
+        }
+        if raw.contains(',') && !raw.starts_with('{') {
+            return OptionValue::List(raw.split(',').map(|value| value.to_string()).collect());
+        }
+        if is_keyword(raw) {
+            OptionValue::Enum(raw.to_string())
+        } else {
+            OptionValue::String(raw.to_string())
+        }
+    }
+}
+
+fn is_keyword(value: &str) -> bool {
+    value.chars().next().is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && value.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_.".contains(c))
+}
+
+impl fmt::Display for OptionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionValue::Bool(value) => write!(f, "{}", value),
+            OptionValue::Int(value) => write!(f, "{}", value),
+            OptionValue::Enum(value) | OptionValue::String(value) => write!(f, "{}", value),
+            OptionValue::List(values) => write!(f, "{}", values.join(", ")),
+        }
+    }
+}
+
+/// Printer options in the order CUPS reported them.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct PrinterOptions(Vec<(String, OptionValue)>);
+
+impl PrinterOptions {
+    /// Parses `name=value` pairs as printed by `lpoptions`, honouring quotes,
+    /// backslash escapes and `{...}` collections like `cupsParseOptions` does.
+    pub fn parse(line: &str) -> Self {
+        let mut options = PrinterOptions::default();
+        let mut chars = line.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|c| *c != '=' && !c.is_whitespace()) {
+                name.push(c);
+            }
+
+            if chars.next_if_eq(&'=').is_none() {
+                match name.strip_prefix("no") {
+                    Some(negated) if !negated.is_empty() => options.insert(negated, OptionValue::Bool(false)),
+                    _ => options.insert(&name, OptionValue::Bool(true)),
+                }
+                continue;
+            }
+
+            let mut value = String::new();
+            let mut quoted = false;
+            let mut depth = 0;
+            while let Some(c) = chars.next_if(|c| depth > 0 || !c.is_whitespace()) {
+                match c {
+                    '\\' => value.extend(chars.next()),
+                    '\'' | '"' if depth == 0 => {
+                        quoted = true;
+                        while let Some(inner) = chars.next_if(|inner| *inner != c) {
+                            if inner == '\\' {
+                                value.extend(chars.next());
+                            } else {
+                                value.push(inner);
+                            }
+                        }
+                        chars.next();
+                    }
+                    '{' => {
+                        depth += 1;
+                        value.push(c);
+                    }
+                    '}' => {
+                        depth -= 1;
+                        value.push(c);
+                    }
+                    _ => value.push(c),
+                }
+            }
+            if !name.is_empty() {
+                options.insert(&name, OptionValue::from_raw(&value, quoted));
+            }
+        }
+        options
+    }
+
+    /// Sets an option, keeping its position when it already exists.
+    pub fn insert(&mut self, name: &str, value: OptionValue) {
+        match self.0.iter_mut().find(|(key, _)| key == name) {
+            Some((_, existing)) => *existing = value,
+            None => self.0.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OptionValue> {
+        self.0.iter().find(|(key, _)| key == name).map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &OptionValue)> {
+        self.0.iter().map(|(name, value)| (name.as_str(), value))
+    }
+}
+
+/// A PPD option and its choices, one line of `lpoptions -l`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OptionChoices {
+    pub name: String,
+    pub label: String,
+    pub choices: Vec<String>,
+    pub default: Option<String>,
+}
+
+impl OptionChoices {
+    /// Parses lines like `PageSize/Media Size: Letter *A4 Legal`, `*` marking the default.
+    pub fn parse(output: &str) -> Vec<OptionChoices> {
+        output.lines()
+            .filter_map(|line| {
+                let (key, choices) = line.split_once(':')?;
+                let (name, label) = key.split_once('/').unwrap_or((key, key));
+                let mut default = None;
+                let choices = choices.split_whitespace()
+                    .map(|choice| match choice.strip_prefix('*') {
+                        Some(choice) => {
+                            default = Some(choice.to_string());
+                            choice.to_string()
+                        }
+                        None => choice.to_string(),
+                    })
+                    .collect();
+                Some(OptionChoices {
+                    name: name.trim().to_string(),
+                    label: label.trim().to_string(),
+                    choices,
+                    default,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(line: &str) -> Vec<(String, OptionValue)> {
+        PrinterOptions::parse(line).0
+    }
+
+    fn string(value: &str) -> OptionValue {
+        OptionValue::String(value.to_string())
+    }
+
+    #[test]
+    fn quoted_values_keep_their_spaces() {
+        let options = PrinterOptions::parse(r#"printer-info='Office Laser' printer-location="Floor 2, room 214" copies=1"#);
+        assert_eq!(options.get("printer-info"), Some(&string("Office Laser")));
+        assert_eq!(options.get("printer-location"), Some(&string("Floor 2, room 214")));
+        assert_eq!(options.get("copies"), Some(&OptionValue::Int(1)));
+    }
+
+    #[test]
+    fn quoted_values_stay_strings() {
+        let options = PrinterOptions::parse("a='42' b=\"true\" c='one,two'");
+        assert_eq!(options.get("a"), Some(&string("42")));
+        assert_eq!(options.get("b"), Some(&string("true")));
+        assert_eq!(options.get("c"), Some(&string("one,two")));
+    }
+
+    #[test]
+    fn backslashes_escape_the_next_char() {
+        let options = PrinterOptions::parse(r#"printer-info=Office\ Laser printer-location='Bob\'s desk' path="C:\\spool""#);
+        assert_eq!(options.get("printer-info"), Some(&string("Office Laser")));
+        assert_eq!(options.get("printer-location"), Some(&string("Bob's desk")));
+        assert_eq!(options.get("path"), Some(&string(r"C:\spool")));
+    }
+
+    #[test]
+    fn collections_keep_their_spaces_and_braces() {
+        let options = PrinterOptions::parse("media-col={media-size={x-dimension=21000 y-dimension=29700}} sides=one-sided");
+        assert_eq!(options.get("media-col"), Some(&string("{media-size={x-dimension=21000 y-dimension=29700}}")));
+        assert_eq!(options.get("sides"), Some(&OptionValue::Enum("one-sided".to_string())));
+    }
+
+    #[test]
+    fn values_are_typed() {
+        assert_eq!(parsed("a=true b=false c=-3 d=two-sided-long-edge e=none,standard f=A4 g"), vec![
+            ("a".to_string(), OptionValue::Bool(true)),
+            ("b".to_string(), OptionValue::Bool(false)),
+            ("c".to_string(), OptionValue::Int(-3)),
+            ("d".to_string(), OptionValue::Enum("two-sided-long-edge".to_string())),
+            ("e".to_string(), OptionValue::List(vec!["none".to_string(), "standard".to_string()])),
+            ("f".to_string(), string("A4")),
+            ("g".to_string(), OptionValue::Bool(true)),
+        ]);
+    }
+
+    #[test]
+    fn no_prefix_negates_bare_options() {
+        let options = PrinterOptions::parse("nocollate no");
+        assert_eq!(options.get("collate"), Some(&OptionValue::Bool(false)));
+        assert_eq!(options.get("no"), Some(&OptionValue::Bool(true)));
+    }
+
+    #[test]
+    fn repeated_options_keep_their_first_position() {
+        assert_eq!(parsed("a=1 b=2 a=3"), vec![
+            ("a".to_string(), OptionValue::Int(3)),
+            ("b".to_string(), OptionValue::Int(2)),
+        ]);
+    }
+
+    #[test]
+    fn empty_input_has_no_options() {
+        assert!(parsed("").is_empty());
+        assert!(parsed(" \t\n").is_empty());
+    }
+
+    #[test]
+    fn malformed_input_is_parsed_as_far_as_it_goes() {
+        // A value without a name is dropped.
+        assert_eq!(parsed("=orphan a=1"), vec![("a".to_string(), OptionValue::Int(1))]);
+        // An unterminated quote runs to the end of the line.
+        assert_eq!(parsed("a='never closed b=2"), vec![("a".to_string(), string("never closed b=2"))]);
+        // A trailing backslash escapes nothing.
+        assert_eq!(parsed(r"a=end\"), vec![("a".to_string(), OptionValue::Enum("end".to_string()))]);
+        // An empty value is an empty string.
+        assert_eq!(parsed("a= b=1"), vec![
+            ("a".to_string(), string("")),
+            ("b".to_string(), OptionValue::Int(1)),
+        ]);
+    }
+
+    #[test]
+    fn choices_mark_the_default() {
+        let choices = OptionChoices::parse("PageSize/Media Size: Letter *A4 Legal\nDuplex/2-Sided Printing: *None DuplexNoTumble DuplexTumble\n");
+        assert_eq!(choices, vec![
+            OptionChoices {
+                name: "PageSize".to_string(),
+                label: "Media Size".to_string(),
+                choices: vec!["Letter".to_string(), "A4".to_string(), "Legal".to_string()],
+                default: Some("A4".to_string()),
+            },
+            OptionChoices {
+                name: "Duplex".to_string(),
+                label: "2-Sided Printing".to_string(),
+                choices: vec!["None".to_string(), "DuplexNoTumble".to_string(), "DuplexTumble".to_string()],
+                default: Some("None".to_string()),
+            },
+        ]);
+    }
+
+    #[test]
+    fn choices_without_default_or_label() {
+        let choices = OptionChoices::parse("Resolution: 300dpi 600dpi");
+        assert_eq!(choices, vec![OptionChoices {
+            name: "Resolution".to_string(),
+            label: "Resolution".to_string(),
+            choices: vec!["300dpi".to_string(), "600dpi".to_string()],
+            default: None,
+        }]);
+    }
+
+    #[test]
+    fn choices_of_empty_and_malformed_input() {
+        assert!(OptionChoices::parse("").is_empty());
+        // Lines without a colon are not options.
+        assert!(OptionChoices::parse("garbage\n\n").is_empty());
+        let choices = OptionChoices::parse("InputSlot/Source:\nnonsense\n");
+        assert_eq!(choices.len(), 1);
+        assert_eq!(choices[0].name, "InputSlot");
+        assert!(choices[0].choices.is_empty());
+        assert_eq!(choices[0].default, None);
+    }
+}
@@ -1,61 +1,456 @@
-use serde::Deserialize;
-use std::process::Command;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug,Default, Clone, Deserialize)]
+use super::{
+    class,
+    error::{CupsError, run},
+    options::{OptionChoices, OptionValue, PrinterOptions},
+};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub enum PrinterState {
+    Idle,
+    Processing,
+    Stopped,
+    #[default]
+    Unknown,
+}
+
+impl PrinterState {
+    /// Maps the IPP `printer-state` enum.
+    pub fn from_ipp(state: i64) -> Self {
+        match state {
+            3 => PrinterState::Idle,
+            4 => PrinterState::Processing,
+            5 => PrinterState::Stopped,
+            _ => PrinterState::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for PrinterState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrinterState::Idle => write!(f, "idle"),
+            PrinterState::Processing => write!(f, "printing"),
+            PrinterState::Stopped => write!(f, "stopped"),
+            PrinterState::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug,Default, Clone, Deserialize, Serialize)]
 pub struct Printer{
     pub name : String,
-    pub options : Vec<String>,
-}
-pub fn get_all_printers() -> Vec<Printer> {
-    let output = Command::new("lpstat")
-        .arg("-e")
-        .output().expect("Failed to execute lpstat command check if CUPS is installed");
-    let printer_names= String::from_utf8(output.stdout).expect("Failed to convert output to string");
-    let enabled_printers:Vec<Printer> = printer_names.split('\n')
+    /// Left out of `--json` listings, the fields below carry what matters of them.
+    #[serde(skip_serializing)]
+    pub options : PrinterOptions,
+    pub state: PrinterState,
+    pub state_message: String,
+    pub state_reasons: Vec<String>,
+    pub accepting: bool,
+    /// `printer-info`, what `lpadmin -D` sets.
+    pub description: String,
+    /// `printer-location`, what `lpadmin -L` sets.
+    pub location: String,
+    pub device_uri: String,
+    /// `printer-make-and-model`, the model of the driver the printer was set up with.
+    pub make_and_model: String,
+    /// Whether this is a class, a pool of printers jobs go to whichever is free.
+    pub is_class: bool,
+    /// The printers of a class.
+    pub members: Vec<String>,
+}
+
+impl Printer {
+    /// Fills state, reasons, accepting flag, description, location, device,
+    /// driver model and class members from the options CUPS reports.
+    pub fn with_state_from_options(mut self) -> Self {
+        if let Some(OptionValue::Int(state)) = self.options.get("printer-state") {
+            self.state = PrinterState::from_ipp(*state);
+        }
+        if let Some(message) = self.options.get("printer-state-message") {
+            self.state_message = message.to_string();
+        }
+        self.state_reasons = self.options.get("printer-state-reasons")
+            .map_or(vec![], |reasons| reasons.to_list())
+            .into_iter()
+            .filter(|reason| reason != "none")
+            .collect();
+        self.accepting = self.options.get("printer-is-accepting-jobs") != Some(&OptionValue::Bool(false));
+        self.description = self.options.get("printer-info").map_or(String::new(), |info| info.to_string());
+        self.location = self.options.get("printer-location").map_or(String::new(), |location| location.to_string());
+        self.device_uri = self.options.get("device-uri").map_or(String::new(), |uri| uri.to_string());
+        self.make_and_model = self.options.get("printer-make-and-model").map_or(String::new(), |model| model.to_string());
+        // CUPS_PRINTER_CLASS is the lowest bit of printer-type.
+        self.is_class = matches!(self.options.get("printer-type"), Some(OptionValue::Int(printer_type)) if printer_type & 1 == 1);
+        self.members = self.options.get("member-names").map_or(vec![], |members| members.to_list());
+        self
+    }
+}
+
+/// Where a default destination is stored.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DefaultScope {
+    /// The server default, for every user.
+    System,
+    /// The current user's `~/.cups/lpoptions`.
+    User,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct PrinterStatus {
+    state: PrinterState,
+    state_message: String,
+    accepting: bool,
+}
+
+pub fn get_all_printers() -> Result<Vec<Printer>, CupsError> {
+    let printer_names = run("lpstat", &["-e"])?;
+    let mut statuses = get_printer_statuses()?;
+    let mut devices = get_printer_devices()?;
+    let mut classes = class::get_class_members()?;
+    printer_names.lines()
         .filter_map(|name| {
             let printer_name = name.trim();
             if printer_name.is_empty() {
                 return None;
             }
-            let options_output = Command::new("lpoptions")
-                .arg("-d")
-                .arg(printer_name)
-                .output()
-                .expect(format!(
-                        "Failed to execute lpstat command check if CUPS is installed v {}",
-                        printer_name
-                    ).as_str());
-
-            let line= String::from_utf8(options_output.stdout).expect("Failed to convert output to string");
-            let options: Vec<String> = line.split(' ').map(|option| option.to_string()).collect();
-            
-            Some(Printer {
-                name: printer_name.to_string(),
-                options 
-            })
+            Some(get_printer_options(printer_name).map(|options| {
+                let printer = Printer {
+                    name: printer_name.to_string(),
+                    options,
+                    ..Default::default()
+                }.with_state_from_options();
+                let members = classes.remove(printer_name);
+                let printer = Printer {
+                    device_uri: devices.remove(printer_name).unwrap_or(printer.device_uri),
+                    is_class: printer.is_class || members.is_some(),
+                    members: members.unwrap_or(printer.members),
+                    ..printer
+                };
+                match statuses.remove(printer_name) {
+                    Some(status) => Printer {
+                        state: status.state,
+                        state_message: status.state_message,
+                        accepting: status.accepting,
+                        ..printer
+                    },
+                    None => printer,
+                }
+            }))
+        })
+        .collect()
+}
+
+fn get_printer_statuses() -> Result<HashMap<String, PrinterStatus>, CupsError> {
+    let output = match run("lpstat", &["-p", "-a"]) {
+        Ok(output) => output,
+        Err(CupsError::Failed { stderr, .. }) if stderr.contains("No destinations") => String::new(),
+        Err(error) => return Err(error),
+    };
+    Ok(parse_printer_statuses(&output))
+}
+
+/// Parses `lpstat -p -a`, the indented lines following a printer being its state message.
+fn parse_printer_statuses(output: &str) -> HashMap<String, PrinterStatus> {
+    let mut statuses: HashMap<String, PrinterStatus> = HashMap::new();
+    let mut message_owner: Option<String> = None;
+    for line in output.lines() {
+        if line.starts_with(char::is_whitespace) {
+            if let Some(status) = message_owner.as_ref().and_then(|name| statuses.get_mut(name)) {
+                let message = line.trim();
+                if !message.is_empty() {
+                    if !status.state_message.is_empty() {
+                        status.state_message.push(' ');
+                    }
+                    status.state_message.push_str(message);
+                }
+            }
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["printer", name, rest @ ..] => {
+                let state = match rest {
+                    ["is", "idle.", ..] => PrinterState::Idle,
+                    ["now", "printing", ..] => PrinterState::Processing,
+                    ["disabled", ..] => PrinterState::Stopped,
+                    _ => PrinterState::Unknown,
+                };
+                statuses.entry(name.to_string()).or_default().state = state;
+                message_owner = Some(name.to_string());
+            }
+            [name, "accepting", "requests", ..] => {
+                statuses.entry(name.to_string()).or_default().accepting = true;
+                message_owner = None;
+            }
+            [name, "not", "accepting", "requests", ..] => {
+                statuses.entry(name.to_string()).or_default().accepting = false;
+                message_owner = None;
+            }
+            _ => message_owner = None,
+        }
+    }
+    statuses
+}
+
+/// Parses the `device for NAME: URI` lines of `lpstat -v`.
+fn get_printer_devices() -> Result<HashMap<String, String>, CupsError> {
+    let output = match run("lpstat", &["-v"]) {
+        Ok(output) => output,
+        Err(CupsError::Failed { stderr, .. }) if stderr.contains("No destinations") => String::new(),
+        Err(error) => return Err(error),
+    };
+    Ok(output.lines()
+        .filter_map(|line| line.strip_prefix("device for "))
+        .filter_map(|line| line.split_once(": "))
+        .map(|(name, uri)| (name.to_string(), uri.trim().to_string()))
+        .collect())
+}
+
+/// The options of `name` as `lpoptions -p` lists them. Unlike `-d`, it leaves
+/// the user's default destination alone.
+pub fn get_printer_options(name: &str) -> Result<PrinterOptions, CupsError> {
+    let line = run("lpoptions", &["-p", name])?;
+    Ok(PrinterOptions::parse(&line))
+}
+
+pub fn get_printer_option_choices(name: &str) -> Result<Vec<OptionChoices>, CupsError> {
+    let output = run("lpoptions", &["-p", name, "-l"])?;
+    Ok(OptionChoices::parse(&output))
+}
+
+pub fn set_printer_option(name: &str, option: &str, value: &str) -> Result<(), CupsError> {
+    run("lpadmin", &["-p", name, "-o", &format!("{}={}", option, value)])?;
+    Ok(())
+}
+
+pub fn enable_printer(name: &str) -> Result<(), CupsError> {
+    run("cupsenable", &[name])?;
+    Ok(())
+}
+
+pub fn disable_printer(name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+    match reason {
+        Some(reason) => run("cupsdisable", &["-r", reason, name])?,
+        None => run("cupsdisable", &[name])?,
+    };
+    Ok(())
+}
+
+pub fn accept_jobs(name: &str) -> Result<(), CupsError> {
+    run("cupsaccept", &[name])?;
+    Ok(())
+}
+
+pub fn reject_jobs(name: &str, reason: Option<&str>) -> Result<(), CupsError> {
+    match reason {
+        Some(reason) => run("cupsreject", &["-r", reason, name])?,
+        None => run("cupsreject", &[name])?,
+    };
+    Ok(())
+}
+
+/// The default destination `lpstat -d` reports, the user's own default winning over the system one.
+pub fn get_default_printer() -> Result<Option<String>, CupsError> {
+    let output = run("lpstat", &["-d"])?;
+    Ok(output.lines()
+        .find_map(|line| line.split_once("default destination:"))
+        .map(|(_, name)| name.trim().to_string())
+        .filter(|name| !name.is_empty()))
+}
+
+pub fn set_default_printer(name: &str, scope: DefaultScope) -> Result<(), CupsError> {
+    match scope {
+        DefaultScope::System => run("lpadmin", &["-d", name])?,
+        DefaultScope::User => run("lpoptions", &["-d", name])?,
+    };
+    Ok(())
+}
+
+/// `~/.cups/lpoptions`, where `lpoptions -d` keeps the user's own default.
+pub fn user_lpoptions() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".cups").join("lpoptions"))
+}
+
+/// The `Default` destination of an lpoptions file, if the user set one.
+pub fn get_user_default(lpoptions: &Path) -> Option<String> {
+    let contents = fs::read_to_string(lpoptions).ok()?;
+    contents.lines()
+        .find_map(|line| line.strip_prefix("Default "))
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(|name| name.to_string())
+}
+
+/// Makes `name` the `Default` line of an lpoptions file like `lpoptions -d` does,
+/// keeping the options saved for it and for the previous default.
+pub fn set_user_default(lpoptions: &Path, name: &str) -> Result<(), CupsError> {
+    let contents = fs::read_to_string(lpoptions).unwrap_or_default();
+    let mut found = false;
+    let mut lines: Vec<String> = contents.lines()
+        .map(|line| {
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let destination = rest.split_whitespace().next().unwrap_or("");
+            match kind {
+                "Default" | "Dest" if destination == name => {
+                    found = true;
+                    format!("Default {}", rest)
+                }
+                "Default" => format!("Dest {}", rest),
+                _ => line.to_string(),
+            }
         })
         .collect();
-    enabled_printers
+    if !found {
+        lines.push(format!("Default {}", name));
+    }
+    if let Some(dir) = lpoptions.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(lpoptions, lines.join("\n") + "\n")?;
+    Ok(())
+}
+
+/// Checks `name` the way cupsd does: at most 127 bytes of printable
+/// characters, none of them blank, `/`, `\`, `?`, `'`, `"`, `#` or `@`, and
+/// not already used by a printer or class in `existing`, ignoring case.
+pub fn validate_name<'a>(name: &str, mut existing: impl Iterator<Item = &'a str>) -> Result<(), CupsError> {
+    let invalid = |message: String| Err(CupsError::InvalidName(message));
+    if name.is_empty() {
+        return invalid("it can't be empty".to_string());
+    }
+    if name.len() > 127 {
+        return invalid(format!("it is {} bytes long, the limit is 127", name.len()));
+    }
+    if let Some(c) = name.chars().find(|c| c.is_whitespace() || c.is_control()) {
+        return invalid(format!("it can't contain {}", if c == ' ' { "spaces".to_string() } else { format!("{:?}", c) }));
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '/' | '\\' | '?' | '\'' | '"' | '#' | '@')) {
+        return invalid(format!("it can't contain {}", c));
+    }
+    if let Some(taken) = existing.find(|taken| taken.eq_ignore_ascii_case(name)) {
+        return invalid(format!("{} already exists", taken));
+    }
+    Ok(())
 }
 
-pub fn create_printer(name: String, device: String, driver: String) {
+/// Adds a printer with `lpadmin`, leaving out the description and location when empty.
+pub fn create_printer(name: &str, device: &str, driver: &str, description: &str, location: &str) -> Result<(), CupsError> {
+    let mut args = vec!["-p", name, "-E", "-v", device, "-m", driver];
+    if !description.is_empty() {
+        args.extend(["-D", description]);
+    }
+    if !location.is_empty() {
+        args.extend(["-L", location]);
+    }
+    run("lpadmin", &args)?;
+    Ok(())
+}
 
-    Command::new("lpadmin")
-        .arg("-p")
-        .arg(name.clone())
-        .arg("-E")
-        .arg("-v")
-        .arg(device.clone())
-        .arg("-m")
-        .arg(driver.clone())
-        .output().expect("Failed to execute lpstat command check if CUPS is installed");
-    
+/// Changes an existing printer with `lpadmin -p`, keeping its PPD unless a
+/// `driver` is given. Empty description and location clear them.
+pub fn modify_printer(name: &str, device: &str, driver: Option<&str>, description: &str, location: &str) -> Result<(), CupsError> {
+    let mut args = vec!["-p", name, "-v", device, "-D", description, "-L", location];
+    if let Some(driver) = driver {
+        args.extend(["-m", driver]);
+    }
+    run("lpadmin", &args)?;
+    Ok(())
 }
-pub fn remove_printer(name: &str) {
-    Command::new("lpadmin")
-        .arg("-x")
-        .arg(name)
-        .output().expect("Failed to execute lpstat command check if CUPS is installed");
+
+pub fn remove_printer(name: &str) -> Result<(), CupsError> {
+    run("lpadmin", &["-x", name])?;
+    Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `lpstat -p -a` with an idle, a printing and a disabled printer, the
+    /// latter rejecting jobs too.
+    const LPSTAT: &str = "\
+printer Office is idle.  enabled since Sat Oct 18 09:00:00 2026
+printer Lab now printing Lab-7.  enabled since Sat Oct 18 10:00:00 2026
+\tSending data to printer.
+printer Basement disabled since Fri Oct 17 18:00:00 2026 -
+\tOut of toner,
+\twaiting for a new cartridge.
+Office accepting requests since Sat Oct 18 09:00:00 2026
+Lab accepting requests since Sat Oct 18 10:00:00 2026
+Basement not accepting requests since Fri Oct 17 18:00:00 2026 -
+\tMoving to the 3rd floor
+";
 
+    fn status(state: PrinterState, state_message: &str, accepting: bool) -> PrinterStatus {
+        PrinterStatus {
+            state,
+            state_message: state_message.to_string(),
+            accepting,
+        }
+    }
+
+    #[test]
+    fn statuses_are_read_per_printer() {
+        let statuses = parse_printer_statuses(LPSTAT);
+        assert_eq!(statuses.len(), 3);
+        assert_eq!(statuses["Office"], status(PrinterState::Idle, "", true));
+        assert_eq!(statuses["Lab"], status(PrinterState::Processing, "Sending data to printer.", true));
+    }
+
+    #[test]
+    fn messages_over_several_lines_are_joined() {
+        let statuses = parse_printer_statuses(LPSTAT);
+        assert_eq!(statuses["Basement"], status(PrinterState::Stopped, "Out of toner, waiting for a new cartridge.", false));
+    }
+
+    #[test]
+    fn rejecting_queues_are_not_accepting() {
+        let output = "printer Front is idle.  enabled since Sat Oct 18 09:00:00 2026\n\
+            Front not accepting requests since Sat Oct 18 09:30:00 2026 -\n\
+            \tRejecting Jobs\n";
+        let statuses = parse_printer_statuses(output);
+        assert_eq!(statuses["Front"], status(PrinterState::Idle, "", false));
+    }
+
+    #[test]
+    fn unknown_lines_end_a_message() {
+        let statuses = parse_printer_statuses("printer Office paused\nscheduler is running\n\tnot a message\n");
+        assert_eq!(statuses["Office"], status(PrinterState::Unknown, "", false));
+        assert!(parse_printer_statuses("").is_empty());
+    }
+
+    #[test]
+    fn names_up_to_127_bytes_are_valid() {
+        assert_eq!(validate_name(&"a".repeat(127), std::iter::empty()), Ok(()));
+        assert!(matches!(validate_name(&"a".repeat(128), std::iter::empty()), Err(CupsError::InvalidName(_))));
+        // The limit is in bytes, so fewer multi-byte characters fill it.
+        assert!(validate_name(&"ü".repeat(64), std::iter::empty()).is_err());
+        assert_eq!(validate_name("Büro_2.OG-Laser", std::iter::empty()), Ok(()));
+    }
+
+    #[test]
+    fn forbidden_characters_are_refused() {
+        for name in ["", "Office Laser", "Office\tLaser", "Lab\u{7}", "a/b", "a\\b", "what?", "it's", "\"q\"", "#1", "lab@home"] {
+            assert!(matches!(validate_name(name, std::iter::empty()), Err(CupsError::InvalidName(_))), "{:?}", name);
+        }
+        assert_eq!(
+            validate_name("Office Laser", std::iter::empty()),
+            Err(CupsError::InvalidName("it can't contain spaces".to_string()))
+        );
+    }
+
+    #[test]
+    fn taken_names_are_refused_whatever_their_case() {
+        let existing = ["Office", "Lab"];
+        assert_eq!(
+            validate_name("office", existing.into_iter()),
+            Err(CupsError::InvalidName("Office already exists".to_string()))
+        );
+        assert_eq!(validate_name("Office2", existing.into_iter()), Ok(()));
+    }
+}
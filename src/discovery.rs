@@ -0,0 +1,110 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use crate::cups::{
+    backend::Backend,
+    device::{Device, DiscoveryOptions},
+    driver::Driver,
+    error::CupsError,
+};
+
+/// What the discovery workers send back to the UI thread.
+#[derive(Debug)]
+pub enum DiscoveryEvent {
+    Device(Device),
+    DevicesDone(Result<(), CupsError>),
+    Drivers(Result<Vec<Driver>, CupsError>),
+    /// The drivers made for a device, see `Discovery::match_drivers`.
+    MatchingDrivers(Device, Result<Vec<Driver>, CupsError>),
+}
+
+/// Device and driver lookups running on background threads.
+///
+/// Dropping it cancels them: a worker stops as soon as it fails to send,
+/// killing `lpinfo` on its way out.
+#[derive(Debug)]
+pub struct Discovery {
+    receiver: Receiver<DiscoveryEvent>,
+    /// Kept to start driver lookups once devices are in.
+    sender: Sender<DiscoveryEvent>,
+    pub scanning_devices: bool,
+    pub loading_drivers: bool,
+    /// Driver lookups for a device still running.
+    pub matching_drivers: usize,
+}
+
+impl Discovery {
+    /// Nothing running yet, for lookups started later on.
+    pub fn idle() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Discovery {
+            receiver,
+            sender,
+            scanning_devices: false,
+            loading_drivers: false,
+            matching_drivers: 0,
+        }
+    }
+
+    /// Starts probing for devices, and listing drivers too when `drivers` is set.
+    pub fn start(backend: &Backend, options: &DiscoveryOptions, drivers: bool) -> Self {
+        let mut discovery = Discovery::idle();
+        discovery.scanning_devices = true;
+        discovery.loading_drivers = drivers;
+
+        let device_backend = backend.clone();
+        let device_sender = discovery.sender.clone();
+        let options = options.clone();
+        thread::spawn(move || {
+            let done = device_backend.discover_devices(&options, &mut |device| {
+                device_sender.send(DiscoveryEvent::Device(device)).is_ok()
+            });
+            let _ = device_sender.send(DiscoveryEvent::DevicesDone(done));
+        });
+
+        if drivers {
+            let driver_backend = backend.clone();
+            let driver_sender = discovery.sender.clone();
+            thread::spawn(move || {
+                let _ = driver_sender.send(DiscoveryEvent::Drivers(driver_backend.get_all_drivers()));
+            });
+        }
+
+        discovery
+    }
+
+    /// Asks cups-driverd for the drivers made for `device`, which can take a
+    /// while, sending them back as `MatchingDrivers`.
+    pub fn match_drivers(&mut self, backend: &Backend, device: Device) {
+        self.matching_drivers += 1;
+        let backend = backend.clone();
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let matching = backend.get_matching_drivers(&device);
+            let _ = sender.send(DiscoveryEvent::MatchingDrivers(device, matching));
+        });
+    }
+
+    /// Whatever the workers sent since the last call, without waiting.
+    pub fn poll(&mut self) -> Vec<DiscoveryEvent> {
+        let events: Vec<DiscoveryEvent> = self.receiver.try_iter().collect();
+        for event in &events {
+            match event {
+                DiscoveryEvent::DevicesDone(_) => self.scanning_devices = false,
+                DiscoveryEvent::Drivers(_) => self.loading_drivers = false,
+                DiscoveryEvent::MatchingDrivers(..) => self.matching_drivers -= 1,
+                DiscoveryEvent::Device(_) => {}
+            }
+        }
+        events
+    }
+
+    /// Whether devices and the driver list are all in.
+    pub fn has_found(&self) -> bool {
+        !self.scanning_devices && !self.loading_drivers
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.has_found() && self.matching_drivers == 0
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Cell,Block,Clear,StatefulWidget,Row,Table,TableState},
+};
+use ratatui::prelude::*;
+use ratatui::layout::Constraint;
+
+use crate::theme::Theme;
+use crate::utils::popup_area;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// A directory listing to pick a file to print from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileBrowser {
+    pub dir: PathBuf,
+    pub entries: Vec<FileEntry>,
+    pub selected: usize,
+}
+
+impl FileBrowser {
+    /// Lists `dir`, directories first, hidden entries left out.
+    pub fn open(dir: PathBuf) -> io::Result<Self> {
+        let mut entries: Vec<FileEntry> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| FileEntry {
+                name: entry.file_name().to_string_lossy().to_string(),
+                is_dir: entry.path().is_dir(),
+            })
+            .filter(|entry| !entry.name.starts_with('.'))
+            .collect();
+        entries.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| a.name.cmp(&b.name)));
+        Ok(FileBrowser {
+            dir,
+            entries,
+            selected: 0,
+        })
+    }
+
+    pub fn next(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.selected = if self.selected >= self.entries.len() - 1 {
+            0
+        } else {
+            self.selected + 1
+        };
+    }
+
+    pub fn previous(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.selected = if self.selected == 0 {
+            self.entries.len() - 1
+        } else {
+            self.selected - 1
+        };
+    }
+
+    /// Goes up a directory, selecting the one we came from.
+    pub fn parent(&mut self) -> io::Result<()> {
+        let Some(parent) = self.dir.parent().map(|parent| parent.to_path_buf()) else {
+            return Ok(());
+        };
+        let name = self.dir.file_name().map(|name| name.to_string_lossy().to_string());
+        *self = FileBrowser::open(parent)?;
+        self.selected = self.entries.iter().position(|entry| Some(&entry.name) == name.as_ref()).unwrap_or(0);
+        Ok(())
+    }
+
+    /// Descends into the selected directory, or returns the selected file.
+    pub fn enter(&mut self) -> io::Result<Option<PathBuf>> {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return Ok(None);
+        };
+        let path = self.dir.join(&entry.name);
+        if entry.is_dir {
+            *self = FileBrowser::open(path)?;
+            Ok(None)
+        } else {
+            Ok(Some(path))
+        }
+    }
+
+    pub fn render(&self, theme: &Theme, area: Rect, buf: &mut Buffer) {
+        let title = Line::from(format!(" {} ", self.dir.display()).bold());
+        let block = Block::bordered()
+            .title(title.centered())
+            .border_set(border::THICK)
+            .border_style(theme.popup());
+        let popup = popup_area(area, 70, area.height.saturating_sub(4).min(self.entries.len() as u16 + 2).max(3));
+        Clear.render(popup, buf);
+        let rows: Vec<Row> = self.entries
+            .iter()
+            .map(|entry| if entry.is_dir {
+                Row::new(vec![Cell::from(format!("{}/", entry.name))]).style(theme.directory())
+            } else {
+                Row::new(vec![Cell::from(entry.name.clone())])
+            })
+            .collect();
+        let table = Table::new(rows, [Constraint::Percentage(100)])
+            .row_highlight_style(theme.highlight())
+            .highlight_symbol("  ")
+            .block(block);
+        let mut state = TableState::default().with_selected(self.selected);
+        StatefulWidget::render(table, popup, buf, &mut state);
+    }
+}
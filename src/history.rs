@@ -0,0 +1,320 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style,Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Cell,Block,Paragraph,StatefulWidget,Row,Table,TableState,Borders},
+};
+use ratatui::prelude::*;
+use ratatui::layout::Constraint;
+
+use crate::cups::job::{Job, JobState};
+
+use crate::utils::{
+    TUIMode,
+    FilterField,
+    edit_text,
+};
+
+use crate::App;
+use crate::keymap::{self, Action, Context, Keymap};
+use crate::theme::Theme;
+use crossterm::event::KeyEvent;
+
+/// Narrows the job history down, empty fields matching everything.
+#[derive(Debug, Default, Clone)]
+pub struct HistoryFilter {
+    pub printer: String,
+    pub user: String,
+    /// Earliest completion date, a prefix of `YYYY-MM-DD HH:MM:SS`.
+    pub from: String,
+    /// Latest completion date, `2026-10-18` covering the whole day.
+    pub to: String,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, job: &Job) -> bool {
+        let contains = |value: &str, filter: &str| value.to_lowercase().contains(&filter.to_lowercase());
+        let completed = job.completed.get(..self.to.len()).unwrap_or(&job.completed);
+        contains(&job.printer, &self.printer)
+            && contains(&job.user, &self.user)
+            && job.completed.as_str() >= self.from.as_str()
+            && (self.to.is_empty() || completed <= self.to.as_str())
+    }
+
+    fn field_mut(&mut self, field: FilterField) -> &mut String {
+        match field {
+            FilterField::Printer => &mut self.printer,
+            FilterField::User => &mut self.user,
+            FilterField::From => &mut self.from,
+            FilterField::To => &mut self.to,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct History<'a> {
+    pub state: TableState,
+    pub jobs: Vec<&'a Job>,
+    pub filter: &'a HistoryFilter,
+    pub filter_field: Option<FilterField>,
+    pub keymap: &'a Keymap,
+    pub theme: &'a Theme,
+    pub help: bool,
+}
+
+impl<'a> History<'a> {
+    pub fn new(app: &'a App) -> Self {
+        History {
+            state: TableState::default()
+                .with_selected(app.selected_history),
+            jobs: History::visible(app),
+            filter: &app.history_filter,
+            filter_field: app.filter_field,
+            keymap: &app.config.keymap,
+            theme: &app.config.palette,
+            help: app.help,
+        }
+    }
+
+    /// The history jobs passing the filter, most recently completed first.
+    fn visible(app: &App) -> Vec<&Job> {
+        app.history
+            .iter()
+            .filter(|job| app.history_filter.matches(job))
+            .collect()
+    }
+
+    /// Opens the history, filtered to the printer the jobs screen was showing.
+    pub fn open(app:&mut App) {
+        app.history_filter.printer = if app.jobs_all_printers {
+            String::new()
+        } else {
+            app.printers.get(app.selected_printer).map_or(String::new(), |p| p.name.clone())
+        };
+        app.selected_history = 0;
+        app.filter_field = None;
+        History::refresh_history(app);
+        app.change_mode(TUIMode::History);
+    }
+
+    pub fn handle_events(app:&mut App, key_event: KeyEvent) {
+        if let Some(field) = app.filter_field {
+            History::handle_filter_mode(app, field, key_event);
+            return;
+        }
+        match app.config.keymap.action(key_event, keymap::HISTORY) {
+            Some(Action::Up) => History::previous_job(app),
+            Some(Action::Down) => History::next_job(app),
+            Some(Action::Filter) => app.filter_field = Some(FilterField::Printer),
+            Some(Action::ClearFilter) => {
+                app.history_filter = HistoryFilter::default();
+                app.selected_history = 0;
+            }
+            Some(Action::Refresh) => History::refresh_history(app),
+            Some(Action::Back) => app.change_mode(TUIMode::Jobs),
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
+            _ => {}
+        }
+    }
+
+    fn handle_filter_mode(app:&mut App, field: FilterField, key_event: KeyEvent) {
+        match app.config.keymap.text_action(key_event, keymap::HISTORY_FILTER) {
+            Some(Action::NextField) => app.filter_field = Some(field.next()),
+            Some(Action::Select | Action::Back) => app.filter_field = None,
+            _ => {
+                edit_text(app.history_filter.field_mut(field), key_event);
+            }
+        }
+        app.selected_history = 0;
+    }
+
+    fn refresh_history(app:&mut App) {
+        let history = app.backend.get_completed_jobs(None);
+        app.history = app.report(history).unwrap_or_default();
+        app.history.sort_by(|a, b| b.completed.cmp(&a.completed));
+        let count = History::visible(app).len();
+        if app.selected_history >= count {
+            app.selected_history = count.saturating_sub(1);
+        }
+    }
+
+    fn next_job(app:&mut App) {
+        let count = History::visible(app).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_history = if app.selected_history >= count - 1 {
+            0
+        } else {
+            app.selected_history + 1
+        };
+    }
+
+    fn previous_job(app:&mut App) {
+        let count = History::visible(app).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_history = if app.selected_history == 0 {
+            count - 1
+        } else {
+            app.selected_history - 1
+        };
+    }
+
+    fn jobs_to_rows(&self) -> Vec<Row<'static>> {
+        self.jobs
+            .iter()
+            .map(|job| {
+                let style = match job.state {
+                    JobState::Completed => self.theme.text(),
+                    _ => self.theme.error(),
+                };
+                Row::new(vec![
+                    Cell::from(job.id.to_string()),
+                    Cell::from(job.printer.clone()),
+                    Cell::from(job.user.clone()),
+                    Cell::from(job.title.clone()),
+                    Cell::from(job.state.to_string()),
+                    Cell::from(job.completed.clone()),
+                ])
+                .style(style)
+            })
+            .collect()
+    }
+
+    fn detail_to_rows(job: &Job) -> Vec<Row<'static>> {
+        let count = |value: Option<u32>| value.map_or("-".to_string(), |value| value.to_string());
+        let or_dash = |value: &str| if value.is_empty() { "-".to_string() } else { value.to_string() };
+        let mut rows = vec![
+            ("Job", job.id.to_string()),
+            ("Printer", job.printer.clone()),
+            ("User", job.user.clone()),
+            ("Title", or_dash(&job.title)),
+            ("Size", job.size_to_string()),
+            ("Pages", count(job.pages)),
+            ("Copies", count(job.copies)),
+            ("Submitted", or_dash(&job.submitted)),
+            ("Completed", or_dash(&job.completed)),
+            ("Final state", job.state.to_string()),
+        ];
+        rows.extend(job.state_reasons.iter().enumerate().map(|(i, reason)| {
+            (if i == 0 { "Reason" } else { "" }, reason.clone())
+        }));
+        rows.into_iter()
+            .map(|(name, value)| Row::new(vec![Cell::from(name).bold(), Cell::from(value)]))
+            .collect()
+    }
+
+    fn context(&self) -> Context {
+        if self.filter_field.is_some() {
+            keymap::HISTORY_FILTER
+        } else {
+            keymap::HISTORY
+        }
+    }
+
+    fn render_filter(&self, area: Rect, buf: &mut Buffer) {
+        let fields = [
+            (FilterField::Printer, " Printer: ", &self.filter.printer),
+            (FilterField::User, " User: ", &self.filter.user),
+            (FilterField::From, " From: ", &self.filter.from),
+            (FilterField::To, " To: ", &self.filter.to),
+        ];
+        let mut spans = vec![];
+        for (field, label, value) in fields {
+            spans.push(label.fg(self.theme.text).bold());
+            let value = if self.filter_field == Some(field) {
+                Span::styled(format!("{}_", value), self.theme.highlight())
+            } else if value.is_empty() {
+                Span::styled("any", self.theme.muted())
+            } else {
+                value.to_string().fg(self.theme.text)
+            };
+            spans.push(value);
+            spans.push("  ".into());
+        }
+        let block = Block::default().borders(Borders::ALL)
+            .title(Line::from(" Filter ".fg(self.theme.text).bold()))
+            .border_set(border::THICK)
+            .border_style(self.theme.border(self.filter_field.is_some()));
+        Paragraph::new(Line::from(spans)).block(block).render(area, buf);
+    }
+
+    pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Length(3),
+                Constraint::Min(0),
+            ])
+            .split(area);
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![
+                Constraint::Percentage(65),
+                Constraint::Percentage(35),
+            ])
+            .split(layout[1]);
+
+        self.render_filter(layout[0], buf);
+
+        let instructions = self.keymap.hints(self.theme, self.context(), self.filter_field.is_some(), columns[0].width.saturating_sub(2));
+        let block = Block::default().borders(Borders::ALL)
+            .title(Line::from(" Completed Jobs ".fg(self.theme.text).bold()).centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+
+        let widths = [
+            Constraint::Length(6),
+            Constraint::Percentage(18),
+            Constraint::Percentage(14),
+            Constraint::Percentage(30),
+            Constraint::Length(10),
+            Constraint::Length(19),
+        ];
+
+        let header = Row::new(vec![
+                Cell::from("ID"),
+                Cell::from("Printer"),
+                Cell::from("User"),
+                Cell::from("Title"),
+                Cell::from("State"),
+                Cell::from("Completed"),
+            ])
+            .style(Style::new().bold())
+            .bottom_margin(1);
+
+        let table = Table::new(self.jobs_to_rows(),widths)
+            .header(header)
+            .column_spacing(1)
+            .row_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
+            .block(block);
+
+        StatefulWidget::render(
+            table,
+            columns[0],
+            buf,
+            &mut self.state,
+        );
+
+        let detail = self.state.selected()
+            .and_then(|selected| self.jobs.get(selected))
+            .map_or(vec![], |job| History::detail_to_rows(job));
+        let block = Block::default().borders(Borders::ALL)
+            .title(Line::from(" Details ".fg(self.theme.text).bold()).centered())
+            .border_set(border::THICK);
+        let table = Table::new(detail, [Constraint::Length(12), Constraint::Min(0)])
+            .column_spacing(1)
+            .block(block);
+        Widget::render(table, columns[1], buf);
+
+        if self.help {
+            self.keymap.render_help(self.theme, self.context(), area, buf);
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Style,Stylize},
+    symbols::border,
+    text::Line,
+    widgets::{Cell,Block,Clear,StatefulWidget,Row,Table,TableState,Borders},
+};
+use ratatui::prelude::*;
+use ratatui::layout::Constraint;
+
+use crate::cups::{
+    error::CupsError,
+    job::{Job, JobState},
+    printer::Printer,
+};
+
+use crate::utils::{
+    TUIMode,
+    Status,
+    popup_area,
+};
+
+use crate::App;
+use crate::history::History;
+use crate::keymap::{self, Action, Context, Keymap};
+use crate::theme::Theme;
+use crossterm::event::KeyEvent;
+
+#[derive(Debug)]
+pub struct Jobs<'a> {
+    pub state: TableState,
+    pub move_state: TableState,
+    pub jobs: &'a Vec<Job>,
+    pub printers: &'a Vec<Printer>,
+    pub scope: Option<&'a str>,
+    pub move_target: Option<usize>,
+    pub keymap: &'a Keymap,
+    pub theme: &'a Theme,
+    pub help: bool,
+}
+
+impl<'a> Jobs<'a> {
+    pub fn new(app: &'a App) -> Self {
+        Jobs {
+            state: TableState::default()
+                .with_selected(app.selected_job),
+            move_state: TableState::default()
+                .with_selected(app.move_target),
+            jobs: &app.jobs,
+            printers: &app.printers,
+            scope: Jobs::scope(app),
+            move_target: app.move_target,
+            keymap: &app.config.keymap,
+            theme: &app.config.palette,
+            help: app.help,
+        }
+    }
+
+    /// The printer jobs are listed for, `None` meaning every printer.
+    fn scope(app: &App) -> Option<&str> {
+        if app.jobs_all_printers {
+            None
+        } else {
+            app.printers.get(app.selected_printer).map(|p| p.name.as_str())
+        }
+    }
+
+    pub fn open(app:&mut App) {
+        app.selected_job = 0;
+        app.move_target = None;
+        Jobs::refresh_jobs(app);
+        app.change_mode(TUIMode::Jobs);
+    }
+
+    pub fn handle_events(app:&mut App, key_event: KeyEvent) {
+        if app.move_target.is_some() {
+            Jobs::handle_move_mode(app,key_event);
+            return;
+        }
+        match app.config.keymap.action(key_event, keymap::JOBS) {
+            Some(Action::Up) => Jobs::previous_job(app),
+            Some(Action::Down) => Jobs::next_job(app),
+            Some(Action::ToggleScope) => {
+                app.jobs_all_printers = !app.jobs_all_printers;
+                app.selected_job = 0;
+                Jobs::refresh_jobs(app);
+            }
+            Some(Action::Refresh) => Jobs::refresh_jobs(app),
+            Some(Action::CancelJob) => Jobs::run_on_job(app, "canceled", |app, id| app.backend.cancel_job(id)),
+            Some(Action::CancelAllJobs) => Jobs::cancel_all_jobs(app),
+            Some(Action::HoldJob) => Jobs::run_on_job(app, "held", |app, id| app.backend.hold_job(id)),
+            Some(Action::ReleaseJob) => Jobs::run_on_job(app, "released", |app, id| app.backend.release_job(id)),
+            Some(Action::RestartJob) => Jobs::run_on_job(app, "restarted", |app, id| app.backend.restart_job(id)),
+            Some(Action::MoveJob) => Jobs::start_move(app),
+            Some(Action::Completed) => History::open(app),
+            Some(Action::Back) => app.change_mode(TUIMode::View),
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
+            _ => {}
+        }
+    }
+
+    fn handle_move_mode(app:&mut App, key_event: KeyEvent) {
+        let count = app.printers.len();
+        match app.config.keymap.action(key_event, keymap::MOVE_JOB) {
+            Some(Action::Up) => {
+                app.move_target = app.move_target.map(|target| if target == 0 { count - 1 } else { target - 1 });
+            }
+            Some(Action::Down) => {
+                app.move_target = app.move_target.map(|target| if target >= count - 1 { 0 } else { target + 1 });
+            }
+            Some(Action::Select) => Jobs::move_job(app),
+            Some(Action::Back) => app.move_target = None,
+            _ => {}
+        }
+    }
+
+    pub fn refresh_jobs(app:&mut App) {
+        let scope = Jobs::scope(app).map(|scope| scope.to_string());
+        let jobs = app.backend.get_jobs(scope.as_deref());
+        app.jobs = app.report(jobs).unwrap_or_default();
+        if app.selected_job >= app.jobs.len() {
+            app.selected_job = app.jobs.len().saturating_sub(1);
+        }
+    }
+
+    fn next_job(app:&mut App) {
+        if app.jobs.is_empty() {
+            return;
+        }
+        app.selected_job = if app.selected_job >= app.jobs.len() - 1 {
+            0
+        } else {
+            app.selected_job + 1
+        };
+    }
+
+    fn previous_job(app:&mut App) {
+        if app.jobs.is_empty() {
+            return;
+        }
+        app.selected_job = if app.selected_job == 0 {
+            app.jobs.len() - 1
+        } else {
+            app.selected_job - 1
+        };
+    }
+
+    fn run_on_job(app:&mut App, done: &str, action: fn(&App, u32) -> Result<(), CupsError>) {
+        let Some(id) = app.jobs.get(app.selected_job).map(|job| job.id) else {
+            return;
+        };
+        let result = action(app, id);
+        if app.report(result).is_some() {
+            app.status = Some(Status::Info(format!("Job {} {}", id, done)));
+        }
+        Jobs::refresh_jobs(app);
+    }
+
+    fn cancel_all_jobs(app:&mut App) {
+        let printer = Jobs::scope(app)
+            .or_else(|| app.jobs.get(app.selected_job).map(|job| job.printer.as_str()))
+            .map(|printer| printer.to_string());
+        let Some(printer) = printer else {
+            return;
+        };
+        let canceled = app.backend.cancel_all_jobs(&printer);
+        if app.report(canceled).is_some() {
+            app.status = Some(Status::Info(format!("All jobs on {} canceled", printer)));
+        }
+        Jobs::refresh_jobs(app);
+    }
+
+    fn start_move(app:&mut App) {
+        if !app.jobs.is_empty() && !app.printers.is_empty() {
+            app.move_target = Some(0);
+        }
+    }
+
+    fn move_job(app:&mut App) {
+        let target = app.move_target.take()
+            .and_then(|target| app.printers.get(target))
+            .map(|printer| printer.name.clone());
+        let (Some(id), Some(target)) = (app.jobs.get(app.selected_job).map(|job| job.id), target) else {
+            return;
+        };
+        let moved = app.backend.move_job(id, &target);
+        if app.report(moved).is_some() {
+            app.status = Some(Status::Info(format!("Job {} moved to {}", id, target)));
+        }
+        Jobs::refresh_jobs(app);
+    }
+
+    fn jobs_to_rows(&self) -> Vec<Row<'static>> {
+        self.jobs
+            .iter()
+            .map(|job| {
+                let style = match job.state {
+                    JobState::Processing => self.theme.active(),
+                    JobState::Held | JobState::Stopped => self.theme.error(),
+                    _ => self.theme.text(),
+                };
+                Row::new(vec![
+                    Cell::from(job.id.to_string()),
+                    Cell::from(job.printer.clone()),
+                    Cell::from(job.user.clone()),
+                    Cell::from(job.title.clone()),
+                    Cell::from(job.size_to_string()),
+                    Cell::from(job.state.to_string()),
+                    Cell::from(job.submitted.clone()),
+                ])
+                .style(style)
+            })
+            .collect()
+    }
+
+    fn context(&self) -> Context {
+        if self.move_target.is_some() {
+            keymap::MOVE_JOB
+        } else {
+            keymap::JOBS
+        }
+    }
+
+    fn render_move_popup(&mut self, area: Rect, buf: &mut Buffer) {
+        let title = Line::from(" Move job to ".bold());
+        let block = Block::bordered()
+            .title(title.centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.popup());
+        let popup = popup_area(area, 40, self.printers.len() as u16 + 2);
+        Clear.render(popup, buf);
+        let rows: Vec<Row> = self.printers
+            .iter()
+            .map(|printer| Row::new(vec![Cell::from(printer.name.clone())]))
+            .collect();
+        let table = Table::new(rows, [Constraint::Percentage(100)])
+            .row_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
+            .block(block);
+        StatefulWidget::render(table, popup, buf, &mut self.move_state);
+    }
+
+    pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
+        let instructions = self.keymap.hints(self.theme, self.context(), false, area.width.saturating_sub(2));
+        let title = Line::from(match self.scope {
+            Some(printer) => format!(" Jobs on {} ", printer),
+            None => " Jobs ".to_string(),
+        }.fg(self.theme.text).bold());
+        let block = Block::default().borders(Borders::ALL)
+            .title(title.centered())
+            .title_bottom(instructions.centered())
+            .border_set(border::THICK);
+
+        let widths = [
+            Constraint::Length(6),
+            Constraint::Percentage(15),
+            Constraint::Percentage(12),
+            Constraint::Percentage(30),
+            Constraint::Length(8),
+            Constraint::Length(11),
+            Constraint::Length(19),
+        ];
+
+        let header = Row::new(vec![
+                Cell::from("ID"),
+                Cell::from("Printer"),
+                Cell::from("User"),
+                Cell::from("Title"),
+                Cell::from("Size"),
+                Cell::from("State"),
+                Cell::from("Submitted"),
+            ])
+            .style(Style::new().bold())
+            .bottom_margin(1);
+
+        let table = Table::new(self.jobs_to_rows(),widths)
+            .header(header)
+            .column_spacing(1)
+            .row_highlight_style(self.theme.highlight())
+            .cell_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
+            .block(block);
+
+        StatefulWidget::render(
+            table,
+            area,
+            buf,
+            &mut self.state,
+        );
+
+        if self.move_target.is_some() {
+            self.render_move_popup(area, buf);
+        }
+
+        if self.help {
+            self.keymap.render_help(self.theme, self.context(), area, buf);
+        }
+    }
+}
@@ -0,0 +1,811 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Constraint, Rect},
+    style::Stylize,
+    symbols::border,
+    text::{Line, Span},
+    widgets::{Block, Cell, Clear, Row, Table, Widget},
+};
+use serde::Deserialize;
+
+use crate::theme::Theme;
+use crate::utils::popup_area;
+
+/// Everything a key can do, each screen handling the ones listed in its `Context`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Action {
+    Up,
+    Down,
+    Left,
+    Right,
+    NextField,
+    PreviousField,
+    Select,
+    Back,
+    Quit,
+    Help,
+    Add,
+    Edit,
+    Remove,
+    Undo,
+    ToggleEnabled,
+    ToggleAccepting,
+    Jobs,
+    Classes,
+    SetDefault,
+    SystemDefault,
+    UserDefault,
+    TestPage,
+    PrintFile,
+    Write,
+    Rescan,
+    StopScan,
+    EnterUri,
+    Search,
+    Yes,
+    No,
+    CancelJob,
+    CancelAllJobs,
+    HoldJob,
+    ReleaseJob,
+    RestartJob,
+    MoveJob,
+    ToggleScope,
+    Refresh,
+    Completed,
+    Filter,
+    ClearFilter,
+    NewClass,
+    RemoveMember,
+    Pick,
+}
+
+impl Action {
+    pub const ALL: [Action; 44] = [
+        Action::Up,
+        Action::Down,
+        Action::Left,
+        Action::Right,
+        Action::NextField,
+        Action::PreviousField,
+        Action::Select,
+        Action::Back,
+        Action::Quit,
+        Action::Help,
+        Action::Add,
+        Action::Edit,
+        Action::Remove,
+        Action::Undo,
+        Action::ToggleEnabled,
+        Action::ToggleAccepting,
+        Action::Jobs,
+        Action::Classes,
+        Action::SetDefault,
+        Action::SystemDefault,
+        Action::UserDefault,
+        Action::TestPage,
+        Action::PrintFile,
+        Action::Write,
+        Action::Rescan,
+        Action::StopScan,
+        Action::EnterUri,
+        Action::Search,
+        Action::Yes,
+        Action::No,
+        Action::CancelJob,
+        Action::CancelAllJobs,
+        Action::HoldJob,
+        Action::ReleaseJob,
+        Action::RestartJob,
+        Action::MoveJob,
+        Action::ToggleScope,
+        Action::Refresh,
+        Action::Completed,
+        Action::Filter,
+        Action::ClearFilter,
+        Action::NewClass,
+        Action::RemoveMember,
+        Action::Pick,
+    ];
+
+    /// The name used in the `[keys.bindings]` table of the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Up => "up",
+            Action::Down => "down",
+            Action::Left => "left",
+            Action::Right => "right",
+            Action::NextField => "next_field",
+            Action::PreviousField => "previous_field",
+            Action::Select => "select",
+            Action::Back => "back",
+            Action::Quit => "quit",
+            Action::Help => "help",
+            Action::Add => "add",
+            Action::Edit => "edit",
+            Action::Remove => "remove",
+            Action::Undo => "undo",
+            Action::ToggleEnabled => "toggle_enabled",
+            Action::ToggleAccepting => "toggle_accepting",
+            Action::Jobs => "jobs",
+            Action::Classes => "classes",
+            Action::SetDefault => "set_default",
+            Action::SystemDefault => "system_default",
+            Action::UserDefault => "user_default",
+            Action::TestPage => "test_page",
+            Action::PrintFile => "print_file",
+            Action::Write => "write",
+            Action::Rescan => "rescan",
+            Action::StopScan => "stop_scan",
+            Action::EnterUri => "enter_uri",
+            Action::Search => "search",
+            Action::Yes => "yes",
+            Action::No => "no",
+            Action::CancelJob => "cancel_job",
+            Action::CancelAllJobs => "cancel_all_jobs",
+            Action::HoldJob => "hold_job",
+            Action::ReleaseJob => "release_job",
+            Action::RestartJob => "restart_job",
+            Action::MoveJob => "move_job",
+            Action::ToggleScope => "toggle_scope",
+            Action::Refresh => "refresh",
+            Action::Completed => "completed",
+            Action::Filter => "filter",
+            Action::ClearFilter => "clear_filter",
+            Action::NewClass => "new_class",
+            Action::RemoveMember => "remove_member",
+            Action::Pick => "pick",
+        }
+    }
+
+    /// Keys of the `vim` and `arrows` presets, the first one being shown in instructions.
+    fn preset_keys(self, arrows: bool) -> &'static [&'static str] {
+        match (self, arrows) {
+            (Action::Up, false) => &["k", "Up"],
+            (Action::Up, true) => &["Up"],
+            (Action::Down, false) => &["j", "Down"],
+            (Action::Down, true) => &["Down"],
+            (Action::Left, false) => &["h", "Left"],
+            (Action::Left, true) => &["Left"],
+            (Action::Right, false) => &["l", "Right"],
+            (Action::Right, true) => &["Right"],
+            (Action::Edit, false) => &["e", "Enter"],
+            (Action::Edit, true) => &["Enter", "e"],
+            (Action::NextField, _) => &["Tab"],
+            (Action::PreviousField, _) => &["BackTab"],
+            (Action::Select, _) => &["Enter"],
+            (Action::Back, _) => &["Esc"],
+            (Action::Quit, _) => &["q"],
+            (Action::Help, _) => &["?"],
+            (Action::Add, _) => &["a"],
+            (Action::Remove, _) => &["d"],
+            (Action::Undo, _) => &["u"],
+            (Action::ToggleEnabled, _) => &["x"],
+            (Action::ToggleAccepting, _) => &["r"],
+            (Action::Jobs, _) => &["o"],
+            (Action::Classes, _) => &["c"],
+            (Action::SetDefault, _) => &["s"],
+            (Action::SystemDefault, _) => &["s"],
+            (Action::UserDefault, _) => &["u"],
+            (Action::TestPage, _) => &["t"],
+            (Action::PrintFile, _) => &["p"],
+            (Action::Write, _) => &["w"],
+            (Action::Rescan, _) => &["r"],
+            (Action::StopScan, _) => &["c"],
+            (Action::EnterUri, _) => &["u"],
+            (Action::Search, _) => &["/"],
+            (Action::Yes, _) => &["y"],
+            (Action::No, _) => &["n"],
+            (Action::CancelJob, _) => &["c"],
+            (Action::CancelAllJobs, _) => &["C"],
+            (Action::HoldJob, _) => &["h"],
+            (Action::ReleaseJob, _) => &["r"],
+            (Action::RestartJob, _) => &["s"],
+            (Action::MoveJob, _) => &["m"],
+            (Action::ToggleScope, _) => &["p"],
+            (Action::Refresh, _) => &["u"],
+            (Action::Completed, _) => &["w"],
+            (Action::Filter, _) => &["f"],
+            (Action::ClearFilter, _) => &["x"],
+            (Action::NewClass, _) => &["n"],
+            (Action::RemoveMember, _) => &["x"],
+            (Action::Pick, _) => &["Space"],
+        }
+    }
+}
+
+/// The actions a screen or popup handles, with their instruction labels.
+/// Entries with an empty label work but are left out of the instructions.
+pub type Context = &'static [(Action, &'static str)];
+
+pub const PRINTERS: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Right, "Options"),
+    (Action::NextField, ""),
+    (Action::Add, "Add new printer"),
+    (Action::Edit, "Edit printer"),
+    (Action::Remove, "Remove printer"),
+    (Action::Undo, "Undo remove"),
+    (Action::ToggleEnabled, "Enable/Disable"),
+    (Action::ToggleAccepting, "Accept/Reject"),
+    (Action::Jobs, "Jobs"),
+    (Action::Classes, "Classes"),
+    (Action::SetDefault, "Set default"),
+    (Action::TestPage, "Test page"),
+    (Action::PrintFile, "Print file"),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+pub const OPTIONS: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Edit, "Edit option"),
+    (Action::Left, "Printers"),
+    (Action::PreviousField, ""),
+    (Action::Back, ""),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+pub const OPTION_CHOICES: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Select, "Save"),
+    (Action::Back, "Cancel"),
+];
+
+/// Popups taking typed text, only answering keys that don't type a character.
+pub const TEXT_INPUT: Context = &[
+    (Action::Select, "Confirm"),
+    (Action::Back, "Cancel"),
+];
+
+pub const DEFAULT_PROMPT: Context = &[
+    (Action::SystemDefault, "System-wide"),
+    (Action::UserDefault, "Just for me"),
+    (Action::Back, "Cancel"),
+];
+
+pub const FILE_BROWSER: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Left, "Parent"),
+    (Action::Right, ""),
+    (Action::Select, "Open/Print"),
+    (Action::Back, "Cancel"),
+];
+
+pub const CONFIRM: Context = &[
+    (Action::Yes, "Yes"),
+    (Action::No, "No"),
+    (Action::Select, ""),
+    (Action::Back, ""),
+];
+
+pub const FORM: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::NextField, ""),
+    (Action::PreviousField, ""),
+    (Action::Edit, "Edit Mode"),
+    (Action::Write, "Save"),
+    (Action::Rescan, "Rescan"),
+    (Action::StopScan, "Cancel scan"),
+    (Action::Back, "Quit Add Mode"),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+/// The printer name, description and location fields of the form.
+pub const FORM_TEXT: Context = &[
+    (Action::Select, ""),
+    (Action::Back, "Quit Edit Mode"),
+];
+
+pub const DEVICES: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::EnterUri, "Enter URI"),
+    (Action::Rescan, "Rescan"),
+    (Action::StopScan, "Cancel scan"),
+    (Action::Back, "Quit Edit Mode"),
+    (Action::Help, "Help"),
+];
+
+pub const URI_BUILDER: Context = &[
+    (Action::Left, "Prev scheme"),
+    (Action::Right, "Next scheme"),
+    (Action::NextField, "Field"),
+    (Action::PreviousField, ""),
+    (Action::Down, ""),
+    (Action::Up, ""),
+    (Action::Select, "Add"),
+    (Action::Back, "Cancel"),
+];
+
+pub const DRIVERS: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Search, "Search"),
+    (Action::Back, "Quit Edit Mode"),
+    (Action::Help, "Help"),
+];
+
+pub const DRIVER_SEARCH: Context = &[
+    (Action::Select, "Keep results"),
+    (Action::Back, "Clear search"),
+];
+
+pub const CHANGES: Context = &[
+    (Action::Yes, "Apply"),
+    (Action::No, "Cancel"),
+    (Action::Select, ""),
+    (Action::Back, ""),
+];
+
+pub const JOBS: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::CancelJob, "Cancel"),
+    (Action::CancelAllJobs, "Cancel all"),
+    (Action::HoldJob, "Hold"),
+    (Action::ReleaseJob, "Release"),
+    (Action::RestartJob, "Restart"),
+    (Action::MoveJob, "Move"),
+    (Action::ToggleScope, "All/Printer"),
+    (Action::Refresh, "Refresh"),
+    (Action::Completed, "Completed"),
+    (Action::Back, "Printers"),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+pub const MOVE_JOB: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Select, "Move"),
+    (Action::Back, "Cancel"),
+];
+
+pub const HISTORY: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Filter, "Filter"),
+    (Action::ClearFilter, "Clear filter"),
+    (Action::Refresh, "Refresh"),
+    (Action::Back, "Jobs"),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+pub const HISTORY_FILTER: Context = &[
+    (Action::NextField, "Next field"),
+    (Action::Select, "Done"),
+    (Action::Back, ""),
+];
+
+pub const CLASSES: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Right, "Members"),
+    (Action::NextField, ""),
+    (Action::NewClass, "New class"),
+    (Action::Add, "Add members"),
+    (Action::Remove, "Remove class"),
+    (Action::Refresh, "Refresh"),
+    (Action::Back, "Printers"),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+pub const MEMBERS: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::RemoveMember, "Remove member"),
+    (Action::Left, "Classes"),
+    (Action::PreviousField, ""),
+    (Action::Back, ""),
+    (Action::Help, "Help"),
+    (Action::Quit, "Quit"),
+];
+
+pub const MEMBER_PICKER: Context = &[
+    (Action::Up, "Up"),
+    (Action::Down, "Down"),
+    (Action::Pick, "Pick"),
+    (Action::NextField, "Name/Printers"),
+    (Action::PreviousField, ""),
+    (Action::Select, "Create"),
+    (Action::Back, "Cancel"),
+];
+
+/// Every context, named for the errors about keys clashing in them.
+const CONTEXTS: [(&str, Context); 21] = [
+    ("the printers list", PRINTERS),
+    ("the options pane", OPTIONS),
+    ("the option choices", OPTION_CHOICES),
+    ("text prompts", TEXT_INPUT),
+    ("the default printer prompt", DEFAULT_PROMPT),
+    ("the file browser", FILE_BROWSER),
+    ("confirmations", CONFIRM),
+    ("the printer form", FORM),
+    ("the printer form fields", FORM_TEXT),
+    ("the devices list", DEVICES),
+    ("the device URI builder", URI_BUILDER),
+    ("the drivers list", DRIVERS),
+    ("the driver search", DRIVER_SEARCH),
+    ("the changes prompt", CHANGES),
+    ("the jobs list", JOBS),
+    ("the move job popup", MOVE_JOB),
+    ("the job history", HISTORY),
+    ("the history filter", HISTORY_FILTER),
+    ("the classes list", CLASSES),
+    ("the class members", MEMBERS),
+    ("the member picker", MEMBER_PICKER),
+];
+
+/// A key as written in the config file, like `k`, `Enter`, `Space` or `Ctrl+r`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    ctrl: bool,
+}
+
+impl Key {
+    pub fn parse(text: &str) -> Result<Key, String> {
+        let (ctrl, name) = match text.strip_prefix("Ctrl+").or_else(|| text.strip_prefix("C-")) {
+            Some(name) => (true, name),
+            None => (false, text),
+        };
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            // Terminals report Ctrl with the unshifted character.
+            (Some(c), None) if ctrl => KeyCode::Char(c.to_ascii_lowercase()),
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name {
+                "Space" => KeyCode::Char(' '),
+                "Enter" => KeyCode::Enter,
+                "Esc" => KeyCode::Esc,
+                "Tab" => KeyCode::Tab,
+                "BackTab" => KeyCode::BackTab,
+                "Backspace" => KeyCode::Backspace,
+                "Delete" => KeyCode::Delete,
+                "Insert" => KeyCode::Insert,
+                "Up" => KeyCode::Up,
+                "Down" => KeyCode::Down,
+                "Left" => KeyCode::Left,
+                "Right" => KeyCode::Right,
+                "Home" => KeyCode::Home,
+                "End" => KeyCode::End,
+                "PageUp" => KeyCode::PageUp,
+                "PageDown" => KeyCode::PageDown,
+                _ => match name.strip_prefix('F').and_then(|number| number.parse().ok()) {
+                    Some(number @ 1..=12) => KeyCode::F(number),
+                    _ => return Err(format!("unknown key \"{}\"", text)),
+                },
+            },
+        };
+        Ok(Key { code, ctrl })
+    }
+
+    fn matches(&self, key_event: KeyEvent) -> bool {
+        self.code == key_event.code && self.ctrl == key_event.modifiers.contains(KeyModifiers::CONTROL)
+    }
+
+    /// Whether the key types a character into text fields.
+    fn types(&self) -> bool {
+        matches!(self.code, KeyCode::Char(_)) && !self.ctrl
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::Enter => write!(f, "Enter"),
+            KeyCode::Esc => write!(f, "Esc"),
+            KeyCode::Tab => write!(f, "Tab"),
+            KeyCode::BackTab => write!(f, "BackTab"),
+            KeyCode::Backspace => write!(f, "Backspace"),
+            KeyCode::Delete => write!(f, "Delete"),
+            KeyCode::Insert => write!(f, "Insert"),
+            KeyCode::Up => write!(f, "Up"),
+            KeyCode::Down => write!(f, "Down"),
+            KeyCode::Left => write!(f, "Left"),
+            KeyCode::Right => write!(f, "Right"),
+            KeyCode::Home => write!(f, "Home"),
+            KeyCode::End => write!(f, "End"),
+            KeyCode::PageUp => write!(f, "PageUp"),
+            KeyCode::PageDown => write!(f, "PageDown"),
+            KeyCode::F(number) => write!(f, "F{}", number),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+/// One key or a list of them for an action in the config file.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum KeyList {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl KeyList {
+    fn keys(&self) -> Vec<&str> {
+        match self {
+            KeyList::One(key) => vec![key.as_str()],
+            KeyList::Many(keys) => keys.iter().map(|key| key.as_str()).collect(),
+        }
+    }
+}
+
+/// The keys bound to each action, driving both event handling and instructions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Key>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new("vim", &BTreeMap::new()).unwrap_or(Keymap { bindings: BTreeMap::new() })
+    }
+}
+
+impl Keymap {
+    pub const PRESETS: [&str; 2] = ["vim", "arrows"];
+
+    /// Starts from `preset` and rebinds the actions named in `overrides`,
+    /// failing on unknown names or keys and on keys clashing within a screen.
+    pub fn new(preset: &str, overrides: &BTreeMap<String, KeyList>) -> Result<Keymap, String> {
+        if !Keymap::PRESETS.contains(&preset) {
+            return Err(format!("unknown keys preset \"{}\", expected one of {}", preset, Keymap::PRESETS.join(", ")));
+        }
+        let mut bindings = BTreeMap::new();
+        for action in Action::ALL {
+            let keys = action.preset_keys(preset == "arrows")
+                .iter()
+                .map(|key| Key::parse(key))
+                .collect::<Result<Vec<Key>, String>>()?;
+            bindings.insert(action, keys);
+        }
+        for (name, keys) in overrides {
+            let action = Action::ALL.into_iter()
+                .find(|action| action.name() == name)
+                .ok_or_else(|| format!("unknown action \"{}\" in keys.bindings", name))?;
+            let keys = keys.keys()
+                .into_iter()
+                .map(Key::parse)
+                .collect::<Result<Vec<Key>, String>>()
+                .map_err(|error| format!("keys.bindings.{}: {}", name, error))?;
+            if keys.is_empty() {
+                return Err(format!("keys.bindings.{} needs at least one key", name));
+            }
+            bindings.insert(action, keys);
+        }
+        let keymap = Keymap { bindings };
+        keymap.check_clashes()?;
+        Ok(keymap)
+    }
+
+    fn check_clashes(&self) -> Result<(), String> {
+        for (name, context) in CONTEXTS {
+            for (index, (action, _)) in context.iter().enumerate() {
+                for (other, _) in &context[index + 1..] {
+                    if let Some(key) = self.keys(*action).iter().find(|key| self.keys(*other).contains(key)) {
+                        return Err(format!("{} is bound to both {} and {} in {}", key, action.name(), other.name(), name));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn keys(&self, action: Action) -> &[Key] {
+        self.bindings.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    /// The action of `context` bound to the pressed key.
+    pub fn action(&self, key_event: KeyEvent, context: &[(Action, &str)]) -> Option<Action> {
+        context.iter()
+            .map(|(action, _)| *action)
+            .find(|action| self.keys(*action).iter().any(|key| key.matches(key_event)))
+    }
+
+    /// Like `action`, for fields being typed into: keys typing a character
+    /// are left to the field.
+    pub fn text_action(&self, key_event: KeyEvent, context: &[(Action, &str)]) -> Option<Action> {
+        context.iter()
+            .map(|(action, _)| *action)
+            .find(|action| self.keys(*action).iter().any(|key| !key.types() && key.matches(key_event)))
+    }
+
+    /// The first key of `action` as instructions show it, like `<u>`, for messages.
+    pub fn key(&self, action: Action) -> String {
+        self.keys(action).first().map_or(String::new(), |key| format!("<{}>", key))
+    }
+
+    /// The key shown for `action`, the first one that doesn't type when `typing`.
+    fn shown_key(&self, action: Action, typing: bool) -> Option<&Key> {
+        self.keys(action).iter().find(|key| !typing || !key.types())
+    }
+
+    /// The instruction line for `context`, fitted in `width` columns: when
+    /// the hints don't all fit, the last ones make room for the help hint.
+    pub fn hints(&self, theme: &Theme, context: &[(Action, &str)], typing: bool, width: u16) -> Line<'static> {
+        let hint = |action: Action, label: &str| -> Option<Vec<Span<'static>>> {
+            let key = self.shown_key(action, typing)?;
+            Some(vec![Span::styled(format!(" {} ", label), theme.text()), Span::styled(format!("<{}> ", key), theme.key())])
+        };
+        let hints: Vec<(Action, Vec<Span<'static>>)> = context.iter()
+            .filter(|(_, label)| !label.is_empty())
+            .filter_map(|(action, label)| Some((*action, hint(*action, label)?)))
+            .collect();
+        let hint_width = |spans: &Vec<Span>| spans.iter().map(Span::width).sum::<usize>();
+        let width = width as usize;
+        if hints.iter().map(|(_, spans)| hint_width(spans)).sum::<usize>() <= width {
+            return Line::from(hints.into_iter().flat_map(|(_, spans)| spans).collect::<Vec<_>>());
+        }
+        let help = hints.iter().find(|(action, _)| *action == Action::Help).map(|(_, spans)| spans.clone());
+        let mut used = help.as_ref().map_or(0, hint_width);
+        let mut spans = vec![];
+        for (action, hint) in hints {
+            if action == Action::Help {
+                continue;
+            }
+            used += hint_width(&hint);
+            if used > width {
+                break;
+            }
+            spans.extend(hint);
+        }
+        spans.extend(help.unwrap_or_default());
+        Line::from(spans)
+    }
+
+    /// A popup listing every action of `context` with all its keys.
+    pub fn render_help(&self, theme: &Theme, context: &[(Action, &str)], area: Rect, buf: &mut Buffer) {
+        let rows: Vec<Row> = context.iter()
+            .filter(|(action, label)| !label.is_empty() && *action != Action::Help)
+            .map(|(action, label)| Row::new(vec![
+                Cell::from(label.to_string()),
+                Cell::from(self.keys(*action).iter()
+                    .map(|key| format!("<{}>", key))
+                    .collect::<Vec<_>>()
+                    .join(" ")).style(theme.key()),
+            ]))
+            .collect();
+        let block = Block::bordered()
+            .title(Line::from(" Keys ".bold()).centered())
+            .title_bottom(Line::from(vec![Span::styled(" Close ", theme.text()), Span::styled("<any key> ", theme.key())]).centered())
+            .border_set(border::THICK)
+            .border_style(theme.popup());
+        let popup = popup_area(area, 50, rows.len() as u16 + 2);
+        Clear.render(popup, buf);
+        Table::new(rows, [Constraint::Percentage(50), Constraint::Percentage(50)])
+            .column_spacing(1)
+            .block(block)
+            .render(popup, buf);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    fn bindings(bindings: &[(&str, KeyList)]) -> BTreeMap<String, KeyList> {
+        bindings.iter().map(|(name, keys)| (name.to_string(), keys.clone())).collect()
+    }
+
+    #[test]
+    fn keys_parse_from_their_names() {
+        for (text, code, ctrl) in [
+            ("k", KeyCode::Char('k'), false),
+            ("K", KeyCode::Char('K'), false),
+            ("?", KeyCode::Char('?'), false),
+            ("Space", KeyCode::Char(' '), false),
+            ("Enter", KeyCode::Enter, false),
+            ("BackTab", KeyCode::BackTab, false),
+            ("PageDown", KeyCode::PageDown, false),
+            ("F1", KeyCode::F(1), false),
+            ("F12", KeyCode::F(12), false),
+            ("Ctrl+r", KeyCode::Char('r'), true),
+            ("Ctrl+C", KeyCode::Char('c'), true),
+            ("C-x", KeyCode::Char('x'), true),
+            ("Ctrl+Up", KeyCode::Up, true),
+        ] {
+            assert_eq!(Key::parse(text), Ok(Key { code, ctrl }), "{}", text);
+        }
+    }
+
+    #[test]
+    fn unknown_keys_are_refused() {
+        for text in ["", "kj", "Return", "F0", "F13", "Fx", "Ctrl+", "ctrl+c"] {
+            assert_eq!(Key::parse(text), Err(format!("unknown key \"{}\"", text)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn keys_display_as_they_are_written() {
+        for text in ["k", "Space", "Enter", "F5", "Ctrl+r", "Ctrl+Up"] {
+            assert_eq!(Key::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(Key::parse("C-R").unwrap().to_string(), "Ctrl+r");
+    }
+
+    #[test]
+    fn ctrl_keys_match_what_terminals_send() {
+        let quit = Key::parse("Ctrl+C").unwrap();
+        assert!(quit.matches(press(KeyCode::Char('c'), KeyModifiers::CONTROL)));
+        assert!(!quit.matches(press(KeyCode::Char('c'), KeyModifiers::NONE)));
+        let shifted = Key::parse("K").unwrap();
+        assert!(shifted.matches(press(KeyCode::Char('K'), KeyModifiers::SHIFT)));
+        assert!(!shifted.matches(press(KeyCode::Char('k'), KeyModifiers::NONE)));
+    }
+
+    #[test]
+    fn presets_differ_in_their_movement_keys() {
+        let vim = Keymap::new("vim", &BTreeMap::new()).unwrap();
+        let arrows = Keymap::new("arrows", &BTreeMap::new()).unwrap();
+        assert_eq!(vim, Keymap::default());
+        assert_eq!(vim.action(press(KeyCode::Char('j'), KeyModifiers::NONE), PRINTERS), Some(Action::Down));
+        assert_eq!(arrows.action(press(KeyCode::Char('j'), KeyModifiers::NONE), PRINTERS), None);
+        assert_eq!(arrows.action(press(KeyCode::Down, KeyModifiers::NONE), PRINTERS), Some(Action::Down));
+        assert_eq!(vim.key(Action::Edit), "<e>");
+        assert_eq!(arrows.key(Action::Edit), "<Enter>");
+    }
+
+    #[test]
+    fn overrides_replace_the_preset_keys() {
+        let keymap = Keymap::new("vim", &bindings(&[
+            ("quit", KeyList::Many(vec!["q".to_string(), "Ctrl+C".to_string()])),
+            ("add", KeyList::One("+".to_string())),
+        ])).unwrap();
+        assert_eq!(keymap.action(press(KeyCode::Char('c'), KeyModifiers::CONTROL), PRINTERS), Some(Action::Quit));
+        assert_eq!(keymap.action(press(KeyCode::Char('+'), KeyModifiers::NONE), PRINTERS), Some(Action::Add));
+        assert_eq!(keymap.action(press(KeyCode::Char('a'), KeyModifiers::NONE), PRINTERS), None);
+        assert_eq!(keymap.key(Action::Add), "<+>");
+    }
+
+    #[test]
+    fn typing_keys_are_left_to_text_fields() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.text_action(press(KeyCode::Char('j'), KeyModifiers::NONE), FORM_TEXT), None);
+        assert_eq!(keymap.text_action(press(KeyCode::Esc, KeyModifiers::NONE), FORM_TEXT), Some(Action::Back));
+    }
+
+    #[test]
+    fn bad_overrides_are_refused() {
+        for (overrides, error) in [
+            (bindings(&[("fly", KeyList::One("f".to_string()))]), "unknown action \"fly\" in keys.bindings"),
+            (bindings(&[("add", KeyList::One("Plus".to_string()))]), "keys.bindings.add: unknown key \"Plus\""),
+            (bindings(&[("add", KeyList::Many(vec![]))]), "keys.bindings.add needs at least one key"),
+            (bindings(&[("add", KeyList::One("d".to_string()))]), "d is bound to both add and remove in the printers list"),
+            (bindings(&[("cancel_job", KeyList::One("Ctrl+X".to_string())), ("hold_job", KeyList::One("C-x".to_string()))]),
+                "Ctrl+x is bound to both cancel_job and hold_job in the jobs list"),
+        ] {
+            assert_eq!(Keymap::new("vim", &overrides), Err(error.to_string()));
+        }
+    }
+
+    #[test]
+    fn keys_may_repeat_across_screens() {
+        // x already toggles enabled in the printers list, which has no rescan.
+        let keymap = Keymap::new("vim", &bindings(&[("rescan", KeyList::One("x".to_string()))])).unwrap();
+        assert_eq!(keymap.action(press(KeyCode::Char('x'), KeyModifiers::NONE), DEVICES), Some(Action::Rescan));
+        assert_eq!(keymap.action(press(KeyCode::Char('x'), KeyModifiers::NONE), PRINTERS), Some(Action::ToggleEnabled));
+    }
+}
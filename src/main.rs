@@ -1,4 +1,5 @@
 use std::io;
+use std::time::Duration;
 use crossterm::event::{self,Event,KeyEvent,KeyEventKind};
 use ratatui::{
     buffer::Buffer,
@@ -7,30 +8,72 @@ use ratatui::{
     DefaultTerminal,Frame,
 };
 use ratatui::prelude::*;
+mod classes;
+mod cli;
+mod confirm;
+mod config;
+mod file_browser;
+mod history;
+mod jobs;
+mod keymap;
 mod new_printer;
 mod printers;
 mod cups;
+mod discovery;
 mod utils;
 mod test_utils;
-use new_printer::NewPrinter;
+mod theme;
+mod uri_builder;
+use classes::{Classes, MemberPicker};
+use confirm::{Confirm, ConfirmAction};
+use config::Config;
+use discovery::Discovery;
+use file_browser::FileBrowser;
+use history::{History, HistoryFilter};
+use jobs::Jobs;
+use keymap::Action;
+use new_printer::{DriverMatch, NewPrinter};
 use printers::Printers;
+use uri_builder::UriBuilder;
 
-use cups::device::Device;
-use cups::printer::{
-    Printer,
-    get_all_printers,
-};
+use cups::backend::Backend;
+use cups::error::CupsError;
+use cups::device::{Device, DiscoveryOptions};
+use cups::printer::Printer;
 use cups::driver::Driver;
+use cups::job::Job;
+use cups::options::OptionChoices;
 
 use utils::{
     TUIMode,
     EditBlock,
     EditMode,
+    Status,
+    ViewBlock,
+    ClassBlock,
+    ReasonAction,
+    FilterField,
 };
 
 fn main() -> io::Result<()> {
+    if let Some(status) = cli::run(std::env::args().skip(1), Config::load) {
+        std::process::exit(status);
+    }
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(error) => {
+            eprintln!("printer-tui: {}", error);
+            std::process::exit(cli::EXIT_FAILURE);
+        }
+    };
     let mut terminal = ratatui::init();
-    let app_result = App::default().run(&mut terminal);
+    let mut app = App{
+        backend: cups::backend(config.cups.backend, &config.cups.server),
+        discovery_options: config.discovery.clone().with_env(),
+        config,
+        ..Default::default()
+    };
+    let app_result = app.run(&mut terminal);
     ratatui::restore();
     app_result
 }
@@ -38,6 +81,8 @@ fn main() -> io::Result<()> {
 #[derive(Debug, Default)]
 pub struct App {
     exit:bool,
+    config: Config,
+    backend: Backend,
     printers: Vec<Printer>,
     devices: Vec<Device>,
     drivers: Vec<Driver>,
@@ -47,12 +92,60 @@ pub struct App {
     selected_edit_block: EditBlock,
     selected_edit_mode: EditMode,
     selected_printer_name: String,
+    printer_description: String,
+    printer_location: String,
+    /// The printer the form is editing as it was before, `None` when adding one.
+    editing_printer: Option<Printer>,
+    /// Whether writing an edited printer leaves its driver alone.
+    keep_driver: bool,
+    changes_prompt: bool,
     mode: TUIMode,
+    status: Option<Status>,
+    selected_view_block: ViewBlock,
+    selected_option: usize,
+    option_choices: Vec<OptionChoices>,
+    selected_choice: usize,
+    option_value: String,
+    reason_prompt: Option<ReasonAction>,
+    reason: String,
+    jobs: Vec<Job>,
+    selected_job: usize,
+    jobs_all_printers: bool,
+    move_target: Option<usize>,
+    history: Vec<Job>,
+    selected_history: usize,
+    history_filter: HistoryFilter,
+    filter_field: Option<FilterField>,
+    default_printer: Option<String>,
+    default_prompt: bool,
+    file_browser: Option<FileBrowser>,
+    driver_search: String,
+    driver_searching: bool,
+    recommended_drivers: Vec<String>,
+    /// The device drivers were last suggested for.
+    suggested_device: Option<Device>,
+    /// The drivers the search matches, in the order the Drivers block lists them.
+    driver_matches: Vec<DriverMatch>,
+    discovery: Option<Discovery>,
+    discovery_options: DiscoveryOptions,
+    uri_builder: Option<UriBuilder>,
+    confirm: Option<Confirm>,
+    /// Whether the keys of the screen in front are listed over it.
+    help: bool,
+    /// Index among the classes of `printers`.
+    selected_class: usize,
+    selected_member: usize,
+    selected_class_block: ClassBlock,
+    member_picker: Option<MemberPicker>,
+    /// Printers removed this session, most recent last, to set up again on undo.
+    removed_printers: Vec<Printer>,
+    /// Redraws so far, driving the spinners.
+    tick: usize,
 }
 
 impl App{
     pub fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
-        self.printers = get_all_printers();
+        self.refresh_printers();
         self.selected_printer= 0;
         self.selected_printer_name = self.printers.get(self.selected_printer)
             .map_or("No Printer".to_string(), |p| p.name.clone());
@@ -71,20 +164,56 @@ impl App{
         frame.render_widget(self,frame.area());
     }
 
+    /// Handles a key if one comes within a spinner frame, then picks up what discovery found meanwhile.
     fn handle_events(&mut self) -> io::Result<()> {
-        match event::read()? {
-            Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
-               self.handle_key_event(key_event)
+        if event::poll(Duration::from_millis(100))? {
+            match event::read()? {
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Press => {
+                   self.handle_key_event(key_event)
+                }
+                _ => {}
             }
-            _ => {}
         }
+        self.tick = self.tick.wrapping_add(1);
+        NewPrinter::poll_discovery(self);
         Ok(())
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) {
+        self.status = None;
+        if self.help {
+            self.help = false;
+            return;
+        }
+        if self.confirm.is_some() {
+            self.handle_confirm(key_event);
+            return;
+        }
         match self.mode {
             TUIMode::View => Printers::handle_events(self,key_event),
             TUIMode::Edit => NewPrinter::handle_events(self,key_event),
+            TUIMode::Jobs => Jobs::handle_events(self,key_event),
+            TUIMode::History => History::handle_events(self,key_event),
+            TUIMode::Classes => Classes::handle_events(self,key_event),
+        }
+    }
+
+    fn handle_confirm(&mut self, key_event: KeyEvent) {
+        match self.config.keymap.action(key_event, keymap::CONFIRM) {
+            Some(Action::Yes | Action::Select) => {
+                if let Some(confirm) = self.confirm.take() {
+                    self.run_confirmed(confirm.action);
+                }
+            }
+            Some(Action::No | Action::Back) => self.confirm = None,
+            _ => {}
+        }
+    }
+
+    fn run_confirmed(&mut self, action: ConfirmAction) {
+        match action {
+            ConfirmAction::RemovePrinter(name) => Printers::remove_printer(self, &name),
+            ConfirmAction::RemoveClass(name) => Classes::remove_class(self, &name),
         }
     }
 
@@ -95,42 +224,174 @@ impl App{
     fn change_mode(&mut self, mode: TUIMode) {
        self.mode = mode;
     }
+
+    /// Shows the error of a failed CUPS call in the status line.
+    fn report<T>(&mut self, result: Result<T, CupsError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.status = Some(Status::Error(error.to_string()));
+                None
+            }
+        }
+    }
+
+    fn refresh_printers(&mut self) {
+        let printers = self.backend.get_all_printers();
+        self.printers = self.report(printers).unwrap_or_default();
+        let default_printer = self.backend.get_default_printer();
+        self.default_printer = self.report(default_printer).flatten();
+    }
 }
 
 impl Widget for &App {
 
     fn render(self,area: Rect, buf: &mut Buffer) {
-        let mut new_printer= NewPrinter::new(
-            self.selected_edit_block,
-            self.selected_edit_mode,
-            &self.devices,
-            &self.drivers,
-            Some(self.selected_device), 
-            Some(self.selected_driver),
-            &self.selected_printer_name,
-        );
-
-        let mut printers = Printers::new(
-            &self.printers,
-            self.selected_printer, 
-        );
-        
         let outer_layout = Layout::default()
              .direction(Direction::Vertical)
              .constraints(vec![
-                 Constraint::Percentage(100),
+                 Constraint::Min(0),
+                 Constraint::Length(if self.status.is_some() {1} else {0}),
              ])
              .split(area);
-        
+
+        if let Some(status) = &self.status {
+            let status_line = match status {
+                Status::Info(message) => Line::from(Span::styled(format!(" {} ", message), self.config.palette.text())),
+                Status::Error(message) => Line::from(Span::styled(format!(" Error: {} ", message), self.config.palette.error()).bold()),
+            };
+            status_line.render(outer_layout[1], buf);
+        }
 
         match self.mode {
             TUIMode::View => {
-                printers.render(outer_layout[0], buf);
+                Printers::new(self).render(outer_layout[0], buf);
             }
             TUIMode::Edit => {
-                new_printer.render(outer_layout[0], buf);
+                NewPrinter::new(self).render(outer_layout[0], buf);
+            }
+            TUIMode::Jobs => {
+                Jobs::new(self).render(outer_layout[0], buf);
+            }
+            TUIMode::History => {
+                History::new(self).render(outer_layout[0], buf);
+            }
+            TUIMode::Classes => {
+                Classes::new(self).render(outer_layout[0], buf);
+            }
+        }
+
+        if let Some(confirm) = &self.confirm {
+            confirm.render(&self.config.keymap, &self.config.palette, outer_layout[0], buf);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyCode;
+    use cups::fake::FakeBackend;
+    use cups::printer::PrinterState;
+
+    fn sample_app() -> App {
+        let mut app = App{
+            backend: Backend::new(FakeBackend::sample()),
+            ..Default::default()
+        };
+        app.refresh_printers();
+        app
+    }
+
+    fn press(app: &mut App, keys: &[KeyCode]) {
+        for key in keys {
+            app.handle_key_event((*key).into());
+        }
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            app.handle_key_event(KeyCode::Char(c).into());
+        }
+    }
+
+    /// Polls discovery like the event loop does until devices, drivers and
+    /// their ranking are all in.
+    fn wait_for_discovery(app: &mut App) {
+        for _ in 0..500 {
+            NewPrinter::poll_discovery(app);
+            if app.discovery.is_none() {
+                return;
             }
+            std::thread::sleep(Duration::from_millis(10));
         }
+        panic!("discovery never finished");
+    }
+
+    fn printer<'a>(app: &'a App, name: &str) -> &'a Printer {
+        app.printers.iter().find(|printer| printer.name == name).unwrap()
+    }
+
+    #[test]
+    fn add_remove_and_undo_a_printer() {
+        let mut app = sample_app();
+
+        press(&mut app, &[KeyCode::Char('a')]);
+        assert_eq!(app.mode, TUIMode::Edit);
+        press(&mut app, &[KeyCode::Char('e')]);
+        press(&mut app, &[KeyCode::Backspace; "New_Printer".len()]);
+        type_text(&mut app, "Hall");
+        press(&mut app, &[KeyCode::Enter]);
+        wait_for_discovery(&mut app);
+        assert_eq!(app.devices.len(), 4);
+        press(&mut app, &[KeyCode::Char('w')]);
+        assert_eq!(app.status, Some(Status::Info("Printer Hall added".to_string())));
+        assert_eq!(app.mode, TUIMode::View);
+        let hall = printer(&app, "Hall");
+        assert_eq!(hall.device_uri, "ipp://192.168.1.20/ipp/print");
+        assert_eq!(hall.make_and_model, "Brother HL-L2350DW series, driverless, cups-filters 2.0.0");
+
+        app.selected_printer = app.printers.iter().position(|printer| printer.name == "Hall").unwrap();
+        press(&mut app, &[KeyCode::Char('d')]);
+        assert!(app.confirm.is_some());
+        press(&mut app, &[KeyCode::Char('y')]);
+        assert_eq!(app.status, Some(Status::Info("Printer Hall removed, undo with <u>".to_string())));
+        assert!(app.printers.iter().all(|printer| printer.name != "Hall"));
+
+        press(&mut app, &[KeyCode::Char('u')]);
+        assert_eq!(app.status, Some(Status::Info("Printer Hall restored".to_string())));
+        assert_eq!(app.printers[app.selected_printer].name, "Hall");
+        assert_eq!(printer(&app, "Hall").make_and_model, "Brother HL-L2350DW series, driverless, cups-filters 2.0.0");
+    }
+
+    #[test]
+    fn undo_puts_back_a_stopped_and_rejecting_printer() {
+        let mut app = sample_app();
+        let lab = printer(&app, "Lab").clone();
+        app.selected_printer = app.printers.iter().position(|printer| printer.name == "Lab").unwrap();
+
+        press(&mut app, &[KeyCode::Char('d'), KeyCode::Enter]);
+        assert!(app.printers.iter().all(|printer| printer.name != "Lab"));
+        press(&mut app, &[KeyCode::Char('u')]);
+        assert_eq!(app.status, Some(Status::Info("Printer Lab restored".to_string())));
+
+        let restored = printer(&app, "Lab");
+        assert_eq!(restored.device_uri, lab.device_uri);
+        assert_eq!(restored.make_and_model, lab.make_and_model);
+        assert_eq!(restored.location, lab.location);
+        assert_eq!(restored.state, PrinterState::Stopped);
+        assert_eq!(restored.state_message, "Paused for maintenance");
+        assert!(!restored.accepting);
+        assert_eq!(restored.options.get("sides"), lab.options.get("sides"));
+    }
+
+    #[test]
+    fn undo_without_removals_does_nothing() {
+        let mut app = sample_app();
+        press(&mut app, &[KeyCode::Char('u')]);
+        assert_eq!(app.status, None);
+        let names: Vec<&str> = app.printers.iter().map(|printer| printer.name.as_str()).collect();
+        assert_eq!(names, ["Office", "Lab", "Lasers"]);
     }
 }
 //
@@ -1,31 +1,39 @@
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Style,Stylize,Color},
+    style::{Style,Stylize},
     symbols::border,
     text::Line,
-    widgets::{Cell,Block,StatefulWidget,Row,Table,TableState,Paragraph,Borders},
+    widgets::{Cell,Block,Clear,StatefulWidget,Row,Table,TableState,Paragraph,Borders},
 };
 use ratatui::prelude::*;
 use ratatui::layout::Constraint;
 
 use crate::cups::{
-    printer::{
-        get_all_printers,
-        create_printer,
-    },
     device::Device,
-    driver::Driver,
+    driver::{self, Driver},
+    error::CupsError,
+    printer::{self, Printer},
+    uri,
 };
 
 use crate::utils::{
     TUIMode,
     EditBlock,
     EditMode,
+    Status,
+    fuzzy_match,
+    spinner,
+    popup_area,
+    edit_text,
 };
-use crossterm::event::{KeyCode,KeyEvent};
+use crossterm::event::KeyEvent;
 
 use crate::App;
+use crate::discovery::{Discovery, DiscoveryEvent};
+use crate::keymap::{self, Action, Context, Keymap};
+use crate::theme::Theme;
+use crate::uri_builder::UriBuilder;
 
 #[derive(Debug)]
 pub struct NewPrinter<'a> {
@@ -34,26 +42,217 @@ pub struct NewPrinter<'a> {
     pub selected_block: EditBlock,
     pub selected_edit_mode: EditMode,
     pub printer_name: String,
+    /// Why the name can't be used, checked as it is typed.
+    pub name_error: Option<String>,
+    pub description: &'a str,
+    pub location: &'a str,
     pub devices:&'a Vec<Device>,
     pub drivers: &'a Vec<Driver>,
+    pub driver_search: &'a str,
+    pub driver_searching: bool,
+    pub driver_matches: &'a [DriverMatch],
+    pub scanning_devices: bool,
+    pub loading_drivers: bool,
+    pub spinner: &'static str,
+    pub uri_builder: Option<&'a UriBuilder>,
+    pub editing_printer: Option<&'a Printer>,
+    pub keep_driver: bool,
+    /// What writing the edited printer would change, while asking to confirm.
+    pub changes: Option<Vec<Change>>,
+    pub keymap: &'a Keymap,
+    pub theme: &'a Theme,
+    pub help: bool,
+}
+
+/// A field of an edited printer that writing it would change.
+#[derive(Debug)]
+pub struct Change {
+    pub field: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+/// A row of the Drivers block.
+#[derive(Debug)]
+pub struct DriverMatch {
+    /// Index into `App::drivers`.
+    pub index: usize,
+    /// Chars of `Driver::search_text` the search matched.
+    pub positions: Vec<usize>,
+    pub recommended: bool,
+}
+
+/// `text` with the chars at `positions` (shifted by `offset`) in `style`.
+fn highlight(text: &str, positions: &[usize], offset: usize, style: Style) -> Line<'static> {
+    Line::from(text
+        .chars()
+        .enumerate()
+        .map(|(position, c)| if positions.contains(&(position + offset)) {
+            Span::styled(c.to_string(), style)
+        } else {
+            Span::raw(c.to_string())
+        })
+        .collect::<Vec<Span>>())
 }
 
 impl<'a> NewPrinter<'a> {
-    pub fn new( selected_block:EditBlock,selected_edit_mode:EditMode,devices: &'a Vec<Device>,drivers: &'a Vec<Driver>,selected_device:Option<usize>,selected_driver:Option<usize>,selected_printer_name:&'a String) -> Self {
+    pub fn new(app: &'a App) -> Self {
         NewPrinter {
             device_state: TableState::default()
-                .with_selected(selected_device),
+                .with_selected(app.selected_device),
             driver_state: TableState::default()
-                .with_selected(selected_driver),
-            selected_block,
-            selected_edit_mode,
-            printer_name: selected_printer_name.clone(),
-            devices,
-            drivers,
+                .with_selected(app.selected_driver),
+            selected_block: app.selected_edit_block,
+            selected_edit_mode: app.selected_edit_mode,
+            printer_name: app.selected_printer_name.to_string(),
+            name_error: NewPrinter::validate_name(app).err().map(|error| error.to_string()),
+            description: &app.printer_description,
+            location: &app.printer_location,
+            devices: &app.devices,
+            drivers: &app.drivers,
+            driver_search: &app.driver_search,
+            driver_searching: app.driver_searching,
+            driver_matches: &app.driver_matches,
+            scanning_devices: app.discovery.as_ref().is_some_and(|discovery| discovery.scanning_devices),
+            loading_drivers: NewPrinter::loading_drivers(app),
+            spinner: spinner(app.tick),
+            uri_builder: app.uri_builder.as_ref(),
+            editing_printer: app.editing_printer.as_ref(),
+            keep_driver: app.keep_driver,
+            changes: if app.changes_prompt { Some(NewPrinter::changes(app)) } else { None },
+            keymap: &app.config.keymap,
+            theme: &app.config.palette,
+            help: app.help,
+        }
+    }
+
+    /// Matches the drivers against the search again, after the search, the
+    /// driver list or the recommendations changed.
+    pub fn refresh_driver_matches(app:&mut App) {
+        app.driver_matches = NewPrinter::driver_matches(app);
+    }
+
+    /// The drivers the search fuzzy matches, recommended ones first in their
+    /// rank order, then the others best match first.
+    fn driver_matches(app: &App) -> Vec<DriverMatch> {
+        let mut recommended: Vec<(usize, DriverMatch)> = vec![];
+        let mut others: Vec<(i64, DriverMatch)> = vec![];
+        for (index, driver) in app.drivers.iter().enumerate() {
+            let Some((score, positions)) = fuzzy_match(&app.driver_search, &driver.search_text()) else {
+                continue;
+            };
+            match app.recommended_drivers.iter().position(|ppd_name| *ppd_name == driver.ppd_name) {
+                Some(rank) => recommended.push((rank, DriverMatch { index, positions, recommended: true })),
+                None => others.push((score, DriverMatch { index, positions, recommended: false })),
+            }
+        }
+        recommended.sort_by_key(|(rank, _)| *rank);
+        others.sort_by_key(|(score, _)| -score);
+        recommended.into_iter().map(|(_, m)| m)
+            .chain(others.into_iter().map(|(_, m)| m))
+            .collect()
+    }
+
+    /// Checks the name of a printer being added against CUPS rules and the
+    /// existing queues. Edited printers keep theirs, so it always passes.
+    fn validate_name(app: &App) -> Result<(), CupsError> {
+        if app.editing_printer.is_some() {
+            return Ok(());
+        }
+        printer::validate_name(&app.selected_printer_name, app.printers.iter().map(|printer| printer.name.as_str()))
+    }
+
+    /// The driver `selected_driver` points at through the search results.
+    /// Whether the driver list or the drivers matching a device are still on their way.
+    fn loading_drivers(app: &App) -> bool {
+        app.discovery.as_ref().is_some_and(|discovery| discovery.loading_drivers || discovery.matching_drivers > 0)
+    }
+
+    fn selected_driver(app: &App) -> Option<&Driver> {
+        app.driver_matches
+            .get(app.selected_driver)
+            .and_then(|m| app.drivers.get(m.index))
+    }
+
+    /// Adds what the discovery workers found since the last frame, suggesting
+    /// drivers once everything is in unless the user is already picking one.
+    pub fn poll_discovery(app:&mut App) {
+        let Some(discovery) = app.discovery.as_mut() else {
+            return;
+        };
+        let searching = !discovery.has_found();
+        let events = discovery.poll();
+        let found = searching && discovery.has_found();
+        for event in events {
+            match event {
+                DiscoveryEvent::Device(device) => app.devices.push(device),
+                DiscoveryEvent::DevicesDone(result) => {
+                    app.report(result);
+                }
+                DiscoveryEvent::Drivers(result) => {
+                    app.drivers = app.report(result).unwrap_or_default();
+                    NewPrinter::refresh_driver_matches(app);
+                }
+                DiscoveryEvent::MatchingDrivers(device, result) => NewPrinter::recommend_drivers(app, &device, result),
+            }
+        }
+        if found && !(app.selected_edit_block == EditBlock::Drivers && app.selected_edit_mode == EditMode::Edit) {
+            NewPrinter::suggest_drivers(app);
+        }
+        if app.discovery.as_ref().is_some_and(Discovery::is_done) {
+            app.discovery = None;
+        }
+    }
+
+    /// Probes for devices again, loading drivers too if they failed to load.
+    fn rescan(app:&mut App) {
+        app.devices.clear();
+        app.selected_device = 0;
+        app.discovery = Some(Discovery::start(&app.backend, &app.discovery_options, app.drivers.is_empty()));
+    }
+
+    fn cancel_discovery(app:&mut App) {
+        if app.discovery.take().is_some() {
+            app.status = Some(Status::Info(format!("Discovery canceled, {} devices found", app.devices.len())));
+        }
+    }
+
+    /// Looks up the drivers made for the selected device in the background,
+    /// `recommend_drivers` ranking them once they are in.
+    pub fn suggest_drivers(app:&mut App) {
+        app.recommended_drivers.clear();
+        app.selected_driver = 0;
+        NewPrinter::refresh_driver_matches(app);
+        app.suggested_device = app.devices.get(app.selected_device).cloned();
+        let Some(device) = app.suggested_device.clone() else {
+            return;
+        };
+        app.discovery.get_or_insert_with(Discovery::idle).match_drivers(&app.backend, device);
+    }
+
+    /// Ranks the drivers `matching` the device and pre-selects the best one,
+    /// unless another device got selected in the meantime.
+    fn recommend_drivers(app:&mut App, device: &Device, matching: Result<Vec<Driver>, CupsError>) {
+        if app.devices.get(app.selected_device) != Some(device) {
+            return;
+        }
+        let matching = app.report(matching).unwrap_or_default();
+        app.recommended_drivers = driver::recommend(device, &matching, &app.drivers, app.config.drivers.prefer);
+        app.selected_driver = 0;
+        NewPrinter::refresh_driver_matches(app);
+        if app.keep_driver && let Some(printer) = &app.editing_printer {
+            app.selected_driver = app.driver_matches
+                .iter()
+                .position(|m| app.drivers[m.index].make_and_model == printer.make_and_model)
+                .unwrap_or(0);
         }
     }
 
     pub fn handle_events(app:&mut App,key_event: KeyEvent) {
+        if app.changes_prompt {
+            NewPrinter::handle_changes_prompt(app,key_event);
+            return;
+        }
         match app.selected_edit_mode {
             EditMode::View => NewPrinter::handle_view_mode(app,key_event),
             EditMode::Edit => NewPrinter::handle_edit_mode(app,key_event),
@@ -61,20 +260,24 @@ impl<'a> NewPrinter<'a> {
     }
 
     fn handle_view_mode(app:&mut App, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('k') => NewPrinter::previous_block(app),
-            KeyCode::Char('j') => NewPrinter::next_block(app),
-            KeyCode::Char('e') => app.selected_edit_mode = EditMode::Edit,
-            KeyCode::Char('w') => {
-                NewPrinter::write(app);
-                app.printers = get_all_printers();
-                app.change_mode(TUIMode::View);
+        match app.config.keymap.action(key_event, keymap::FORM) {
+            Some(Action::Up | Action::PreviousField) => NewPrinter::previous_block(app),
+            Some(Action::Down | Action::NextField) => NewPrinter::next_block(app),
+            Some(Action::Edit) if app.editing_printer.is_some() && app.selected_edit_block == EditBlock::Title => {
+                app.status = Some(Status::Info("Printers can't be renamed, add a new one instead".to_string()));
             }
-            KeyCode::Esc => {
-                app.printers = get_all_printers();
+            Some(Action::Edit) => app.selected_edit_mode = EditMode::Edit,
+            Some(Action::Write) => NewPrinter::write(app),
+            Some(Action::Rescan) => NewPrinter::rescan(app),
+            Some(Action::StopScan) => NewPrinter::cancel_discovery(app),
+            Some(Action::Back) => {
+                app.discovery = None;
+                app.editing_printer = None;
+                app.refresh_printers();
                 app.change_mode(TUIMode::View);
             },
-            KeyCode::Char('q') => app.exit(),
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
             _ => {}
         }
     }
@@ -82,43 +285,140 @@ impl<'a> NewPrinter<'a> {
     fn handle_edit_mode(app:&mut App, key_event: KeyEvent) {
         match app.selected_edit_block {
             EditBlock::Title => NewPrinter::handle_edit_title_mode(app,key_event),
+            EditBlock::Description | EditBlock::Location => NewPrinter::handle_edit_text_mode(app,key_event),
             EditBlock::Devices => NewPrinter::handle_edit_devices_mode(app,key_event),
             EditBlock::Drivers => NewPrinter::handle_edit_drivers_mode(app,key_event),
         }   
     }
 
     fn handle_edit_title_mode(app:&mut App, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-' => app.selected_printer_name.push(c),
-            KeyCode::Backspace => {
-                app.selected_printer_name.pop();
+        match app.config.keymap.text_action(key_event, keymap::FORM_TEXT) {
+            Some(Action::Select | Action::Back) => app.selected_edit_mode = EditMode::View,
+            _ => {
+                edit_text(&mut app.selected_printer_name, key_event);
             }
-            KeyCode::Esc => app.selected_edit_mode = EditMode::View,
-            _ => {}
         }
     }
 
+    /// Free text input for the Description and Location blocks.
+    fn handle_edit_text_mode(app:&mut App, key_event: KeyEvent) {
+        if app.config.keymap.text_action(key_event, keymap::FORM_TEXT).is_some() {
+            app.selected_edit_mode = EditMode::View;
+            return;
+        }
+        let text = if app.selected_edit_block == EditBlock::Description {
+            &mut app.printer_description
+        } else {
+            &mut app.printer_location
+        };
+        edit_text(text, key_event);
+    }
+
     fn handle_edit_devices_mode(app:&mut App, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('k') => NewPrinter::previous_device(app),
-            KeyCode::Char('j') => NewPrinter::next_device(app),
-            KeyCode::Esc => app.selected_edit_mode = EditMode::View,
+        if app.uri_builder.is_some() {
+            NewPrinter::handle_uri_builder(app,key_event);
+            return;
+        }
+        match app.config.keymap.action(key_event, keymap::DEVICES) {
+            Some(Action::Up) => NewPrinter::previous_device(app),
+            Some(Action::Down) => NewPrinter::next_device(app),
+            Some(Action::EnterUri) => app.uri_builder = Some(UriBuilder::default()),
+            Some(Action::Rescan) => NewPrinter::rescan(app),
+            Some(Action::StopScan) => NewPrinter::cancel_discovery(app),
+            Some(Action::Back) => {
+                app.selected_edit_mode = EditMode::View;
+                // Keeps the driver the user picked when the device stayed the same.
+                if app.devices.get(app.selected_device) != app.suggested_device.as_ref() {
+                    NewPrinter::suggest_drivers(app);
+                }
+            }
+            Some(Action::Help) => app.help = true,
             _ => {}
         }
     }
 
+    fn handle_uri_builder(app:&mut App, key_event: KeyEvent) {
+        let Some(builder) = app.uri_builder.as_mut() else {
+            return;
+        };
+        match app.config.keymap.text_action(key_event, keymap::URI_BUILDER) {
+            Some(Action::Left) => builder.previous_scheme(),
+            Some(Action::Right) => builder.next_scheme(),
+            Some(Action::NextField | Action::Down) => builder.next_field(),
+            Some(Action::PreviousField | Action::Up) => builder.previous_field(),
+            Some(Action::Select) => NewPrinter::add_custom_device(app),
+            Some(Action::Back) => app.uri_builder = None,
+            _ => builder.edit(key_event),
+        }
+    }
+
+    /// Adds the URI the builder puts together as a device and selects it,
+    /// keeping the builder open when the URI is not valid.
+    fn add_custom_device(app:&mut App) {
+        let Some(uri) = app.uri_builder.as_ref().map(UriBuilder::uri) else {
+            return;
+        };
+        if app.report(uri::validate(&uri)).is_none() {
+            return;
+        }
+        let mut device = Device {
+            uri,
+            info: "Entered manually".to_string(),
+            ..Default::default()
+        };
+        device.class = match device.scheme() {
+            "usb" | "parallel" | "serial" => "direct",
+            "file" => "file",
+            _ => "network",
+        }.to_string();
+        app.devices.push(device);
+        app.selected_device = app.devices.len() - 1;
+        app.uri_builder = None;
+        app.selected_edit_mode = EditMode::View;
+        NewPrinter::suggest_drivers(app);
+    }
+
     fn handle_edit_drivers_mode(app:&mut App, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('k') => NewPrinter::previous_driver(app),
-            KeyCode::Char('j') => NewPrinter::next_driver(app),
-            KeyCode::Esc => app.selected_edit_mode = EditMode::View,
+        if app.driver_searching {
+            NewPrinter::handle_driver_search(app,key_event);
+            return;
+        }
+        match app.config.keymap.action(key_event, keymap::DRIVERS) {
+            Some(Action::Up) => NewPrinter::previous_driver(app),
+            Some(Action::Down) => NewPrinter::next_driver(app),
+            Some(Action::Search) => app.driver_searching = true,
+            Some(Action::Back) => app.selected_edit_mode = EditMode::View,
+            Some(Action::Help) => app.help = true,
             _ => {}
         }
     }
+
+    fn handle_driver_search(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.text_action(key_event, keymap::DRIVER_SEARCH) {
+            Some(Action::Select) => {
+                app.driver_searching = false;
+                return;
+            }
+            Some(Action::Back) => {
+                app.driver_searching = false;
+                app.driver_search.clear();
+            }
+            _ => {
+                if !edit_text(&mut app.driver_search, key_event) {
+                    return;
+                }
+            }
+        }
+        app.selected_driver = 0;
+        app.keep_driver = false;
+        NewPrinter::refresh_driver_matches(app);
+    }
  
     fn next_block(app:&mut App) {
         match app.selected_edit_block {
-            EditBlock::Title => app.selected_edit_block = EditBlock::Devices,
+            EditBlock::Title => app.selected_edit_block = EditBlock::Description,
+            EditBlock::Description => app.selected_edit_block = EditBlock::Location,
+            EditBlock::Location => app.selected_edit_block = EditBlock::Devices,
             EditBlock::Devices => app.selected_edit_block = EditBlock::Drivers,
             EditBlock::Drivers => app.selected_edit_block = EditBlock::Title
         }
@@ -127,11 +427,16 @@ impl<'a> NewPrinter<'a> {
     fn previous_block(app:&mut App) {
         match app.selected_edit_block {
             EditBlock::Title => app.selected_edit_block = EditBlock::Drivers,
-            EditBlock::Devices => app.selected_edit_block = EditBlock::Title,
+            EditBlock::Description => app.selected_edit_block = EditBlock::Title,
+            EditBlock::Location => app.selected_edit_block = EditBlock::Description,
+            EditBlock::Devices => app.selected_edit_block = EditBlock::Location,
             EditBlock::Drivers => app.selected_edit_block = EditBlock::Devices
         }
     }
     fn next_device(app:&mut App) {
+        if app.devices.is_empty() {
+            return;
+        }
         app.selected_device= if app.selected_device >= app.devices.len() - 1 {
             0
         } else {
@@ -140,6 +445,9 @@ impl<'a> NewPrinter<'a> {
     }
 
     fn previous_device(app:&mut App) {
+        if app.devices.is_empty() {
+            return;
+        }
         app.selected_device = if app.selected_device == 0 {
             app.devices.len() - 1
         } else {
@@ -148,7 +456,12 @@ impl<'a> NewPrinter<'a> {
     }
 
     fn next_driver(app:&mut App) {
-        app.selected_driver = if app.selected_driver >= app.drivers.len() - 1 {
+        let count = app.driver_matches.len();
+        if count == 0 {
+            return;
+        }
+        app.keep_driver = false;
+        app.selected_driver = if app.selected_driver >= count - 1 {
             0
         } else {
             app.selected_driver + 1
@@ -156,8 +469,13 @@ impl<'a> NewPrinter<'a> {
     }
 
     fn previous_driver(app:&mut App) {
+        let count = app.driver_matches.len();
+        if count == 0 {
+            return;
+        }
+        app.keep_driver = false;
         app.selected_driver = if app.selected_driver == 0 {
-            app.drivers.len() - 1
+            count - 1
         } else {
             app.selected_driver- 1
         };
@@ -167,129 +485,272 @@ impl<'a> NewPrinter<'a> {
         self.devices
             .iter()
             .map(|device| {
-                let device = device.value.clone();
                 Row::new(vec![
-                    Cell::from(device),
+                    Cell::from(device.name().to_string()),
+                    Cell::from(uri::mask_credentials(&device.uri)),
+                    Cell::from(device.class.clone()),
                 ])
             })
             .collect()
     }
   
+    /// Creates the printer, or asks to confirm the changes to the edited one,
+    /// keeping the form open when it fails.
     fn write(app:&mut App) {
-        let device =app.devices.get(app.selected_device)
-                .map_or("No URI".to_string(), |device| device.value.clone());
-        let driver = app.drivers.get(app.selected_driver)
-            .map_or("No Driver".to_string(), |driver| {
-                let words:Vec<&str> = driver.value.split(' ').collect();
-                words[0].to_string()
+        if app.report(NewPrinter::validate_name(app)).is_none() {
+            return;
+        }
+        let Some(device) = app.devices.get(app.selected_device).map(|device| device.uri.clone()) else {
+            app.status = Some(Status::Error("Pick a device or enter its URI first".to_string()));
+            return;
+        };
+        if app.report(uri::validate(&device)).is_none() {
+            return;
+        }
+        if NewPrinter::loading_drivers(app) {
+            app.status = Some(Status::Error("Wait for the drivers to load first".to_string()));
+            return;
+        }
+        if app.editing_printer.is_some() {
+            if NewPrinter::changes(app).is_empty() {
+                app.status = Some(Status::Info("Nothing to change".to_string()));
+            } else {
+                app.changes_prompt = true;
+            }
+            return;
+        }
+        let Some(driver) = NewPrinter::selected_driver(app).map(|driver| driver.ppd_name.clone()) else {
+            app.status = Some(Status::Error("Pick a driver first".to_string()));
+            return;
+        };
+        let created = app.backend.create_printer(&app.selected_printer_name,
+            &device,
+            &driver,
+            &app.printer_description,
+            &app.printer_location,
+        );
+        if app.report(created).is_some() {
+            app.status = Some(Status::Info(format!("Printer {} added", app.selected_printer_name)));
+            app.discovery = None;
+            app.refresh_printers();
+            app.change_mode(TUIMode::View);
+        }
+    }
+
+    /// The driver to switch the edited printer to, `None` to keep its own.
+    fn new_driver(app: &App) -> Option<&Driver> {
+        if app.keep_driver {
+            return None;
+        }
+        NewPrinter::selected_driver(app)
+    }
+
+    /// How the form differs from the printer being edited.
+    fn changes(app: &App) -> Vec<Change> {
+        let Some(printer) = &app.editing_printer else {
+            return vec![];
+        };
+        let device = app.devices.get(app.selected_device).map_or("", |device| device.uri.as_str());
+        let mut changes = vec![];
+        if device != printer.device_uri {
+            changes.push(Change {
+                field: "Device",
+                old: uri::mask_credentials(&printer.device_uri),
+                new: uri::mask_credentials(device),
+            });
+        }
+        if let Some(driver) = NewPrinter::new_driver(app) {
+            changes.push(Change {
+                field: "Driver",
+                old: printer.make_and_model.clone(),
+                new: format!("{} ({})", driver.make_and_model, driver.ppd_name),
             });
-        create_printer(app.selected_printer_name.clone(), 
-            device,
-            driver
+        }
+        if app.printer_description != printer.description {
+            changes.push(Change {
+                field: "Description",
+                old: printer.description.clone(),
+                new: app.printer_description.clone(),
+            });
+        }
+        if app.printer_location != printer.location {
+            changes.push(Change {
+                field: "Location",
+                old: printer.location.clone(),
+                new: app.printer_location.clone(),
+            });
+        }
+        changes
+    }
+
+    fn handle_changes_prompt(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.action(key_event, keymap::CHANGES) {
+            Some(Action::Yes | Action::Select) => NewPrinter::apply_changes(app),
+            Some(Action::No | Action::Back) => app.changes_prompt = false,
+            _ => {}
+        }
+    }
+
+    fn apply_changes(app:&mut App) {
+        app.changes_prompt = false;
+        let device = app.devices.get(app.selected_device).map_or(String::new(), |device| device.uri.clone());
+        let driver = NewPrinter::new_driver(app).map(|driver| driver.ppd_name.clone());
+        let modified = app.backend.modify_printer(&app.selected_printer_name,
+            &device,
+            driver.as_deref(),
+            &app.printer_description,
+            &app.printer_location,
         );
-    }    
+        if app.report(modified).is_some() {
+            app.status = Some(Status::Info(format!("Printer {} updated", app.selected_printer_name)));
+            app.discovery = None;
+            app.editing_printer = None;
+            app.refresh_printers();
+            app.change_mode(TUIMode::View);
+        }
+    }
 
+    /// Driver rows with the searched chars highlighted, recommended drivers
+    /// first, the make only shown on the first driver of each manufacturer.
     fn drivers_to_rows(&self) -> Vec<Row<'static>> {
-        self.drivers
+        let matched = self.theme.active().bold();
+        let mut previous_make = "";
+        self.driver_matches
             .iter()
-            .map(|driver| {
+            .enumerate()
+            .map(|(row, m)| {
+                let driver = &self.drivers[m.index];
+                let make = if m.recommended {
+                    if row == 0 { Span::styled("Recommended", self.theme.success()) } else { "".into() }
+                } else if driver.make == previous_make {
+                    "".into()
+                } else {
+                    driver.make.clone().bold()
+                };
+                if !m.recommended {
+                    previous_make = &driver.make;
+                }
+                // search_text() is the model, a space, then the PPD name.
+                let ppd_offset = driver.make_and_model.chars().count() + 1;
                 Row::new(vec![
-                    Cell::from(driver.value.clone()),
+                    Cell::from(make),
+                    Cell::from(highlight(&driver.make_and_model, &m.positions, 0, matched)),
+                    Cell::from(driver.driver_type.to_string()),
+                    Cell::from(driver.natural_language.clone()),
+                    Cell::from(highlight(&driver.ppd_name, &m.positions, ppd_offset, matched)),
                 ])
             })
             .collect()
-    }   
-
-    fn get_instructions(&self) -> Line {
-        let mut instructions_items = vec![];
-        match self.selected_edit_mode {
-            EditMode::View => {
-                instructions_items.push(" Up ".white());
-                instructions_items.push("<k> ".blue().bold());
-                instructions_items.push(" Down ".white());
-                instructions_items.push("<j> ".blue().bold());
-                instructions_items.push(" Edit Mode ".white());
-                instructions_items.push("<e> ".blue().bold());
-                instructions_items.push(" Quit Add Mode ".white());
-                instructions_items.push("<Esc> ".blue().bold());
-                instructions_items.push(" Quit ".white());
-                instructions_items.push("<q> ".blue().bold());
-            },
-            EditMode::Edit => {
-                match self.selected_block {
-                    EditBlock::Title => {
-                        instructions_items.push(" Quit Edit Mode ".white());
-                        instructions_items.push("<Esc> ".blue().bold());
-                    },
-                    EditBlock::Devices => {
-                        instructions_items.push(" Up ".white());
-                        instructions_items.push("<k> ".blue().bold());
-                        instructions_items.push(" Down ".white());
-                        instructions_items.push("<j> ".blue().bold());
-                        instructions_items.push(" Quit Edit Mode ".white());
-                        instructions_items.push("<Esc> ".blue().bold());
-                    },
-                    EditBlock::Drivers => {
-                        instructions_items.push(" Up ".white());
-                        instructions_items.push("<k> ".blue().bold());
-                        instructions_items.push(" Down ".white());
-                        instructions_items.push("<j> ".blue().bold());
-                        instructions_items.push(" Quit Edit Mode ".white());
-                        instructions_items.push("<Esc> ".blue().bold());
-                    }
-                }
-            }
+    }
+
+    /// A block title with a spinner while `busy`.
+    fn progress_title(&self, label: &str, busy: bool) -> Line<'static> {
+        if busy {
+            Line::from(vec![format!(" {} ", label).bold(), Span::styled(format!("{} ", self.spinner), Style::new().fg(self.theme.focus))])
+        } else {
+            Line::from(format!(" {} ", label).bold())
         }
-        Line::from(instructions_items)
     }
+
+    /// The keys the form or the block being edited answers, and whether it takes typed text.
+    fn context(&self) -> (Context, bool) {
+        match (self.selected_edit_mode, self.selected_block) {
+            (EditMode::View, _) => (keymap::FORM, false),
+            (EditMode::Edit, EditBlock::Title | EditBlock::Description | EditBlock::Location) => (keymap::FORM_TEXT, true),
+            (EditMode::Edit, EditBlock::Devices) => (keymap::DEVICES, false),
+            (EditMode::Edit, EditBlock::Drivers) if self.driver_searching => (keymap::DRIVER_SEARCH, true),
+            (EditMode::Edit, EditBlock::Drivers) => (keymap::DRIVERS, false),
+        }
+    }
+
+    fn get_instructions(&self, width: u16) -> Line<'static> {
+        let (context, typing) = self.context();
+        let scanning = self.scanning_devices || self.loading_drivers;
+        let entries: Vec<(Action, &str)> = context.iter()
+            .filter(|(action, _)| scanning || *action != Action::StopScan)
+            .map(|(action, label)| match (action, self.selected_edit_mode) {
+                (Action::Back, EditMode::View) if self.editing_printer.is_some() => (*action, "Discard Changes"),
+                _ => (*action, *label),
+            })
+            .collect();
+        self.keymap.hints(self.theme, &entries, typing, width)
+    }
+
     fn get_printer_name_widget(&self) -> Paragraph<'_> {
         let printer_name_title = Line::from(" Printer Name ".bold());
-        let printer_name_block = Block::default().title(printer_name_title)
+        let mut printer_name_block = Block::default().title(printer_name_title)
             .borders(Borders::ALL)
             .border_set(border::THICK)
-            .border_style(Style::default().fg(
-                if self.selected_block == EditBlock::Title {Color::Yellow} else {Color::White}));
+            .border_style(self.theme.border(self.selected_block == EditBlock::Title));
+        if let Some(error) = &self.name_error {
+            printer_name_block = printer_name_block
+                .title_bottom(Line::from(Span::styled(format!(" {} ", error), self.theme.error()).bold()).right_aligned());
+        }
 
         Paragraph::new(self.printer_name.as_str())
             .block(printer_name_block)
-            .style(Style::default().fg(Color::White))
+            .style(self.theme.text())
     }
     
+    /// A free text block, `optional` greyed out while empty.
+    fn get_text_widget(&self, label: &str, value: &str, block: EditBlock) -> Paragraph<'_> {
+        let title = Line::from(format!(" {} ", label).bold());
+        let text_block = Block::default().title(title)
+            .borders(Borders::ALL)
+            .border_set(border::THICK)
+            .border_style(self.theme.border(self.selected_block == block));
+
+        let editing = self.selected_edit_mode == EditMode::Edit && self.selected_block == block;
+        let text = if editing {
+            Line::from(format!("{}_", value).fg(self.theme.text))
+        } else if value.is_empty() {
+            Line::from(Span::styled("optional", self.theme.muted()))
+        } else {
+            Line::from(value.to_string().fg(self.theme.text))
+        };
+        Paragraph::new(text).block(text_block)
+    }
+
     fn get_device_name_widget(&self) -> Paragraph<'_> {
-        let printer_name_title = Line::from(" Device ".bold());
+        let printer_name_title = self.progress_title("Device", self.scanning_devices);
         let printer_name_block = Block::default().title(printer_name_title)
             .borders(Borders::ALL)
             .border_set(border::THICK)
-            .border_style(Style::default().fg(
-                if self.selected_block == EditBlock::Devices {Color::Yellow} else {Color::White}));
+            .border_style(self.theme.border(self.selected_block == EditBlock::Devices));
 
         Paragraph::new(self.devices.get(self.device_state.selected().unwrap_or(0))
-            .map_or("No Device".to_string(), |device| {
-                device.value.clone()
+            .map_or(if self.scanning_devices {"Looking for devices...".to_string()} else {"No Device".to_string()}, |device| {
+                format!("{} ({})", device.name(), uri::mask_credentials(&device.uri))
             }))
             .block(printer_name_block)
-            .style(Style::default().fg(Color::White))
+            .style(self.theme.text())
     }
 
     fn get_driver_name_widget(&self) -> Paragraph<'_> {
-        let printer_name_title = Line::from(" Driver ".bold());
+        let printer_name_title = self.progress_title("Driver", self.loading_drivers);
         let printer_name_block = Block::default().title(printer_name_title)
             .borders(Borders::ALL)
             .border_set(border::THICK)
-            .border_style(Style::default().fg(
-                if self.selected_block == EditBlock::Drivers {Color::Yellow} else {Color::White}));
+            .border_style(self.theme.border(self.selected_block == EditBlock::Drivers));
 
-        Paragraph::new(self.drivers.get(self.driver_state.selected().unwrap_or(0))
-            .map_or("No Driver".to_string(), |driver| {
-                driver.value.clone()
+        if let Some(printer) = self.editing_printer && self.keep_driver {
+            return Paragraph::new(format!("{} (current driver)", printer.make_and_model))
+                .block(printer_name_block)
+                .style(self.theme.text());
+        }
+
+        Paragraph::new(self.driver_matches.get(self.driver_state.selected().unwrap_or(0))
+            .and_then(|m| self.drivers.get(m.index))
+            .map_or(if self.loading_drivers {"Loading drivers...".to_string()} else {"No Driver".to_string()}, |driver| {
+                format!("{} ({})", driver.make_and_model, driver.ppd_name)
             }))
             .block(printer_name_block)
-            .style(Style::default().fg(Color::White))
+            .style(self.theme.text())
     }
 
     pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
-        let instructions = self.get_instructions();
-        let edit_title = Line::from(" Edit ".white().bold());
+        let instructions = self.get_instructions(area.width.saturating_sub(2));
+        let edit_title = Line::from(" Edit ".fg(self.theme.text).bold());
         let edit_block = Block::default().borders(Borders::ALL)
             .title(edit_title.centered())
             .title_bottom(instructions.centered())
@@ -314,6 +775,8 @@ impl<'a> NewPrinter<'a> {
                      Constraint::Length(3),
                      Constraint::Length(3),
                      Constraint::Length(3),
+                     Constraint::Length(3),
+                     Constraint::Length(3),
                  ])
                  .split(form_outer_layout[0]);
 
@@ -332,6 +795,8 @@ impl<'a> NewPrinter<'a> {
                      Constraint::Length(3),
                      Constraint::Length(3),
                      Constraint::Length(3),
+                     Constraint::Length(3),
+                     Constraint::Length(3),
                  ])
                  .split(form_outer_layout[0]);
 
@@ -340,27 +805,41 @@ impl<'a> NewPrinter<'a> {
         let printer_name_paragraph = self.get_printer_name_widget();
         printer_name_paragraph.render(form_left_layout[0], buf);
 
+        let description_paragraph = self.get_text_widget("Description", self.description, EditBlock::Description);
+        description_paragraph.render(form_left_layout[1], buf);
+
+        let location_paragraph = self.get_text_widget("Location", self.location, EditBlock::Location);
+        location_paragraph.render(form_left_layout[2], buf);
+
         let device_paragraph = self.get_device_name_widget();
-        device_paragraph.render(form_left_layout[1], buf);
+        device_paragraph.render(form_left_layout[3], buf);
 
         let driver_paragraph = self.get_driver_name_widget();
-        driver_paragraph.render(form_left_layout[2], buf);
+        driver_paragraph.render(form_left_layout[4], buf);
 
         if self.selected_edit_mode == EditMode::Edit && self.selected_block == EditBlock::Devices {
             let rows = self.printers_to_rows();
-            let devices_title = Line::from(" Available Devices ".bold());
+            let devices_title = self.progress_title(&format!("Available Devices ({})", self.devices.len()), self.scanning_devices);
             let devices_block = Block::default()
                 .title(devices_title)
-                .title_style(Style::default().fg(
-                    if self.selected_block == EditBlock::Devices {Color::Yellow} else {Color::White}));
+                .title_style(self.theme.border(self.selected_block == EditBlock::Devices));
 
             let devices_table_widths = [
-                Constraint::Percentage(100),
+                Constraint::Percentage(40),
+                Constraint::Percentage(45),
+                Constraint::Length(8),
             ];   
+            let header = Row::new(vec![
+                    Cell::from("Name"),
+                    Cell::from("URI"),
+                    Cell::from("Class"),
+                ])
+                .style(Style::new().bold());
             let devices_table =Table::new(rows,devices_table_widths) 
+                .header(header)
                 .column_spacing(1)
-                .row_highlight_style(Style::new().yellow())
-                .cell_highlight_style(Style::new().blue())
+                .row_highlight_style(self.theme.highlight())
+                .cell_highlight_style(self.theme.highlight())
                 .highlight_symbol("  ")
                 .block(devices_block);
 
@@ -373,31 +852,97 @@ impl<'a> NewPrinter<'a> {
         }
 
         if self.selected_edit_mode == EditMode::Edit && self.selected_block == EditBlock::Drivers {
-     
-            let drivers_title = Line::from(" Available Drivers ".bold());
+            let drivers_layout = Layout::default()
+                 .direction(Direction::Vertical)
+                 .constraints(vec![
+                     Constraint::Length(if self.driver_searching || !self.driver_search.is_empty() {3} else {0}),
+                     Constraint::Min(0),
+                 ])
+                 .split(form_outer_layout[1]);
+
+            let search_block = Block::default()
+                .title(Line::from(format!(" Search ({} of {}) ", self.driver_matches.len(), self.drivers.len()).bold()))
+                .borders(Borders::ALL)
+                .border_set(border::THICK)
+                .border_style(self.theme.border(self.driver_searching));
+            Paragraph::new(format!("/{}{}", self.driver_search, if self.driver_searching {"_"} else {""}))
+                .block(search_block)
+                .style(self.theme.text())
+                .render(drivers_layout[0], buf);
+
+            let drivers_title = self.progress_title("Available Drivers", self.loading_drivers);
             let drivers_block = Block::default()
                 .title(drivers_title)
-                .title_style(Style::default().fg(
-                    if self.selected_block == EditBlock::Drivers {Color::Yellow} else {Color::White}));
+                .title_style(self.theme.border(self.selected_block == EditBlock::Drivers));
 
             let drivers_widths = [
-                Constraint::Percentage(100),
+                Constraint::Length(12),
+                Constraint::Percentage(38),
+                Constraint::Length(14),
+                Constraint::Length(5),
+                Constraint::Percentage(35),
             ];   
 
+            let header = Row::new(vec![
+                    Cell::from("Make"),
+                    Cell::from("Model"),
+                    Cell::from("Type"),
+                    Cell::from("Lang"),
+                    Cell::from("PPD"),
+                ])
+                .style(Style::new().bold());
+
             let drivers_table = Table::new(self.drivers_to_rows(),drivers_widths) 
+                .header(header)
                 .column_spacing(1)
-                .row_highlight_style(Style::new().yellow())
-                .cell_highlight_style(Style::new().blue())
+                .row_highlight_style(self.theme.highlight())
+                .cell_highlight_style(self.theme.highlight())
                 .highlight_symbol("  ")
                 .block(drivers_block);
 
             StatefulWidget::render(
                 drivers_table,
-                form_outer_layout[1],
+                drivers_layout[1],
                 buf,
                 &mut self.driver_state,
             );
         }
+
+        if let Some(builder) = self.uri_builder {
+            builder.render(self.keymap, self.theme, area, buf);
+        }
+
+        if let Some(changes) = &self.changes {
+            self.render_changes(changes, area, buf);
+        }
+
+        if self.help {
+            self.keymap.render_help(self.theme, self.context().0, area, buf);
+        }
+    }
+
+    fn render_changes(&self, changes: &[Change], area: Rect, buf: &mut Buffer) {
+        let popup = popup_area(area, 80, changes.len() as u16 * 2 + 2);
+        let block = Block::bordered()
+            .title(Line::from(format!(" Changes to {} ", self.printer_name).bold()).centered())
+            .title_bottom(self.keymap.hints(self.theme, keymap::CHANGES, false, popup.width.saturating_sub(2)).centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.popup());
+        let or_none = |value: &str| if value.is_empty() { "(none)".to_string() } else { value.to_string() };
+        Clear.render(popup, buf);
+        let rows: Vec<Row> = changes
+            .iter()
+            .map(|change| Row::new(vec![
+                    Cell::from(change.field).bold(),
+                    Cell::from(Line::from(Span::styled(format!("- {}", or_none(&change.old)), self.theme.error()))),
+                ]))
+            .zip(changes.iter().map(|change| Row::new(vec![
+                    Cell::from(""),
+                    Cell::from(Line::from(Span::styled(format!("+ {}", or_none(&change.new)), self.theme.success()))),
+                ])))
+            .flat_map(|(old, new)| [old, new])
+            .collect();
+        Widget::render(Table::new(rows, [Constraint::Length(12), Constraint::Min(0)]).block(block), popup, buf);
     }
 }
 //
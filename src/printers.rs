@@ -1,61 +1,232 @@
 use ratatui::{
     buffer::Buffer,
     layout::Rect,
-    style::{Style,Stylize,Color},
+    style::{Style,Stylize},
     symbols::border,
     text::Line,
-    widgets::{Cell,Block,StatefulWidget,Row,Table,TableState,Borders},
+    widgets::{Cell,Block,Clear,StatefulWidget,Row,Table,TableState,Paragraph,Borders},
 };
 use ratatui::prelude::*;
 use ratatui::layout::Constraint;
 
 use crate::cups::{
-    printer::{
-        Printer,
-        remove_printer,
-        get_all_printers,
-    },
-    device::get_all_devices,
-    driver::get_all_drivers,
+    device::Device,
+    job::TEST_PAGE,
+    options::{OptionChoices, OptionValue},
+    printer::{DefaultScope, Printer, PrinterState},
+    uri,
 };
 
 use crate::utils::{
     TUIMode,
     EditBlock,
     EditMode,
+    Status,
+    ViewBlock,
+    ReasonAction,
+    popup_area,
+    edit_text,
 };
 
+use std::path::Path;
+
 use crate::App;
-use crossterm::event::{KeyCode,KeyEvent};
+use crate::classes::Classes;
+use crate::confirm::{Confirm, ConfirmAction};
+use crate::discovery::Discovery;
+use crate::file_browser::FileBrowser;
+use crate::jobs::Jobs;
+use crate::keymap::{self, Action, Context, Keymap};
+use crate::theme::Theme;
+use crossterm::event::KeyEvent;
+
+/// A row of the Options pane, `choices` being set for PPD backed options.
+#[derive(Debug)]
+pub struct OptionEntry<'b> {
+    pub name: String,
+    pub value: String,
+    pub choices: Option<&'b OptionChoices>,
+}
 
 #[derive(Debug)]
 pub struct Printers<'a> {
     pub state: TableState,
+    pub options_state: TableState,
+    pub choices_state: TableState,
     pub printers:&'a Vec<Printer>,
     pub selected_printer: usize,    
+    pub selected_block: ViewBlock,
+    pub selected_edit_mode: EditMode,
+    pub option_choices: &'a Vec<OptionChoices>,
+    pub option_value: &'a str,
+    pub reason_prompt: Option<ReasonAction>,
+    pub reason: &'a str,
+    pub default_printer: Option<&'a str>,
+    pub default_prompt: bool,
+    pub file_browser: Option<&'a FileBrowser>,
+    /// The printer undo would bring back.
+    pub last_removed: Option<&'a str>,
+    pub keymap: &'a Keymap,
+    pub theme: &'a Theme,
+    pub help: bool,
 }
 
 
 impl<'a> Printers<'a> {
-    pub fn new(printers: &'a Vec<Printer>,selected_printer:usize) -> Self {
+    pub fn new(app: &'a App) -> Self {
         Printers{
             state: TableState::default()
-                .with_selected(selected_printer),
-            printers,
-            selected_printer 
+                .with_selected(app.selected_printer),
+            options_state: TableState::default()
+                .with_selected(if app.selected_view_block == ViewBlock::Options {Some(app.selected_option)} else {None}),
+            choices_state: TableState::default()
+                .with_selected(app.selected_choice),
+            printers: &app.printers,
+            selected_printer: app.selected_printer,
+            selected_block: app.selected_view_block,
+            selected_edit_mode: app.selected_edit_mode,
+            option_choices: &app.option_choices,
+            option_value: &app.option_value,
+            reason_prompt: app.reason_prompt,
+            reason: &app.reason,
+            default_printer: app.default_printer.as_deref(),
+            last_removed: app.removed_printers.last().map(|printer| printer.name.as_str()),
+            default_prompt: app.default_prompt,
+            file_browser: app.file_browser.as_ref(),
+            keymap: &app.config.keymap,
+            theme: &app.config.palette,
+            help: app.help,
         }
     }
+
     pub fn handle_events(app:&mut App, key_event: KeyEvent) {
-        match key_event.code {
-            KeyCode::Char('k')=> Printers::previous_printer(app),
-            KeyCode::Char('j')=> Printers::next_printer(app),
-            KeyCode::Char('a')=> Printers::add_printer(app),
-            KeyCode::Char('d')=> Printers::remove_printer(app),
-            KeyCode::Char('q') => app.exit(),
+        if app.reason_prompt.is_some() {
+            Printers::handle_reason_prompt(app,key_event);
+            return;
+        }
+        if app.default_prompt {
+            Printers::handle_default_prompt(app,key_event);
+            return;
+        }
+        if app.file_browser.is_some() {
+            Printers::handle_file_browser(app,key_event);
+            return;
+        }
+        match (app.selected_view_block, app.selected_edit_mode) {
+            (ViewBlock::Printers, _) => Printers::handle_printers_block(app,key_event),
+            (ViewBlock::Options, EditMode::View) => Printers::handle_options_block(app,key_event),
+            (ViewBlock::Options, EditMode::Edit) => Printers::handle_edit_option_mode(app,key_event),
+        }
+    }
+
+    fn handle_printers_block(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.action(key_event, keymap::PRINTERS) {
+            Some(Action::Up) => Printers::previous_printer(app),
+            Some(Action::Down) => Printers::next_printer(app),
+            Some(Action::Right | Action::NextField) => Printers::focus_options(app),
+            Some(Action::Add) => Printers::add_printer(app),
+            Some(Action::Edit) => Printers::edit_printer(app),
+            Some(Action::Remove) => Printers::confirm_remove(app),
+            Some(Action::Undo) => Printers::undo_remove(app),
+            Some(Action::ToggleEnabled) => Printers::toggle_enabled(app),
+            Some(Action::ToggleAccepting) => Printers::toggle_accepting(app),
+            Some(Action::Jobs) => Jobs::open(app),
+            Some(Action::Classes) => Classes::open(app),
+            Some(Action::SetDefault) => app.default_prompt = !app.printers.is_empty(),
+            Some(Action::TestPage) => Printers::print(app, Path::new(TEST_PAGE), "Test Page"),
+            Some(Action::PrintFile) => Printers::open_file_browser(app),
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
+            _ => {}
+        }
+    }
+
+    fn handle_file_browser(app:&mut App, key_event: KeyEvent) {
+        let Some(browser) = app.file_browser.as_mut() else {
+            return;
+        };
+        let result = match app.config.keymap.action(key_event, keymap::FILE_BROWSER) {
+            Some(Action::Up) => {
+                browser.previous();
+                Ok(None)
+            }
+            Some(Action::Down) => {
+                browser.next();
+                Ok(None)
+            }
+            Some(Action::Left) => browser.parent().map(|_| None),
+            Some(Action::Right | Action::Select) => browser.enter(),
+            Some(Action::Back) => {
+                app.file_browser = None;
+                Ok(None)
+            }
+            _ => Ok(None),
+        };
+        match result {
+            Ok(Some(path)) => {
+                app.file_browser = None;
+                let title = path.file_name().map_or(String::new(), |name| name.to_string_lossy().to_string());
+                Printers::print(app, &path, &title);
+            }
+            Ok(None) => {}
+            Err(error) => app.status = Some(Status::Error(error.to_string())),
+        }
+    }
+
+    fn handle_default_prompt(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.action(key_event, keymap::DEFAULT_PROMPT) {
+            Some(Action::SystemDefault) => Printers::set_default(app, DefaultScope::System),
+            Some(Action::UserDefault) => Printers::set_default(app, DefaultScope::User),
+            Some(Action::Back) => app.default_prompt = false,
+            _ => {}
+        }
+    }
+
+    fn handle_reason_prompt(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.text_action(key_event, keymap::TEXT_INPUT) {
+            Some(Action::Select) => Printers::run_reason_action(app),
+            Some(Action::Back) => app.reason_prompt = None,
+            _ => {
+                edit_text(&mut app.reason, key_event);
+            }
+        }
+    }
+
+    fn handle_options_block(app:&mut App, key_event: KeyEvent) {
+        match app.config.keymap.action(key_event, keymap::OPTIONS) {
+            Some(Action::Up) => Printers::previous_option(app),
+            Some(Action::Down) => Printers::next_option(app),
+            Some(Action::Edit) => Printers::edit_option(app),
+            Some(Action::Left | Action::PreviousField | Action::Back) => app.selected_view_block = ViewBlock::Printers,
+            Some(Action::Help) => app.help = true,
+            Some(Action::Quit) => app.exit(),
             _ => {}
         }
     }
 
+    fn handle_edit_option_mode(app:&mut App, key_event: KeyEvent) {
+        let has_choices = Printers::option_entries(app.printers.get(app.selected_printer), &app.option_choices)
+            .get(app.selected_option)
+            .is_some_and(|entry| entry.choices.is_some());
+        if has_choices {
+            match app.config.keymap.action(key_event, keymap::OPTION_CHOICES) {
+                Some(Action::Up) => Printers::previous_choice(app),
+                Some(Action::Down) => Printers::next_choice(app),
+                Some(Action::Select) => Printers::write_option(app),
+                Some(Action::Back) => app.selected_edit_mode = EditMode::View,
+                _ => {}
+            }
+            return;
+        }
+        match app.config.keymap.text_action(key_event, keymap::TEXT_INPUT) {
+            Some(Action::Select) => Printers::write_option(app),
+            Some(Action::Back) => app.selected_edit_mode = EditMode::View,
+            _ => {
+                edit_text(&mut app.option_value, key_event);
+            }
+        }
+    }
+
     fn next_printer(app:&mut App) {
         if app.printers.is_empty() {
             return;
@@ -70,6 +241,9 @@ impl<'a> Printers<'a> {
     }
 
     fn previous_printer(app:&mut App) {
+        if app.printers.is_empty() {
+            return;
+        }
         app.selected_printer= if app.selected_printer== 0 {
             app.printers.len() - 1
         } else {
@@ -79,34 +253,429 @@ impl<'a> Printers<'a> {
             .map_or("No Printer".to_string(), |p| p.name.clone());
     }
 
+    fn focus_options(app:&mut App) {
+        let Some(printer) = app.printers.get(app.selected_printer) else {
+            return;
+        };
+        let choices = app.backend.get_printer_option_choices(&printer.name);
+        app.option_choices = app.report(choices).unwrap_or_default();
+        app.selected_option = 0;
+        app.selected_view_block = ViewBlock::Options;
+    }
+
+    fn next_option(app:&mut App) {
+        let count = Printers::option_entries(app.printers.get(app.selected_printer), &app.option_choices).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_option = if app.selected_option >= count - 1 {
+            0
+        } else {
+            app.selected_option + 1
+        };
+    }
+
+    fn previous_option(app:&mut App) {
+        let count = Printers::option_entries(app.printers.get(app.selected_printer), &app.option_choices).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_option = if app.selected_option == 0 {
+            count - 1
+        } else {
+            app.selected_option - 1
+        };
+    }
+
+    fn next_choice(app:&mut App) {
+        let count = Printers::selected_choices(app).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_choice = if app.selected_choice >= count - 1 {
+            0
+        } else {
+            app.selected_choice + 1
+        };
+    }
+
+    fn previous_choice(app:&mut App) {
+        let count = Printers::selected_choices(app).len();
+        if count == 0 {
+            return;
+        }
+        app.selected_choice = if app.selected_choice == 0 {
+            count - 1
+        } else {
+            app.selected_choice - 1
+        };
+    }
+
+    fn selected_choices(app:&App) -> Vec<String> {
+        Printers::option_entries(app.printers.get(app.selected_printer), &app.option_choices)
+            .get(app.selected_option)
+            .and_then(|entry| entry.choices)
+            .map_or(vec![], |choices| choices.choices.clone())
+    }
+
+    fn edit_option(app:&mut App) {
+        let entries = Printers::option_entries(app.printers.get(app.selected_printer), &app.option_choices);
+        let Some(entry) = entries.get(app.selected_option) else {
+            return;
+        };
+        app.option_value = entry.value.clone();
+        app.selected_choice = entry.choices
+            .and_then(|choices| choices.choices.iter().position(|choice| *choice == entry.value))
+            .unwrap_or(0);
+        app.selected_edit_mode = EditMode::Edit;
+    }
+
+    fn write_option(app:&mut App) {
+        let Some(printer) = app.printers.get(app.selected_printer).map(|printer| printer.name.clone()) else {
+            return;
+        };
+        let entries = Printers::option_entries(app.printers.get(app.selected_printer), &app.option_choices);
+        let Some(entry) = entries.get(app.selected_option) else {
+            return;
+        };
+        let option = entry.name.clone();
+        let value = match entry.choices {
+            Some(choices) => choices.choices.get(app.selected_choice).cloned().unwrap_or_default(),
+            None => app.option_value.clone(),
+        };
+        let written = app.backend.set_printer_option(&printer, &option, &value);
+        if app.report(written).is_some() {
+            app.status = Some(Status::Info(format!("{} set to {} on {}", option, value, printer)));
+        }
+        app.selected_edit_mode = EditMode::View;
+        app.refresh_printers();
+        let choices = app.backend.get_printer_option_choices(&printer);
+        app.option_choices = app.report(choices).unwrap_or_default();
+    }
+
+    fn toggle_enabled(app:&mut App) {
+        let Some(printer) = app.printers.get(app.selected_printer) else {
+            return;
+        };
+        if printer.state == PrinterState::Stopped {
+            let name = printer.name.clone();
+            let enabled = app.backend.enable_printer(&name);
+            if app.report(enabled).is_some() {
+                app.status = Some(Status::Info(format!("Printer {} enabled", name)));
+            }
+            app.refresh_printers();
+        } else {
+            app.reason.clear();
+            app.reason_prompt = Some(ReasonAction::Disable);
+        }
+    }
+
+    fn toggle_accepting(app:&mut App) {
+        let Some(printer) = app.printers.get(app.selected_printer) else {
+            return;
+        };
+        if !printer.accepting {
+            let name = printer.name.clone();
+            let accepted = app.backend.accept_jobs(&name);
+            if app.report(accepted).is_some() {
+                app.status = Some(Status::Info(format!("Printer {} is accepting jobs", name)));
+            }
+            app.refresh_printers();
+        } else {
+            app.reason.clear();
+            app.reason_prompt = Some(ReasonAction::Reject);
+        }
+    }
+
+    fn run_reason_action(app:&mut App) {
+        let Some(action) = app.reason_prompt.take() else {
+            return;
+        };
+        let Some(name) = app.printers.get(app.selected_printer).map(|p| p.name.clone()) else {
+            return;
+        };
+        let reason = Some(app.reason.trim()).filter(|reason| !reason.is_empty());
+        let (result, message) = match action {
+            ReasonAction::Disable => (app.backend.disable_printer(&name, reason), format!("Printer {} disabled", name)),
+            ReasonAction::Reject => (app.backend.reject_jobs(&name, reason), format!("Printer {} is rejecting jobs", name)),
+        };
+        if app.report(result).is_some() {
+            app.status = Some(Status::Info(message));
+        }
+        app.refresh_printers();
+    }
+
+    fn set_default(app:&mut App, scope: DefaultScope) {
+        app.default_prompt = false;
+        let Some(name) = app.printers.get(app.selected_printer).map(|p| p.name.clone()) else {
+            return;
+        };
+        let set = app.backend.set_default_printer(&name, scope);
+        if app.report(set).is_some() {
+            let scope = match scope {
+                DefaultScope::System => "system",
+                DefaultScope::User => "your",
+            };
+            app.status = Some(Status::Info(format!("{} is now {} default printer", name, scope)));
+        }
+        app.refresh_printers();
+    }
+
+    fn open_file_browser(app:&mut App) {
+        if app.printers.is_empty() {
+            return;
+        }
+        let dir = app.config.print_directory()
+            .or_else(|| std::env::current_dir().ok())
+            .or_else(dirs::home_dir)
+            .unwrap_or_else(|| "/".into());
+        match FileBrowser::open(dir) {
+            Ok(browser) => app.file_browser = Some(browser),
+            Err(error) => app.status = Some(Status::Error(error.to_string())),
+        }
+    }
+
+    /// Prints `path` on the selected printer and reports the job id.
+    fn print(app:&mut App, path: &Path, title: &str) {
+        let Some(name) = app.printers.get(app.selected_printer).map(|p| p.name.clone()) else {
+            return;
+        };
+        let printed = app.backend.print_file(&name, path, title);
+        if let Some(id) = app.report(printed) {
+            app.status = Some(Status::Info(format!("{} sent to {} as job {}", title, name, id)));
+        }
+    }
+
     fn add_printer(app:&mut App) {
         app.selected_printer = 0;
-        app.selected_printer_name = "New Printer".to_string();
+        app.selected_printer_name = "New_Printer".to_string();
+        app.printer_description.clear();
+        app.printer_location.clear();
+        app.devices.clear();
+        app.editing_printer = None;
+        Printers::open_form(app);
+    }
+
+    /// Opens the form on the selected printer, its current device listed
+    /// first and its driver kept unless another one gets picked.
+    fn edit_printer(app:&mut App) {
+        let Some(printer) = app.printers.get(app.selected_printer).cloned() else {
+            return;
+        };
+        if printer.is_class {
+            app.status = Some(Status::Info(format!("{} is a class, change its members from the Classes view {}", printer.name, app.config.keymap.key(Action::Classes))));
+            return;
+        }
+        app.selected_printer_name = printer.name.clone();
+        app.printer_description = printer.description.clone();
+        app.printer_location = printer.location.clone();
+        app.devices = vec![Device {
+            uri: printer.device_uri.clone(),
+            info: "Current device".to_string(),
+            ..Default::default()
+        }];
+        app.editing_printer = Some(printer);
+        Printers::open_form(app);
+    }
+
+    /// Resets the form state shared by adding and editing, and starts discovery.
+    fn open_form(app:&mut App) {
         app.selected_device = 0;
         app.selected_driver = 0;
+        app.driver_search.clear();
+        app.driver_searching = false;
+        app.keep_driver = app.editing_printer.is_some();
+        app.changes_prompt = false;
         app.selected_edit_block = EditBlock::Title;
         app.selected_edit_mode = EditMode::View;
-        app.devices = get_all_devices();
-        app.drivers = get_all_drivers();
+        app.drivers.clear();
+        app.recommended_drivers.clear();
+        app.suggested_device = None;
+        app.driver_matches.clear();
+        app.discovery = Some(Discovery::start(&app.backend, &app.discovery_options, true));
         app.change_mode(TUIMode::Edit);
     }
 
-    fn remove_printer(app:&mut App) {
-        let printer_to_remove = app.printers.get(app.selected_printer)
-            .map_or(Printer::default(), |p| p.clone());
-        remove_printer(&printer_to_remove.name);
-        app.printers = get_all_printers();
-        app.selected_printer = 0;
+    fn confirm_remove(app:&mut App) {
+        let Some(printer) = app.printers.get(app.selected_printer) else {
+            return;
+        };
+        let undo = app.config.keymap.key(Action::Undo);
+        if printer.is_class {
+            app.confirm = Some(Confirm::new(
+                format!("Remove {}", printer.name),
+                format!("Remove the class {}? Its printers stay. It can be added back with {} until you quit.", printer.name, undo),
+                ConfirmAction::RemoveClass(printer.name.clone()),
+            ));
+            return;
+        }
+        app.confirm = Some(Confirm::new(
+            format!("Remove {}", printer.name),
+            format!("Remove the printer {} and cancel its jobs? It can be added back with {} until you quit.", printer.name, undo),
+            ConfirmAction::RemovePrinter(printer.name.clone()),
+        ));
+    }
+
+    /// Removes the printer, keeping what it takes to set it up again.
+    pub fn remove_printer(app:&mut App, name: &str) {
+        let snapshot = app.printers.iter().find(|printer| printer.name == name).cloned();
+        let removed = app.backend.remove_printer(name);
+        if app.report(removed).is_some() {
+            app.status = Some(Status::Info(format!("Printer {} removed, undo with {}", name, app.config.keymap.key(Action::Undo))));
+            app.removed_printers.extend(snapshot);
+        }
+        app.refresh_printers();
+        if app.selected_printer >= app.printers.len() {
+            app.selected_printer = app.printers.len().saturating_sub(1);
+        }
+    }
+
+    /// Sets the last removed printer up again with its device, description,
+    /// location, options, sharing and enabled and accepting state, and the
+    /// installed driver of the same model.
+    fn undo_remove(app:&mut App) {
+        let Some(printer) = app.removed_printers.pop() else {
+            return;
+        };
+        if printer.is_class {
+            Printers::undo_remove_class(app, printer);
+            return;
+        }
+        let drivers = app.backend.get_all_drivers();
+        let Some(drivers) = app.report(drivers) else {
+            app.removed_printers.push(printer);
+            return;
+        };
+        let driver = drivers.iter()
+            .find(|driver| driver.make_and_model == printer.make_and_model)
+            .map(|driver| driver.ppd_name.clone());
+        let is_ipp = Device { uri: printer.device_uri.clone(), ..Default::default() }.is_ipp();
+        let Some(driver) = driver.or_else(|| is_ipp.then(|| "everywhere".to_string())) else {
+            app.status = Some(Status::Error(format!("No installed driver for {}, add {} back by hand", printer.make_and_model, printer.name)));
+            app.removed_printers.push(printer);
+            return;
+        };
+        let created = app.backend.create_printer(&printer.name, &printer.device_uri, &driver, &printer.description, &printer.location);
+        if app.report(created).is_none() {
+            app.removed_printers.push(printer);
+            return;
+        }
+        let mut failed = 0;
+        for (option, value) in printer.options.iter() {
+            if option.starts_with("printer-") || option.starts_with("marker-") || option == "device-uri" {
+                continue;
+            }
+            let value = match value {
+                OptionValue::List(values) => values.join(","),
+                value => value.to_string(),
+            };
+            if app.backend.set_printer_option(&printer.name, option, &value).is_err() {
+                failed += 1;
+            }
+        }
+        // create_printer brings the queue back enabled and accepting with the
+        // server's sharing default, so put back how it was.
+        let mut restored = vec![];
+        if let Some(OptionValue::Bool(shared)) = printer.options.get("printer-is-shared") {
+            restored.push(app.backend.set_printer_option(&printer.name, "printer-is-shared", &shared.to_string()));
+        }
+        if printer.state == PrinterState::Stopped {
+            let reason = Some(printer.state_message.as_str()).filter(|reason| !reason.is_empty());
+            restored.push(app.backend.disable_printer(&printer.name, reason));
+        }
+        if !printer.accepting {
+            restored.push(app.backend.reject_jobs(&printer.name, None));
+        }
+        failed += restored.iter().filter(|result| result.is_err()).count();
+        app.status = Some(Status::Info(if failed == 0 {
+            format!("Printer {} restored", printer.name)
+        } else {
+            format!("Printer {} restored, {} settings could not be set back", printer.name, failed)
+        }));
+        app.refresh_printers();
+        app.selected_printer = app.printers.iter().position(|p| p.name == printer.name).unwrap_or(0);
+    }
+
+    /// Gathers the members of a removed class again, skipping those removed since.
+    fn undo_remove_class(app:&mut App, class: Printer) {
+        let members: Vec<&String> = class.members.iter()
+            .filter(|member| app.printers.iter().any(|printer| &&printer.name == member))
+            .collect();
+        if members.is_empty() {
+            app.status = Some(Status::Error(format!("None of the printers of {} are left to restore it with", class.name)));
+            return;
+        }
+        let added = members.iter()
+            .try_for_each(|member| app.backend.add_class_member(&class.name, member));
+        if app.report(added).is_none() {
+            app.removed_printers.push(class);
+            return;
+        }
+        app.status = Some(Status::Info(format!("Class {} restored", class.name)));
+        app.refresh_printers();
+        app.selected_printer = app.printers.iter().position(|p| p.name == class.name).unwrap_or(0);
+    }
+
+    /// Options reported for the printer followed by the PPD options it doesn't list yet.
+    pub fn option_entries<'b>(printer: Option<&'b Printer>, option_choices: &'b [OptionChoices]) -> Vec<OptionEntry<'b>> {
+        let mut entries: Vec<OptionEntry<'b>> = printer
+            .map_or(vec![], |printer| printer.options
+                .iter()
+                .map(|(name, value)| OptionEntry {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                    choices: option_choices.iter().find(|choices| choices.name == name),
+                })
+                .collect());
+        for choices in option_choices {
+            if !entries.iter().any(|entry| entry.name == choices.name) {
+                entries.push(OptionEntry {
+                    name: choices.name.clone(),
+                    value: choices.default.clone().unwrap_or_default(),
+                    choices: Some(choices),
+                });
+            }
+        }
+        entries
     }
 
     fn printers_to_rows(&self) -> Vec<Row<'static>> {
         self.printers
             .iter()
             .map(|printer| {
-                let name = printer.name.to_string();
+                let is_default = self.default_printer == Some(printer.name.as_str());
+                let name = if is_default {
+                    format!("{} (default)", printer.name)
+                } else {
+                    printer.name.to_string()
+                };
+                let state = if printer.accepting {
+                    printer.state.to_string()
+                } else {
+                    format!("{}, rejecting", printer.state)
+                };
+                let message = if printer.is_class {
+                    format!("Members: {}", printer.members.join(", "))
+                } else if printer.state_message.is_empty() {
+                    printer.state_reasons.join(", ")
+                } else {
+                    printer.state_message.clone()
+                };
+                let style = self.theme.printer_state(printer.state);
+                // Classes keep their state color, but their name tells them apart.
+                let name = if printer.is_class {
+                    Cell::from(format!("{} [class]", name)).style(self.theme.class())
+                } else {
+                    Cell::from(name)
+                };
                 Row::new(vec![
-                    Cell::from(name),
+                    name,
+                    Cell::from(state),
+                    Cell::from(message),
+                    Cell::from(printer.description.clone()),
+                    Cell::from(printer.location.clone()),
                 ])
+                .style(if is_default { style.bold() } else { style })
             })
             .collect()
     }
@@ -119,35 +688,139 @@ impl<'a> Printers<'a> {
             self.selected_printer = self.printers.len() - 1;
         }
         
-        self.printers[self.selected_printer].options
-            .iter()
-            .map(|option| {
-                let name = option.to_string();
+        Printers::option_entries(self.printers.get(self.selected_printer), self.option_choices)
+            .into_iter()
+            .map(|entry| {
+                let value = Printers::shown_value(&entry.name, &entry.value);
                 Row::new(vec![
-                    Cell::from(name),
+                    Cell::from(entry.name),
+                    Cell::from(value),
                 ])
             })
             .collect()
     }   
 
-    fn get_instructions(&self) -> Line<'static> {
-        let mut instructions_items = vec![];
-        instructions_items.push(" Up ".white());
-        instructions_items.push("<k> ".blue().bold());
-        instructions_items.push(" Down ".white());
-        instructions_items.push("<j> ".blue().bold());
-        instructions_items.push(" Add new printer ".white());
-        instructions_items.push("<a> ".blue().bold());
-        instructions_items.push(" Remove printer ".white());
-        instructions_items.push("<d> ".blue().bold());
-        instructions_items.push(" Quit ".white());
-        instructions_items.push("<q> ".blue().bold());
-        Line::from(instructions_items)
+    /// The keys the screen or popup in front answers, and whether it takes typed text.
+    fn context(&self) -> (Context, bool) {
+        if self.file_browser.is_some() {
+            return (keymap::FILE_BROWSER, false);
+        }
+        if self.default_prompt {
+            return (keymap::DEFAULT_PROMPT, false);
+        }
+        if self.reason_prompt.is_some() {
+            return (keymap::TEXT_INPUT, true);
+        }
+        match (self.selected_block, self.selected_edit_mode) {
+            (ViewBlock::Printers, _) => (keymap::PRINTERS, false),
+            (ViewBlock::Options, EditMode::View) => (keymap::OPTIONS, false),
+            (ViewBlock::Options, EditMode::Edit) => {
+                let has_choices = Printers::option_entries(self.printers.get(self.selected_printer), self.option_choices)
+                    .get(self.options_state.selected().unwrap_or(0))
+                    .is_some_and(|entry| entry.choices.is_some());
+                if has_choices {
+                    (keymap::OPTION_CHOICES, false)
+                } else {
+                    (keymap::TEXT_INPUT, true)
+                }
+            }
+        }
+    }
+
+    fn get_instructions(&self, width: u16) -> Line<'static> {
+        let (context, typing) = self.context();
+        let undo = self.last_removed.map(|name| format!("Undo remove {}", name));
+        let entries: Vec<(Action, &str)> = context.iter()
+            .filter_map(|(action, label)| match action {
+                Action::Undo => undo.as_deref().map(|undo| (*action, undo)),
+                _ => Some((*action, *label)),
+            })
+            .collect();
+        self.keymap.hints(self.theme, &entries, typing, width)
+    }
+
+    /// An option value as shown, the credentials in `device-uri` masked.
+    fn shown_value(name: &str, value: &str) -> String {
+        if name == "device-uri" {
+            uri::mask_credentials(value)
+        } else {
+            value.to_string()
+        }
+    }
+
+    fn render_option_editor(&mut self, area: Rect, buf: &mut Buffer) {
+        let entries = Printers::option_entries(self.printers.get(self.selected_printer), self.option_choices);
+        let Some(entry) = entries.get(self.options_state.selected().unwrap_or(0)) else {
+            return;
+        };
+        let title = Line::from(format!(" {} ", entry.name).bold());
+        let block = Block::bordered()
+            .title(title.centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.popup());
+
+        match entry.choices {
+            Some(choices) => {
+                let popup = popup_area(area, 60, choices.choices.len() as u16 + 2);
+                Clear.render(popup, buf);
+                let rows: Vec<Row> = choices.choices
+                    .iter()
+                    .map(|choice| Row::new(vec![Cell::from(choice.clone())]))
+                    .collect();
+                let table = Table::new(rows, [Constraint::Percentage(100)])
+                    .row_highlight_style(self.theme.highlight())
+                    .highlight_symbol("  ")
+                    .block(block);
+                StatefulWidget::render(table, popup, buf, &mut self.choices_state);
+            }
+            None => {
+                let popup = popup_area(area, 60, 3);
+                Clear.render(popup, buf);
+                Paragraph::new(Printers::shown_value(&entry.name, self.option_value))
+                    .block(block)
+                    .style(self.theme.text())
+                    .render(popup, buf);
+            }
+        }
+    }
+
+    fn render_reason_prompt(&self, action: ReasonAction, area: Rect, buf: &mut Buffer) {
+        let name = self.printers.get(self.selected_printer).map_or("", |p| p.name.as_str());
+        let title = match action {
+            ReasonAction::Disable => format!(" Reason for disabling {} (optional) ", name),
+            ReasonAction::Reject => format!(" Reason for rejecting jobs on {} (optional) ", name),
+        };
+        let block = Block::bordered()
+            .title(Line::from(title.bold()).centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.popup());
+        let popup = popup_area(area, 80, 3);
+        Clear.render(popup, buf);
+        Paragraph::new(self.reason)
+            .block(block)
+            .style(self.theme.text())
+            .render(popup, buf);
+    }
+
+    fn render_default_prompt(&self, area: Rect, buf: &mut Buffer) {
+        let name = self.printers.get(self.selected_printer).map_or("", |p| p.name.as_str());
+        let block = Block::bordered()
+            .title(Line::from(format!(" Make {} the default printer ", name).bold()).centered())
+            .border_set(border::THICK)
+            .border_style(self.theme.popup());
+        let popup = popup_area(area, 80, 4);
+        Clear.render(popup, buf);
+        Paragraph::new(vec![
+                Line::from(vec!["System-wide, for every user ".fg(self.theme.text), self.keymap.key(Action::SystemDefault).fg(self.theme.key).bold()]),
+                Line::from(vec!["Just for me, in ~/.cups/lpoptions ".fg(self.theme.text), self.keymap.key(Action::UserDefault).fg(self.theme.key).bold()]),
+            ])
+            .block(block)
+            .render(popup, buf);
     }
 
     pub fn render(&mut self, area: Rect, buf: &mut Buffer) {
-        let instructions = self.get_instructions();
-        let edit_title = Line::from(" Printer TUI ".white().bold());
+        let instructions = self.get_instructions(area.width.saturating_sub(2));
+        let edit_title = Line::from(" Printer TUI ".fg(self.theme.text).bold());
         let edit_block = Block::default().borders(Borders::ALL)
             .title(edit_title.centered())
             .title_bottom(instructions.centered())
@@ -157,28 +830,42 @@ impl<'a> Printers<'a> {
         let layout = Layout::default()
                  .direction(Direction::Horizontal)
                  .constraints(vec![
-                     Constraint::Percentage(50),
-                     Constraint::Percentage(50),
+                     Constraint::Percentage(65),
+                     Constraint::Percentage(35),
                  ])
                  .split(inner_area);
 
 
-        let title = Line::from(if self.printers.len()>1 {" Printers "}else{" Printer "}.white().bold());
+        let title = Line::from(if self.printers.len()>1 {" Printers "}else{" Printer "}.fg(self.theme.text).bold());
 
         let block = Block::default()
             .title(title)
             .border_set(border::THICK)
-            .border_style(Style::default().fg(Color::White));
+            .border_style(self.theme.border(false));
 
         let widths = [
-            Constraint::Percentage(100),
+            Constraint::Percentage(24),
+            Constraint::Percentage(14),
+            Constraint::Percentage(20),
+            Constraint::Percentage(21),
+            Constraint::Percentage(21),
         ];   
 
+        let header = Row::new(vec![
+                Cell::from("Name"),
+                Cell::from("State"),
+                Cell::from("Message"),
+                Cell::from("Description"),
+                Cell::from("Location"),
+            ])
+            .style(Style::new().bold());
+
         let table = Table::new(self.printers_to_rows(),widths) 
+            .header(header)
             .column_spacing(1)
-            .row_highlight_style(Style::new().yellow())
-            .cell_highlight_style(Style::new().blue())
-            .highlight_symbol("  ")
+            .row_highlight_style(self.theme.highlight())
+            .cell_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
             .block(block);
 
         StatefulWidget::render(
@@ -188,26 +875,57 @@ impl<'a> Printers<'a> {
             &mut self.state,
         );
         
-        let title = Line::from(" Options ".white().bold());
+        let title = Line::from(" Options ".fg(self.theme.text).bold());
         let block = Block::bordered()
             .title(title.centered())
             .border_set(border::THICK)
-            .border_style(Style::default().fg(Color::White));
+            .border_style(self.theme.border(self.selected_block == ViewBlock::Options));
 
         let widths = [
-            Constraint::Percentage(100),
+            Constraint::Percentage(40),
+            Constraint::Percentage(60),
         ];   
 
+        let header = Row::new(vec![
+                Cell::from("Name"),
+                Cell::from("Value"),
+            ])
+            .style(Style::new().bold())
+            .bottom_margin(1);
+
         let table = Table::new(self.options_to_rows(),widths) 
+            .header(header)
             .column_spacing(1)
+            .row_highlight_style(self.theme.highlight())
+            .highlight_symbol("  ")
             .block(block);
 
         StatefulWidget::render(
             table,
             layout[1],
             buf,
-            &mut self.state,
+            &mut self.options_state,
         );
+
+        if self.selected_block == ViewBlock::Options && self.selected_edit_mode == EditMode::Edit {
+            self.render_option_editor(layout[1], buf);
+        }
+
+        if let Some(action) = self.reason_prompt {
+            self.render_reason_prompt(action, layout[0], buf);
+        }
+
+        if self.default_prompt {
+            self.render_default_prompt(layout[0], buf);
+        }
+
+        if let Some(browser) = self.file_browser {
+            browser.render(self.theme, area, buf);
+        }
+
+        if self.help {
+            self.keymap.render_help(self.theme, self.context().0, area, buf);
+        }
     }
 }
 //
@@ -0,0 +1,251 @@
+use std::collections::BTreeMap;
+use ratatui::style::{Color, Modifier, Style};
+use serde::Deserialize;
+
+use crate::cups::printer::PrinterState;
+
+/// The colors of the TUI. Colors left to `Reset` fall back on a modifier
+/// where it matters, so the monochrome theme keeps things apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Titles, labels and typed text.
+    pub text: Color,
+    /// Borders of the blocks out of focus.
+    pub border: Color,
+    /// Borders of the focused block and of popups.
+    pub focus: Color,
+    /// The selected row.
+    pub highlight: Color,
+    /// Keys in the instructions.
+    pub key: Color,
+    /// Errors, stopped printers and jobs, removed values.
+    pub error: Color,
+    /// Idle printers, recommended drivers, added values.
+    pub success: Color,
+    /// Busy printers and jobs, search matches, URIs.
+    pub active: Color,
+    /// Placeholders of empty fields.
+    pub muted: Color,
+    /// Class names in the printers list.
+    pub class: Color,
+    /// Directories in the file browser.
+    pub directory: Color,
+}
+
+/// The dark theme, or monochrome when `NO_COLOR` is set.
+impl Default for Theme {
+    fn default() -> Self {
+        if no_color() {
+            Theme::MONOCHROME
+        } else {
+            Theme::DARK
+        }
+    }
+}
+
+/// Whether `NO_COLOR` asks for no colors, see https://no-color.org.
+fn no_color() -> bool {
+    std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+}
+
+/// A theme of the config file, changing some colors of a built-in one.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct CustomTheme {
+    #[serde(default = "CustomTheme::default_base")]
+    pub base: String,
+    /// Colors by name, like `focus = "light-blue"` or `key = "#5f87d7"`.
+    #[serde(flatten)]
+    pub colors: BTreeMap<String, Color>,
+}
+
+impl CustomTheme {
+    fn default_base() -> String {
+        "dark".to_string()
+    }
+}
+
+impl Theme {
+    pub const DARK: Theme = Theme {
+        text: Color::White,
+        border: Color::White,
+        focus: Color::Yellow,
+        highlight: Color::Yellow,
+        key: Color::Blue,
+        error: Color::Red,
+        success: Color::Green,
+        active: Color::Cyan,
+        muted: Color::DarkGray,
+        class: Color::Magenta,
+        directory: Color::Blue,
+    };
+
+    pub const LIGHT: Theme = Theme {
+        text: Color::Black,
+        border: Color::DarkGray,
+        focus: Color::Blue,
+        highlight: Color::Blue,
+        key: Color::Magenta,
+        error: Color::Red,
+        success: Color::Green,
+        active: Color::Blue,
+        muted: Color::Gray,
+        class: Color::Magenta,
+        directory: Color::Blue,
+    };
+
+    pub const HIGH_CONTRAST: Theme = Theme {
+        text: Color::White,
+        border: Color::White,
+        focus: Color::LightYellow,
+        highlight: Color::LightYellow,
+        key: Color::LightCyan,
+        error: Color::LightRed,
+        success: Color::LightGreen,
+        active: Color::LightCyan,
+        muted: Color::Gray,
+        class: Color::LightMagenta,
+        directory: Color::LightBlue,
+    };
+
+    pub const MONOCHROME: Theme = Theme {
+        text: Color::Reset,
+        border: Color::Reset,
+        focus: Color::Reset,
+        highlight: Color::Reset,
+        key: Color::Reset,
+        error: Color::Reset,
+        success: Color::Reset,
+        active: Color::Reset,
+        muted: Color::Reset,
+        class: Color::Reset,
+        directory: Color::Reset,
+    };
+
+    pub const BUILT_IN: [(&str, Theme); 4] = [
+        ("dark", Theme::DARK),
+        ("light", Theme::LIGHT),
+        ("high-contrast", Theme::HIGH_CONTRAST),
+        ("monochrome", Theme::MONOCHROME),
+    ];
+
+    /// The built-in or custom theme called `name`, monochrome whatever the
+    /// name when `NO_COLOR` is set.
+    pub fn new(name: &str, custom: &BTreeMap<String, CustomTheme>) -> Result<Theme, String> {
+        let builtin = |name: &str| Theme::BUILT_IN.iter()
+            .find(|(builtin, _)| *builtin == name)
+            .map(|(_, theme)| *theme);
+        let names = || Theme::BUILT_IN.iter()
+            .map(|(name, _)| name.to_string())
+            .chain(custom.keys().cloned())
+            .collect::<Vec<String>>()
+            .join(", ");
+        let theme = match (builtin(name), custom.get(name)) {
+            (Some(theme), _) => theme,
+            (None, Some(custom)) => {
+                let mut theme = builtin(&custom.base)
+                    .ok_or_else(|| format!("themes.{}.base: unknown theme \"{}\", expected a built-in one", name, custom.base))?;
+                for (color, value) in &custom.colors {
+                    *theme.color_mut(color)
+                        .ok_or_else(|| format!("themes.{}: unknown color \"{}\"", name, color))? = *value;
+                }
+                theme
+            }
+            (None, None) => return Err(format!("unknown theme \"{}\", expected one of {}", name, names())),
+        };
+        if no_color() {
+            return Ok(Theme::MONOCHROME);
+        }
+        Ok(theme)
+    }
+
+    fn color_mut(&mut self, name: &str) -> Option<&mut Color> {
+        Some(match name {
+            "text" => &mut self.text,
+            "border" => &mut self.border,
+            "focus" => &mut self.focus,
+            "highlight" => &mut self.highlight,
+            "key" => &mut self.key,
+            "error" => &mut self.error,
+            "success" => &mut self.success,
+            "active" => &mut self.active,
+            "muted" => &mut self.muted,
+            "class" => &mut self.class,
+            "directory" => &mut self.directory,
+            _ => return None,
+        })
+    }
+
+    /// `color` as foreground, or `fallback` when it is `Reset`.
+    fn style(color: Color, fallback: Modifier) -> Style {
+        if color == Color::Reset {
+            Style::new().add_modifier(fallback)
+        } else {
+            Style::new().fg(color)
+        }
+    }
+
+    pub fn text(&self) -> Style {
+        Style::new().fg(self.text)
+    }
+
+    /// The border of a block, standing out when `focused`.
+    pub fn border(&self, focused: bool) -> Style {
+        if focused {
+            Theme::style(self.focus, Modifier::BOLD)
+        } else {
+            Style::new().fg(self.border)
+        }
+    }
+
+    /// The border of popups.
+    pub fn popup(&self) -> Style {
+        self.border(true)
+    }
+
+    pub fn highlight(&self) -> Style {
+        Theme::style(self.highlight, Modifier::REVERSED)
+    }
+
+    /// The selected one of options laid out side by side.
+    pub fn selected(&self) -> Style {
+        Style::new().fg(self.highlight).add_modifier(Modifier::REVERSED)
+    }
+
+    pub fn key(&self) -> Style {
+        Style::new().fg(self.key).add_modifier(Modifier::BOLD)
+    }
+
+    pub fn error(&self) -> Style {
+        Theme::style(self.error, Modifier::BOLD)
+    }
+
+    pub fn success(&self) -> Style {
+        Style::new().fg(self.success)
+    }
+
+    pub fn active(&self) -> Style {
+        Theme::style(self.active, Modifier::UNDERLINED)
+    }
+
+    pub fn muted(&self) -> Style {
+        Theme::style(self.muted, Modifier::DIM)
+    }
+
+    /// Rows of printers and classes, by state.
+    pub fn printer_state(&self, state: PrinterState) -> Style {
+        match state {
+            PrinterState::Idle => self.success(),
+            PrinterState::Processing => self.active(),
+            PrinterState::Stopped => self.error(),
+            PrinterState::Unknown => self.text(),
+        }
+    }
+
+    pub fn class(&self) -> Style {
+        Theme::style(self.class, Modifier::ITALIC)
+    }
+
+    pub fn directory(&self) -> Style {
+        Theme::style(self.directory, Modifier::BOLD)
+    }
+}
@@ -0,0 +1,109 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::Stylize,
+    symbols::border,
+    text::Line,
+    widgets::{Block,Clear,Paragraph},
+};
+use ratatui::prelude::*;
+
+use crate::cups::uri::{self, UriField, UriParts, UriScheme};
+use crate::keymap::{self, Keymap};
+use crate::theme::Theme;
+use crate::utils::{popup_area, edit_text};
+use crossterm::event::KeyEvent;
+
+/// A form putting a device URI together from its parts, for devices
+/// `lpinfo -v` doesn't find.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UriBuilder {
+    pub scheme: UriScheme,
+    pub parts: UriParts,
+    /// Index into the scheme's fields.
+    pub field: usize,
+}
+
+impl UriBuilder {
+    pub fn uri(&self) -> String {
+        self.scheme.build(&self.parts)
+    }
+
+    fn current_field(&self) -> UriField {
+        let fields = self.scheme.fields();
+        fields[self.field.min(fields.len() - 1)]
+    }
+
+    pub fn next_scheme(&mut self) {
+        self.scheme = self.scheme.next();
+        self.field = 0;
+    }
+
+    pub fn previous_scheme(&mut self) {
+        self.scheme = self.scheme.previous();
+        self.field = 0;
+    }
+
+    pub fn next_field(&mut self) {
+        self.field = (self.field + 1) % self.scheme.fields().len();
+    }
+
+    pub fn previous_field(&mut self) {
+        let count = self.scheme.fields().len();
+        self.field = (self.field + count - 1) % count;
+    }
+
+    /// Types into the current field.
+    pub fn edit(&mut self, key_event: KeyEvent) {
+        let field = self.current_field();
+        edit_text(self.parts.field_mut(field), key_event);
+    }
+
+    pub fn render(&self, keymap: &Keymap, theme: &Theme, area: Rect, buf: &mut Buffer) {
+        let mut lines = vec![];
+
+        let mut schemes = vec![" Scheme ".fg(theme.text).bold()];
+        for scheme in UriScheme::ALL {
+            schemes.push(if scheme == self.scheme {
+                Span::styled(format!(" {} ", scheme), theme.selected())
+            } else {
+                format!(" {} ", scheme).fg(theme.text)
+            });
+        }
+        lines.push(Line::from(schemes));
+        lines.push(Line::from(""));
+
+        for (index, field) in self.scheme.fields().iter().enumerate() {
+            let value = self.parts.field(*field);
+            let value = if *field == UriField::Password {
+                "*".repeat(value.chars().count())
+            } else {
+                value.to_string()
+            };
+            let value = if index == self.field {
+                Span::styled(format!("{}_", value), theme.highlight())
+            } else if value.is_empty() {
+                Span::styled(self.scheme.placeholder(*field), theme.muted())
+            } else {
+                value.fg(theme.text)
+            };
+            lines.push(Line::from(vec![format!(" {:>9}: ", field.to_string()).fg(theme.text).bold(), value]));
+        }
+        lines.push(Line::from(""));
+
+        let uri = self.uri();
+        lines.push(Line::from(vec![" URI: ".fg(theme.text).bold(), Span::styled(uri::mask_credentials(&uri), theme.active())]));
+        if let Err(error) = uri::validate(&uri) {
+            lines.push(Line::from(Span::styled(format!(" {}", error), theme.error())));
+        }
+
+        let popup = popup_area(area, 70, lines.len() as u16 + 2);
+        let block = Block::bordered()
+            .title(Line::from(" Device URI ".bold()).centered())
+            .title_bottom(keymap.hints(theme, keymap::URI_BUILDER, true, popup.width.saturating_sub(2)).centered())
+            .border_set(border::THICK)
+            .border_style(theme.popup());
+        Clear.render(popup, buf);
+        Paragraph::new(lines).block(block).render(popup, buf);
+    }
+}
@@ -1,13 +1,21 @@
+use crossterm::event::{KeyCode,KeyEvent};
+use ratatui::layout::{Constraint,Flex,Layout,Rect};
+
 #[derive(Default,Debug, Clone, Copy, PartialEq)]
 pub enum TUIMode {
     #[default]
     View,
     Edit,
+    Jobs,
+    History,
+    Classes,
 }
 #[derive(Default,Debug, Clone, Copy, PartialEq)]
 pub enum EditBlock {
     #[default]
     Title,
+    Description,
+    Location,
     Devices,
     Drivers
 }
@@ -18,3 +26,102 @@ pub enum EditMode {
     Edit,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    Info(String),
+    Error(String),
+}
+#[derive(Default,Debug, Clone, Copy, PartialEq)]
+pub enum ViewBlock {
+    #[default]
+    Printers,
+    Options,
+}
+
+#[derive(Default,Debug, Clone, Copy, PartialEq)]
+pub enum ClassBlock {
+    #[default]
+    Classes,
+    Members,
+}
+
+/// A rectangle centered in `area`, `percent_x` of its width and `height` rows tall.
+pub fn popup_area(area: Rect, percent_x: u16, height: u16) -> Rect {
+    let [area] = Layout::vertical([Constraint::Length(height)]).flex(Flex::Center).areas(area);
+    let [area] = Layout::horizontal([Constraint::Percentage(percent_x)]).flex(Flex::Center).areas(area);
+    area
+}
+/// Printer actions prompting for an optional reason before they run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReasonAction {
+    Disable,
+    Reject,
+}
+/// The job history filter field being typed into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterField {
+    Printer,
+    User,
+    From,
+    To,
+}
+
+impl FilterField {
+    pub fn next(self) -> Self {
+        match self {
+            FilterField::Printer => FilterField::User,
+            FilterField::User => FilterField::From,
+            FilterField::From => FilterField::To,
+            FilterField::To => FilterField::Printer,
+        }
+    }
+}
+
+/// Matches `pattern` as a case-insensitive subsequence of `text`, returning a score
+/// (higher is better, consecutive and word-start matches weighing more) and the
+/// char positions matched.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let mut pattern = pattern.chars().filter(|c| !c.is_whitespace()).flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut positions = vec![];
+    let mut previous: Option<char> = None;
+    for (index, c) in text.chars().enumerate() {
+        let Some(wanted) = pattern.peek() else {
+            break;
+        };
+        if c.to_lowercase().eq(std::iter::once(*wanted)) {
+            score += 1;
+            if positions.last().is_some_and(|last| last + 1 == index) {
+                score += 5;
+            }
+            if previous.is_none_or(|previous| !previous.is_alphanumeric()) {
+                score += 3;
+            }
+            positions.push(index);
+            pattern.next();
+        }
+        previous = Some(c);
+    }
+    if pattern.peek().is_some() {
+        return None;
+    }
+    Some((score, positions))
+}
+
+/// Types a character into `text` or erases the last one, returning whether `text` changed.
+pub fn edit_text(text: &mut String, key_event: KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Char(c) => {
+            text.push(c);
+            true
+        }
+        KeyCode::Backspace => text.pop().is_some(),
+        _ => false,
+    }
+}
+
+/// The spinner frame to show at `tick`.
+pub fn spinner(tick: usize) -> &'static str {
+    const FRAMES: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+    FRAMES[tick % FRAMES.len()]
+}